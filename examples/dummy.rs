@@ -1,5 +1,3 @@
-use nalgebra;
-
 use nalgebra::Vector6;
 use rtsam::core::group::LieGroup;
 use rtsam::geometry::SE3;
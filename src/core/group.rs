@@ -3,7 +3,7 @@ use crate::core::manifold::Manifold;
 use nalgebra::allocator::Allocator;
 use nalgebra::{DefaultAllocator, DimName, OMatrix, OVector, Scalar};
 use std::fmt::Debug;
-use std::ops::Mul;
+use std::ops::{Mul, Neg};
 
 #[allow(non_snake_case)]
 pub trait LieGroup<N>: Debug + Sized + Copy
@@ -21,13 +21,70 @@ where
         self * g
     }
 
+    /// Compose with derivatives wrpt `self` (H1) and `g` (H2)
+    fn compose_with_derivative(
+        &self,
+        g: &Self,
+        H1: Option<&mut OMatrix<N, Self::D, Self::D>>,
+        H2: Option<&mut OMatrix<N, Self::D, Self::D>>,
+    ) -> Self
+    where
+        DefaultAllocator: Allocator<N, Self::D, Self::D>,
+    {
+        if let Some(H1) = H1 {
+            *H1 = g.inverse().adjoint_map();
+        }
+        if let Some(H2) = H2 {
+            *H2 = OMatrix::<N, Self::D, Self::D>::identity();
+        }
+
+        self.compose(g)
+    }
+
     fn between(&self, g: &Self) -> Self;
 
+    /// Between with derivatives wrpt `self` (H1) and `g` (H2)
+    fn between_with_derivative(
+        &self,
+        g: &Self,
+        H1: Option<&mut OMatrix<N, Self::D, Self::D>>,
+        H2: Option<&mut OMatrix<N, Self::D, Self::D>>,
+    ) -> Self
+    where
+        N: Neg<Output = N>,
+        DefaultAllocator: Allocator<N, Self::D, Self::D>,
+    {
+        let result = self.between(g);
+
+        if let Some(H1) = H1 {
+            *H1 = -result.inverse().adjoint_map();
+        }
+        if let Some(H2) = H2 {
+            *H2 = OMatrix::<N, Self::D, Self::D>::identity();
+        }
+
+        result
+    }
+
+    fn inverse(&self) -> Self;
+
+    /// Inverse with derivative wrpt `self`
+    fn inverse_with_derivative(&self, H: Option<&mut OMatrix<N, Self::D, Self::D>>) -> Self
+    where
+        N: Neg<Output = N>,
+        DefaultAllocator: Allocator<N, Self::D, Self::D>,
+    {
+        if let Some(H) = H {
+            *H = -self.adjoint_map();
+        }
+
+        self.inverse()
+    }
+
     fn adjoint_map(&self) -> OMatrix<N, Self::D, Self::D>
     where
         DefaultAllocator: Allocator<N, Self::D, Self::D>;
 
-    // TODO(fan): H now does not work
     fn logmap(R: &Self, H: Option<&mut OMatrix<N, Self::D, Self::D>>) -> OVector<N, Self::D>
     where
         DefaultAllocator: Allocator<N, Self::D> + Allocator<N, Self::D, Self::D>;
@@ -44,10 +101,63 @@ where
         DefaultAllocator: Allocator<N, Self::D> + Allocator<N, Self::D, Self::D>;
 }
 
+/// Checks the compose, between, inverse, local and retract derivatives of a group against
+/// numerical derivatives at `a`, `b` and the tangent vector `v`
+#[cfg(test)]
+#[allow(non_snake_case)]
+pub(crate) fn check_group_derivatives<D, G>(a: &G, b: &G, v: &OVector<f64, D>)
+where
+    D: DimName,
+    G: LieGroup<f64, D = D> + Manifold<Field = f64, TangentDim = D>,
+    for<'a> &'a G: Mul<G, Output = G>,
+    for<'a, 'b> &'a G: Mul<&'b G, Output = G>,
+    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+{
+    use crate::core::numerical_derivative::{
+        numerical_derivative11, numerical_derivative21, numerical_derivative22, DEFAULT_DELTA,
+    };
+
+    let delta = DEFAULT_DELTA;
+    let (mut H1, mut H2) = (OMatrix::<f64, D, D>::zeros(), OMatrix::<f64, D, D>::zeros());
+
+    a.compose_with_derivative(b, Some(&mut H1), Some(&mut H2));
+    let compose = |x: &G, y: &G| x.compose(y);
+    let expected_H1 = numerical_derivative21(compose, a, b, delta);
+    let expected_H2 = numerical_derivative22(compose, a, b, delta);
+    assert_relative_eq!((&H1 - expected_H1).norm(), 0.0, epsilon = 1e-6);
+    assert_relative_eq!((&H2 - expected_H2).norm(), 0.0, epsilon = 1e-6);
+
+    a.between_with_derivative(b, Some(&mut H1), Some(&mut H2));
+    let between = |x: &G, y: &G| x.between(y);
+    let expected_H1 = numerical_derivative21(between, a, b, delta);
+    let expected_H2 = numerical_derivative22(between, a, b, delta);
+    assert_relative_eq!((&H1 - expected_H1).norm(), 0.0, epsilon = 1e-6);
+    assert_relative_eq!((&H2 - expected_H2).norm(), 0.0, epsilon = 1e-6);
+
+    a.inverse_with_derivative(Some(&mut H1));
+    let expected_H1 = numerical_derivative11(|x: &G| x.inverse(), a, delta);
+    assert_relative_eq!((&H1 - expected_H1).norm(), 0.0, epsilon = 1e-6);
+
+    G::local_with_derivative(a, b, Some(&mut H1), Some(&mut H2));
+    let local = |x: &G, y: &G| G::local(x, y);
+    let expected_H1 = numerical_derivative21(local, a, b, delta);
+    let expected_H2 = numerical_derivative22(local, a, b, delta);
+    assert_relative_eq!((&H1 - expected_H1).norm(), 0.0, epsilon = 1e-6);
+    assert_relative_eq!((&H2 - expected_H2).norm(), 0.0, epsilon = 1e-6);
+
+    G::retract_with_derivative(a, v, Some(&mut H1), Some(&mut H2));
+    let retract = |x: &G, v: &OVector<f64, D>| G::retract(x, v);
+    let expected_H1 = numerical_derivative21(retract, a, v, delta);
+    let expected_H2 = numerical_derivative22(retract, a, v, delta);
+    assert_relative_eq!((&H1 - expected_H1).norm(), 0.0, epsilon = 1e-6);
+    assert_relative_eq!((&H2 - expected_H2).norm(), 0.0, epsilon = 1e-6);
+}
+
 #[cfg(test)]
+#[allow(non_snake_case)]
 mod tests {
+    use crate::core::numerical_derivative::{numerical_derivative11, DEFAULT_DELTA};
     use crate::geometry::so3::*;
-    use finitediff::FiniteDiff;
     use nalgebra::{Matrix3, Vector3};
     use std::f64::consts::PI;
 
@@ -65,7 +175,7 @@ mod tests {
     #[test]
     fn between_works() {
         let r1 = SO3::<f64>::from_axis_angle(&Vector3::x_axis(), 1. * PI);
-        let r2 = SO3::<f64>::from_axis_angle(&Vector3::x_axis(), -1. * PI);
+        let r2 = SO3::<f64>::from_axis_angle(&Vector3::x_axis(), -PI);
 
         assert_relative_eq!(
             (r1.between(&r2).matrix() - SO3::identity().matrix()).norm(),
@@ -86,33 +196,18 @@ mod tests {
         );
     }
 
-    // Left trivialized Derivative of exp(w) wrpt w:
-    // How does exp(w) change when w changes?
-    // We find a y such that: exp(w) exp(y) = exp(w + dw) for dw --> 0
-    // => y = log (exp(-w) * exp(w+dw))
-    fn dexp_numeric(w: Vector3<f64>, dw: Vector3<f64>) -> Vector3<f64> {
-        SO3::logmap(&(SO3::expmap(&-w) * SO3::expmap(&(w + dw))), None)
-    }
-
+    // Left trivialized derivative of exp(w) wrpt w: the y with exp(w) exp(y) = exp(w + dw)
+    // for dw --> 0, i.e. y = log(exp(-w) exp(w + dw))
     #[test]
     fn expmap_works_1() {
         let w = Vector3::new(0.1, 0.27, -0.2);
         let mut actual_dexp = Matrix3::<f64>::identity();
 
-        let f = |x: &Vec<f64>| -> Vec<f64> {
-            let arr: [f64; 3] = dexp_numeric(w, Vector3::new(x[0], x[1], x[2])).into();
-            arr.to_vec()
-        };
-
-        let w_: [f64; 3] = w.into();
-        let expected_dexp_ = w_.to_vec().forward_jacobian(&f);
-        let expected_dexp: Matrix3<f64> =
-            Matrix3::from_iterator(expected_dexp_.iter().flatten().cloned());
-
         SO3::expmap_with_derivative(&w, Some(&mut actual_dexp));
 
-        println!("Norm: {} vs {}", expected_dexp.norm(), actual_dexp.norm());
-        assert_relative_eq!((actual_dexp - expected_dexp).norm(), 0.0, epsilon = 0.02);
+        let expected_dexp =
+            numerical_derivative11(|w: &Vector3<f64>| SO3::expmap(w), &w, DEFAULT_DELTA);
+        assert_relative_eq!((actual_dexp - expected_dexp).norm(), 0.0, epsilon = 1e-6);
     }
 
     #[test]
@@ -120,19 +215,11 @@ mod tests {
         let w = Vector3::new(10., 20., 30.);
         let mut actual_dexp = Matrix3::<f64>::identity();
 
-        let f = |x: &Vec<f64>| -> Vec<f64> {
-            let arr: [f64; 3] = dexp_numeric(w, Vector3::new(x[0], x[1], x[2])).into();
-            arr.to_vec()
-        };
-
-        let w_: [f64; 3] = w.into();
-        let expected_dexp_ = w_.to_vec().central_jacobian(&f);
-        let expected_dexp: Matrix3<f64> =
-            Matrix3::from_iterator(expected_dexp_.iter().flatten().cloned());
-
         SO3::expmap_with_derivative(&w, Some(&mut actual_dexp));
 
-        assert_relative_eq!((actual_dexp - expected_dexp).norm(), 0.0, epsilon = 0.01);
+        let expected_dexp =
+            numerical_derivative11(|w: &Vector3<f64>| SO3::expmap(w), &w, DEFAULT_DELTA);
+        assert_relative_eq!((actual_dexp - expected_dexp).norm(), 0.0, epsilon = 1e-6);
     }
 
     #[test]
@@ -0,0 +1,737 @@
+use approx::{AbsDiffEq, RelativeEq, UlpsEq};
+use nalgebra::{ComplexField, Field, RealField, SMatrix, SVector, SimdValue};
+use num::{FromPrimitive, Num, One, Signed, Zero};
+use simba::scalar::SubsetOf;
+use std::fmt;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign,
+};
+
+/// A dual number `a + v * eps` with `D` infinitesimal parts, for forward-mode automatic
+/// differentiation.
+///
+/// `Jet` implements `RealField`, so it can be put through any code generic over the scalar,
+/// e.g. `skew_symmetric` or the `LieGroup` maps, and `v` carries the derivatives of the result
+/// with respect to up to `D` seeded inputs. Comparisons only look at the real part `a`.
+#[derive(Debug, Clone, Copy)]
+pub struct Jet<const D: usize> {
+    /// The real part
+    pub a: f64,
+    /// The infinitesimal part, i.e. the gradient of `a`
+    pub v: SVector<f64, D>,
+}
+
+impl<const D: usize> Jet<D> {
+    pub fn new(a: f64, v: SVector<f64, D>) -> Self {
+        Jet { a, v }
+    }
+
+    /// A jet with zero derivative
+    pub fn constant(a: f64) -> Self {
+        Jet {
+            a,
+            v: SVector::zeros(),
+        }
+    }
+
+    /// The `i`-th input variable, i.e. a jet with derivative `e_i`
+    pub fn variable(a: f64, i: usize) -> Self {
+        let mut v = SVector::zeros();
+        v[i] = 1.0;
+        Jet { a, v }
+    }
+
+    /// Applies a scalar function with value `f` and derivative `df` at `a`
+    #[inline]
+    fn chain(self, f: f64, df: f64) -> Self {
+        // Keep constants constant, even where the derivative blows up (e.g. sqrt at 0)
+        if self.v.is_zero() {
+            return Jet::constant(f);
+        }
+
+        Jet {
+            a: f,
+            v: self.v * df,
+        }
+    }
+}
+
+/// Evaluates `f` at `x` and returns its value together with its Jacobian wrpt `x`.
+///
+/// `f` is evaluated once, on jets seeded with the unit vectors.
+pub fn jacobian<F, const M: usize, const R: usize>(
+    f: F,
+    x: &SVector<f64, M>,
+) -> (SVector<f64, R>, SMatrix<f64, R, M>)
+where
+    F: Fn(&SVector<Jet<M>, M>) -> SVector<Jet<M>, R>,
+{
+    let y = f(&SVector::from_fn(|i, _| Jet::variable(x[i], i)));
+
+    let value = y.map(|y| y.a);
+    let jacobian = SMatrix::from_fn(|i, j| y[i].v[j]);
+
+    (value, jacobian)
+}
+
+impl<const D: usize> PartialEq for Jet<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.a == other.a
+    }
+}
+
+impl<const D: usize> PartialOrd for Jet<D> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.a.partial_cmp(&other.a)
+    }
+}
+
+impl<const D: usize> fmt::Display for Jet<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} + {:?}eps", self.a, self.v.as_slice())
+    }
+}
+
+impl<const D: usize> Add for Jet<D> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Jet::new(self.a + rhs.a, self.v + rhs.v)
+    }
+}
+
+impl<const D: usize> Sub for Jet<D> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Jet::new(self.a - rhs.a, self.v - rhs.v)
+    }
+}
+
+impl<const D: usize> Mul for Jet<D> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Jet::new(self.a * rhs.a, self.v * rhs.a + rhs.v * self.a)
+    }
+}
+
+impl<const D: usize> Div for Jet<D> {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let a = self.a / rhs.a;
+        Jet::new(a, (self.v - rhs.v * a) / rhs.a)
+    }
+}
+
+impl<const D: usize> Rem for Jet<D> {
+    type Output = Self;
+
+    /// `a % b = a - trunc(a / b) * b`, with the truncation having zero derivative
+    fn rem(self, rhs: Self) -> Self {
+        let q = (self.a / rhs.a).trunc();
+        Jet::new(self.a % rhs.a, self.v - rhs.v * q)
+    }
+}
+
+impl<const D: usize> Neg for Jet<D> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Jet::new(-self.a, -self.v)
+    }
+}
+
+macro_rules! impl_assign_op(
+    ($($Trait: ident, $method: ident, $op: tt);*) => ($(
+        impl<const D: usize> $Trait for Jet<D> {
+            fn $method(&mut self, rhs: Self) {
+                *self = *self $op rhs;
+            }
+        }
+    )*)
+);
+
+impl_assign_op!(
+    AddAssign, add_assign, +;
+    SubAssign, sub_assign, -;
+    MulAssign, mul_assign, *;
+    DivAssign, div_assign, /;
+    RemAssign, rem_assign, %
+);
+
+impl<const D: usize> Zero for Jet<D> {
+    fn zero() -> Self {
+        Jet::constant(0.0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.a == 0.0 && self.v.is_zero()
+    }
+}
+
+impl<const D: usize> One for Jet<D> {
+    fn one() -> Self {
+        Jet::constant(1.0)
+    }
+}
+
+impl<const D: usize> Num for Jet<D> {
+    type FromStrRadixErr = <f64 as Num>::FromStrRadixErr;
+
+    fn from_str_radix(str: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        f64::from_str_radix(str, radix).map(Jet::constant)
+    }
+}
+
+impl<const D: usize> Signed for Jet<D> {
+    fn abs(&self) -> Self {
+        if self.a < 0.0 {
+            -*self
+        } else {
+            *self
+        }
+    }
+
+    fn abs_sub(&self, other: &Self) -> Self {
+        if self.a <= other.a {
+            Jet::zero()
+        } else {
+            *self - *other
+        }
+    }
+
+    fn signum(&self) -> Self {
+        Jet::constant(self.a.signum())
+    }
+
+    fn is_positive(&self) -> bool {
+        self.a.is_sign_positive()
+    }
+
+    fn is_negative(&self) -> bool {
+        self.a.is_sign_negative()
+    }
+}
+
+impl<const D: usize> FromPrimitive for Jet<D> {
+    fn from_i64(n: i64) -> Option<Self> {
+        Some(Jet::constant(n as f64))
+    }
+
+    fn from_u64(n: u64) -> Option<Self> {
+        Some(Jet::constant(n as f64))
+    }
+
+    fn from_f64(n: f64) -> Option<Self> {
+        Some(Jet::constant(n))
+    }
+}
+
+impl<const D: usize> AbsDiffEq for Jet<D> {
+    type Epsilon = Self;
+
+    fn default_epsilon() -> Self {
+        Jet::constant(f64::default_epsilon())
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self) -> bool {
+        self.a.abs_diff_eq(&other.a, epsilon.a)
+    }
+}
+
+impl<const D: usize> RelativeEq for Jet<D> {
+    fn default_max_relative() -> Self {
+        Jet::constant(f64::default_max_relative())
+    }
+
+    fn relative_eq(&self, other: &Self, epsilon: Self, max_relative: Self) -> bool {
+        self.a.relative_eq(&other.a, epsilon.a, max_relative.a)
+    }
+}
+
+impl<const D: usize> UlpsEq for Jet<D> {
+    fn default_max_ulps() -> u32 {
+        f64::default_max_ulps()
+    }
+
+    fn ulps_eq(&self, other: &Self, epsilon: Self, max_ulps: u32) -> bool {
+        self.a.ulps_eq(&other.a, epsilon.a, max_ulps)
+    }
+}
+
+impl<const D: usize> SimdValue for Jet<D> {
+    type Element = Self;
+    type SimdBool = bool;
+
+    fn lanes() -> usize {
+        1
+    }
+
+    fn splat(val: Self) -> Self {
+        val
+    }
+
+    fn extract(&self, _: usize) -> Self {
+        *self
+    }
+
+    unsafe fn extract_unchecked(&self, _: usize) -> Self {
+        *self
+    }
+
+    fn replace(&mut self, _: usize, val: Self) {
+        *self = val
+    }
+
+    unsafe fn replace_unchecked(&mut self, _: usize, val: Self) {
+        *self = val
+    }
+
+    fn select(self, cond: bool, other: Self) -> Self {
+        if cond {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+impl<const D: usize> Field for Jet<D> {}
+
+impl<const D: usize> SubsetOf<Jet<D>> for Jet<D> {
+    fn to_superset(&self) -> Self {
+        *self
+    }
+
+    fn from_superset_unchecked(element: &Self) -> Self {
+        *element
+    }
+
+    fn is_in_subset(_: &Self) -> bool {
+        true
+    }
+}
+
+impl<const D: usize> SubsetOf<Jet<D>> for f64 {
+    fn to_superset(&self) -> Jet<D> {
+        Jet::constant(*self)
+    }
+
+    fn from_superset_unchecked(element: &Jet<D>) -> f64 {
+        element.a
+    }
+
+    fn is_in_subset(element: &Jet<D>) -> bool {
+        element.v.is_zero()
+    }
+}
+
+impl<const D: usize> ComplexField for Jet<D> {
+    type RealField = Self;
+
+    fn from_real(re: Self) -> Self {
+        re
+    }
+
+    fn real(self) -> Self {
+        self
+    }
+
+    fn imaginary(self) -> Self {
+        Jet::zero()
+    }
+
+    fn modulus(self) -> Self {
+        Signed::abs(&self)
+    }
+
+    fn modulus_squared(self) -> Self {
+        self * self
+    }
+
+    fn argument(self) -> Self {
+        if self.a >= 0.0 {
+            Jet::zero()
+        } else {
+            Jet::pi()
+        }
+    }
+
+    fn norm1(self) -> Self {
+        Signed::abs(&self)
+    }
+
+    fn scale(self, factor: Self) -> Self {
+        self * factor
+    }
+
+    fn unscale(self, factor: Self) -> Self {
+        self / factor
+    }
+
+    fn floor(self) -> Self {
+        Jet::constant(self.a.floor())
+    }
+
+    fn ceil(self) -> Self {
+        Jet::constant(self.a.ceil())
+    }
+
+    fn round(self) -> Self {
+        Jet::constant(self.a.round())
+    }
+
+    fn trunc(self) -> Self {
+        Jet::constant(self.a.trunc())
+    }
+
+    fn fract(self) -> Self {
+        Jet::new(self.a.fract(), self.v)
+    }
+
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        self * a + b
+    }
+
+    fn abs(self) -> Self {
+        Signed::abs(&self)
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        (self * self + other * other).sqrt()
+    }
+
+    fn recip(self) -> Self {
+        Jet::one() / self
+    }
+
+    fn conjugate(self) -> Self {
+        self
+    }
+
+    fn sin(self) -> Self {
+        self.chain(self.a.sin(), self.a.cos())
+    }
+
+    fn cos(self) -> Self {
+        self.chain(self.a.cos(), -self.a.sin())
+    }
+
+    fn sin_cos(self) -> (Self, Self) {
+        (self.sin(), self.cos())
+    }
+
+    fn tan(self) -> Self {
+        let t = self.a.tan();
+        self.chain(t, 1.0 + t * t)
+    }
+
+    fn asin(self) -> Self {
+        self.chain(self.a.asin(), 1.0 / (1.0 - self.a * self.a).sqrt())
+    }
+
+    fn acos(self) -> Self {
+        self.chain(self.a.acos(), -1.0 / (1.0 - self.a * self.a).sqrt())
+    }
+
+    fn atan(self) -> Self {
+        self.chain(self.a.atan(), 1.0 / (1.0 + self.a * self.a))
+    }
+
+    fn sinh(self) -> Self {
+        self.chain(self.a.sinh(), self.a.cosh())
+    }
+
+    fn cosh(self) -> Self {
+        self.chain(self.a.cosh(), self.a.sinh())
+    }
+
+    fn tanh(self) -> Self {
+        let t = self.a.tanh();
+        self.chain(t, 1.0 - t * t)
+    }
+
+    fn asinh(self) -> Self {
+        self.chain(self.a.asinh(), 1.0 / (self.a * self.a + 1.0).sqrt())
+    }
+
+    fn acosh(self) -> Self {
+        self.chain(self.a.acosh(), 1.0 / (self.a * self.a - 1.0).sqrt())
+    }
+
+    fn atanh(self) -> Self {
+        self.chain(self.a.atanh(), 1.0 / (1.0 - self.a * self.a))
+    }
+
+    fn log(self, base: Self) -> Self {
+        self.ln() / base.ln()
+    }
+
+    fn log2(self) -> Self {
+        self.chain(self.a.log2(), 1.0 / (self.a * std::f64::consts::LN_2))
+    }
+
+    fn log10(self) -> Self {
+        self.chain(self.a.log10(), 1.0 / (self.a * std::f64::consts::LN_10))
+    }
+
+    fn ln(self) -> Self {
+        self.chain(self.a.ln(), 1.0 / self.a)
+    }
+
+    fn ln_1p(self) -> Self {
+        self.chain(self.a.ln_1p(), 1.0 / (1.0 + self.a))
+    }
+
+    fn sqrt(self) -> Self {
+        let s = self.a.sqrt();
+        self.chain(s, 0.5 / s)
+    }
+
+    fn exp(self) -> Self {
+        let e = self.a.exp();
+        self.chain(e, e)
+    }
+
+    fn exp2(self) -> Self {
+        let e = self.a.exp2();
+        self.chain(e, e * std::f64::consts::LN_2)
+    }
+
+    fn exp_m1(self) -> Self {
+        self.chain(self.a.exp_m1(), self.a.exp())
+    }
+
+    fn powi(self, n: i32) -> Self {
+        self.chain(self.a.powi(n), f64::from(n) * self.a.powi(n - 1))
+    }
+
+    fn powf(self, n: Self) -> Self {
+        let p = self.a.powf(n.a);
+        let res = self.chain(p, n.a * self.a.powf(n.a - 1.0));
+
+        // The exponent only contributes if it is not a constant, and then needs a > 0
+        if n.v.is_zero() {
+            res
+        } else {
+            Jet::new(p, res.v + n.v * (p * self.a.ln()))
+        }
+    }
+
+    fn powc(self, n: Self) -> Self {
+        self.powf(n)
+    }
+
+    fn cbrt(self) -> Self {
+        let c = self.a.cbrt();
+        self.chain(c, 1.0 / (3.0 * c * c))
+    }
+
+    fn is_finite(&self) -> bool {
+        self.a.is_finite() && self.v.iter().all(|v| v.is_finite())
+    }
+
+    fn try_sqrt(self) -> Option<Self> {
+        if self.a >= 0.0 {
+            Some(self.sqrt())
+        } else {
+            None
+        }
+    }
+}
+
+impl<const D: usize> RealField for Jet<D> {
+    fn is_sign_positive(&self) -> bool {
+        self.a.is_sign_positive()
+    }
+
+    fn is_sign_negative(&self) -> bool {
+        self.a.is_sign_negative()
+    }
+
+    fn copysign(self, sign: Self) -> Self {
+        if self.a.is_sign_negative() == sign.a.is_sign_negative() {
+            self
+        } else {
+            -self
+        }
+    }
+
+    fn max(self, other: Self) -> Self {
+        if self.a >= other.a {
+            self
+        } else {
+            other
+        }
+    }
+
+    fn min(self, other: Self) -> Self {
+        if self.a <= other.a {
+            self
+        } else {
+            other
+        }
+    }
+
+    fn clamp(self, min: Self, max: Self) -> Self {
+        RealField::min(RealField::max(self, min), max)
+    }
+
+    /// `atan2(y, x)` with `self` as `y`
+    fn atan2(self, other: Self) -> Self {
+        let r2 = self.a * self.a + other.a * other.a;
+        Jet::new(
+            self.a.atan2(other.a),
+            (self.v * other.a - other.v * self.a) / r2,
+        )
+    }
+
+    fn min_value() -> Option<Self> {
+        Some(Jet::constant(f64::MIN))
+    }
+
+    fn max_value() -> Option<Self> {
+        Some(Jet::constant(f64::MAX))
+    }
+
+    fn pi() -> Self {
+        Jet::constant(std::f64::consts::PI)
+    }
+
+    fn two_pi() -> Self {
+        Jet::constant(2.0 * std::f64::consts::PI)
+    }
+
+    fn frac_pi_2() -> Self {
+        Jet::constant(std::f64::consts::FRAC_PI_2)
+    }
+
+    fn frac_pi_3() -> Self {
+        Jet::constant(std::f64::consts::FRAC_PI_3)
+    }
+
+    fn frac_pi_4() -> Self {
+        Jet::constant(std::f64::consts::FRAC_PI_4)
+    }
+
+    fn frac_pi_6() -> Self {
+        Jet::constant(std::f64::consts::FRAC_PI_6)
+    }
+
+    fn frac_pi_8() -> Self {
+        Jet::constant(std::f64::consts::FRAC_PI_8)
+    }
+
+    fn frac_1_pi() -> Self {
+        Jet::constant(std::f64::consts::FRAC_1_PI)
+    }
+
+    fn frac_2_pi() -> Self {
+        Jet::constant(std::f64::consts::FRAC_2_PI)
+    }
+
+    fn frac_2_sqrt_pi() -> Self {
+        Jet::constant(std::f64::consts::FRAC_2_SQRT_PI)
+    }
+
+    fn e() -> Self {
+        Jet::constant(std::f64::consts::E)
+    }
+
+    fn log2_e() -> Self {
+        Jet::constant(std::f64::consts::LOG2_E)
+    }
+
+    fn log10_e() -> Self {
+        Jet::constant(std::f64::consts::LOG10_E)
+    }
+
+    fn ln_2() -> Self {
+        Jet::constant(std::f64::consts::LN_2)
+    }
+
+    fn ln_10() -> Self {
+        Jet::constant(std::f64::consts::LN_10)
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use crate::core::group::LieGroup;
+    use crate::core::matrix::skew_symmetric_v;
+    use crate::geometry::se3::{se3_right_jacobian_inverse, SE3};
+    use crate::geometry::so3::SO3;
+    use nalgebra::{convert, Vector3, Vector6};
+
+    #[test]
+    fn scalar_derivatives_work() {
+        let x = Jet::<1>::variable(0.7, 0);
+
+        assert_relative_eq!((x * x).v[0], 1.4);
+        assert_relative_eq!((Jet::one() / x).v[0], -1.0 / 0.49);
+        assert_relative_eq!(x.sin().v[0], 0.7f64.cos());
+        assert_relative_eq!(x.acos().v[0], -1.0 / (1.0 - 0.49f64).sqrt());
+        assert_relative_eq!(x.sqrt().v[0], 0.5 / 0.7f64.sqrt());
+        assert_relative_eq!(x.powf(convert(3.0)).v[0], 3.0 * 0.49);
+        assert_relative_eq!(x.powf(x).v[0], 0.7f64.powf(0.7) * (0.7f64.ln() + 1.0));
+        assert_relative_eq!(
+            RealField::atan2(x, Jet::constant(2.0)).v[0],
+            2.0 / (4.0 + 0.49)
+        );
+
+        // Constants stay constant even where the derivative is singular
+        assert!(Jet::<1>::zero().sqrt().v.is_zero());
+    }
+
+    #[test]
+    fn jacobian_of_skew_symmetric() {
+        let w = Vector3::new(0.3, -1.2, 2.0);
+        let v = Vector3::new(1.0, 0.5, -0.2);
+
+        let (value, J) = jacobian(|v| skew_symmetric_v(v) * w.map(Jet::constant), &v);
+
+        assert_relative_eq!((value - v.cross(&w)).norm(), 0.0, epsilon = 1e-12);
+        assert_relative_eq!((J + skew_symmetric_v(&w)).norm(), 0.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn jacobian_of_so3_expmap() {
+        let w = Vector3::new(0.1, -0.4, 0.3);
+        let p = Vector3::new(1.0, 2.0, 3.0);
+
+        let (value, J) = jacobian(|w| SO3::expmap(w) * p.map(Jet::constant), &w);
+
+        // d(R(w) p) / dw = -R [p]x Jr(w)
+        let mut Jr = nalgebra::Matrix3::zeros();
+        let R = SO3::expmap_with_derivative(&w, Some(&mut Jr));
+        assert_relative_eq!((value - R * p).norm(), 0.0, epsilon = 1e-12);
+        assert_relative_eq!(
+            (J + R.matrix() * skew_symmetric_v(&p) * Jr).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn jacobian_of_se3_logmap() {
+        let xi = Vector6::new(0.1, -0.4, 0.3, 1.0, 2.0, -0.5);
+        let T = SE3::expmap(&xi.map(Jet::constant));
+
+        // The derivative of log(T * exp(d)) at d = 0 is the inverse of the right Jacobian
+        let (value, J) = jacobian(
+            |d| SE3::logmap(&(T * SE3::expmap(d)), None),
+            &Vector6::zeros(),
+        );
+
+        assert_relative_eq!((value - xi).norm(), 0.0, epsilon = 1e-12);
+        assert_relative_eq!(
+            (J - se3_right_jacobian_inverse(&xi)).norm(),
+            0.0,
+            epsilon = 1e-8
+        );
+    }
+}
@@ -1,7 +1,186 @@
-pub trait Manifold {
-    type TangentVector;
+use crate::core::group::LieGroup;
+use nalgebra::allocator::Allocator;
+use nalgebra::{DefaultAllocator, DimName, OMatrix, OVector, RealField};
+use std::ops::Mul;
 
-    fn local(origin: &Self, other: &Self) -> Self::TangentVector;
+#[allow(non_snake_case)]
+pub trait Manifold: Sized {
+    /// Scalar type of the tangent space
+    type Field: RealField + Copy;
 
-    fn retract(origin: &Self, v: &Self::TangentVector) -> Self;
+    /// Dimension of the tangent space
+    type TangentDim: DimName;
+
+    fn dim() -> usize {
+        Self::TangentDim::dim()
+    }
+
+    fn local(origin: &Self, other: &Self) -> OVector<Self::Field, Self::TangentDim>
+    where
+        DefaultAllocator: Allocator<Self::Field, Self::TangentDim>;
+
+    fn retract(origin: &Self, v: &OVector<Self::Field, Self::TangentDim>) -> Self
+    where
+        DefaultAllocator: Allocator<Self::Field, Self::TangentDim>;
+
+    /// Local coordinates with derivatives wrpt `origin` (H1) and `other` (H2)
+    fn local_with_derivative(
+        origin: &Self,
+        other: &Self,
+        H1: Option<&mut OMatrix<Self::Field, Self::TangentDim, Self::TangentDim>>,
+        H2: Option<&mut OMatrix<Self::Field, Self::TangentDim, Self::TangentDim>>,
+    ) -> OVector<Self::Field, Self::TangentDim>
+    where
+        DefaultAllocator: Allocator<Self::Field, Self::TangentDim>
+            + Allocator<Self::Field, Self::TangentDim, Self::TangentDim>;
+
+    /// Retraction with derivatives wrpt `origin` (H1) and `v` (H2)
+    fn retract_with_derivative(
+        origin: &Self,
+        v: &OVector<Self::Field, Self::TangentDim>,
+        H1: Option<&mut OMatrix<Self::Field, Self::TangentDim, Self::TangentDim>>,
+        H2: Option<&mut OMatrix<Self::Field, Self::TangentDim, Self::TangentDim>>,
+    ) -> Self
+    where
+        DefaultAllocator: Allocator<Self::Field, Self::TangentDim>
+            + Allocator<Self::Field, Self::TangentDim, Self::TangentDim>;
+}
+
+/// `Manifold::local_with_derivative` for a Lie group with `local(x, y) = log(x⁻¹ y)`
+#[allow(non_snake_case)]
+pub fn group_local_with_derivative<N, D, G>(
+    origin: &G,
+    other: &G,
+    H1: Option<&mut OMatrix<N, D, D>>,
+    H2: Option<&mut OMatrix<N, D, D>>,
+) -> OVector<N, D>
+where
+    N: RealField + Copy,
+    D: DimName,
+    G: LieGroup<N, D = D> + Manifold<Field = N, TangentDim = D>,
+    for<'a> &'a G: Mul<G, Output = G>,
+    for<'a, 'b> &'a G: Mul<&'b G, Output = G>,
+    DefaultAllocator: Allocator<N, D> + Allocator<N, D, D>,
+{
+    let (mut D_b1, mut D_b2, mut D_log) = (
+        OMatrix::<N, D, D>::zeros(),
+        OMatrix::<N, D, D>::zeros(),
+        OMatrix::<N, D, D>::zeros(),
+    );
+    let b = origin.between_with_derivative(other, Some(&mut D_b1), Some(&mut D_b2));
+    let xi = G::logmap(&b, Some(&mut D_log));
+
+    if let Some(H1) = H1 {
+        *H1 = &D_log * D_b1;
+    }
+    if let Some(H2) = H2 {
+        *H2 = D_log * D_b2;
+    }
+
+    xi
+}
+
+/// `Manifold::retract_with_derivative` for a Lie group with `retract(x, v) = x exp(v)`
+#[allow(non_snake_case)]
+pub fn group_retract_with_derivative<N, D, G>(
+    origin: &G,
+    v: &OVector<N, D>,
+    H1: Option<&mut OMatrix<N, D, D>>,
+    H2: Option<&mut OMatrix<N, D, D>>,
+) -> G
+where
+    N: RealField + Copy,
+    D: DimName,
+    G: LieGroup<N, D = D> + Manifold<Field = N, TangentDim = D>,
+    for<'a> &'a G: Mul<G, Output = G>,
+    for<'a, 'b> &'a G: Mul<&'b G, Output = G>,
+    DefaultAllocator: Allocator<N, D> + Allocator<N, D, D>,
+{
+    let (mut D_exp, mut D_g) = (OMatrix::<N, D, D>::zeros(), OMatrix::<N, D, D>::zeros());
+    let g = G::expmap_with_derivative(v, Some(&mut D_exp));
+    let res = origin.compose_with_derivative(&g, H1, Some(&mut D_g));
+
+    if let Some(H2) = H2 {
+        *H2 = D_g * D_exp;
+    }
+
+    res
+}
+
+/// Fixed size vectors are a flat manifold: retract is addition and local is subtraction
+#[allow(non_snake_case)]
+impl<N: RealField + Copy, D: DimName> Manifold for OVector<N, D>
+where
+    DefaultAllocator: Allocator<N, D>,
+{
+    type Field = N;
+    type TangentDim = D;
+
+    fn local(origin: &Self, other: &Self) -> OVector<N, D> {
+        other - origin
+    }
+
+    fn retract(origin: &Self, v: &OVector<N, D>) -> Self {
+        origin + v
+    }
+
+    fn local_with_derivative(
+        origin: &Self,
+        other: &Self,
+        H1: Option<&mut OMatrix<N, D, D>>,
+        H2: Option<&mut OMatrix<N, D, D>>,
+    ) -> OVector<N, D>
+    where
+        DefaultAllocator: Allocator<N, D, D>,
+    {
+        if let Some(H1) = H1 {
+            *H1 = -OMatrix::<N, D, D>::identity();
+        }
+        if let Some(H2) = H2 {
+            *H2 = OMatrix::<N, D, D>::identity();
+        }
+
+        Self::local(origin, other)
+    }
+
+    fn retract_with_derivative(
+        origin: &Self,
+        v: &OVector<N, D>,
+        H1: Option<&mut OMatrix<N, D, D>>,
+        H2: Option<&mut OMatrix<N, D, D>>,
+    ) -> Self
+    where
+        DefaultAllocator: Allocator<N, D, D>,
+    {
+        if let Some(H1) = H1 {
+            *H1 = OMatrix::<N, D, D>::identity();
+        }
+        if let Some(H2) = H2 {
+            *H2 = OMatrix::<N, D, D>::identity();
+        }
+
+        Self::retract(origin, v)
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use nalgebra::{Matrix3, Vector3};
+
+    #[test]
+    fn vector_manifold_works() {
+        let a = Vector3::new(1.0, 2.0, 3.0);
+        let b = Vector3::new(-1.0, 0.5, 4.0);
+
+        let (mut H1, mut H2) = (Matrix3::zeros(), Matrix3::zeros());
+        let d = Manifold::local_with_derivative(&a, &b, Some(&mut H1), Some(&mut H2));
+        assert_eq!(d, b - a);
+        assert_eq!(H1, -Matrix3::identity());
+        assert_eq!(H2, Matrix3::identity());
+
+        assert_eq!(Manifold::retract(&a, &d), b);
+        assert_eq!(<Vector3<f64> as Manifold>::dim(), 3);
+    }
 }
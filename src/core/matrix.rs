@@ -1,5 +1,5 @@
 use nalgebra as na;
-use nalgebra::base::{DMatrix, DMatrixSlice, DVector};
+use nalgebra::base::{DMatrix, DMatrixSlice, DMatrixSliceMut, DVector, DVectorSlice};
 
 pub fn skew_symmetric<N: na::RealField + Copy>(
     wx: N,
@@ -70,6 +70,37 @@ impl<T: na::RealField + core::fmt::Debug> SymmetricBlockMatrix<T> {
         mat
     }
 
+    /// Wraps `matrix`, whose upper triangle is used, with blocks of the given dimensions.
+    ///
+    /// If `append_one_dim` is set, a last block of dimension 1 is added, e.g. for the RHS
+    /// of an augmented system.
+    pub fn from_matrix(
+        dims: &[usize],
+        matrix: DMatrix<T>,
+        append_one_dim: bool,
+    ) -> SymmetricBlockMatrix<T> {
+        let mut mat = SymmetricBlockMatrix {
+            matrix,
+            variable_col_offsets: Vec::new(),
+        };
+
+        mat.fill_offsets(dims, append_one_dim);
+
+        let d = *mat.variable_col_offsets.last().unwrap();
+        assert_eq!(
+            (mat.matrix.nrows(), mat.matrix.ncols()),
+            (d, d),
+            "Matrix does not match the block dimensions"
+        );
+
+        mat
+    }
+
+    /// Total number of rows (and columns)
+    pub fn rows(&self) -> usize {
+        *self.variable_col_offsets.last().unwrap()
+    }
+
     pub fn num_blocks(&self) -> usize {
         self.variable_col_offsets.len() - 1
     }
@@ -93,7 +124,7 @@ impl<T: na::RealField + core::fmt::Debug> SymmetricBlockMatrix<T> {
         (di, dj, dr, dc)
     }
 
-    pub fn block_(&self, i: usize, j: usize, rows: usize, cols: usize) -> DMatrixSlice<T> {
+    pub fn block_(&self, i: usize, j: usize, rows: usize, cols: usize) -> DMatrixSlice<'_, T> {
         let ind = self.calc_indices(i, j, rows, cols);
 
         self.matrix.slice((ind.0, ind.1), (ind.2, ind.3))
@@ -102,6 +133,201 @@ impl<T: na::RealField + core::fmt::Debug> SymmetricBlockMatrix<T> {
     pub fn diagonal(&self, j: usize) -> DVector<T> {
         self.block_(j, j, 1, 1).diagonal()
     }
+
+    /// The block (i, j) as a dense matrix, read from the upper triangle
+    pub fn block(&self, i: usize, j: usize) -> DMatrix<T> {
+        if i == j {
+            let mut block = self.block_(j, j, 1, 1).into_owned();
+            block.fill_lower_triangle_with_upper_triangle();
+            block
+        } else if i < j {
+            self.block_(i, j, 1, 1).into_owned()
+        } else {
+            self.block_(j, i, 1, 1).transpose()
+        }
+    }
+
+    /// The full symmetric matrix, with the lower triangle filled in from the upper one
+    pub fn selfadjoint_view(&self) -> DMatrix<T> {
+        let mut m = self.matrix.clone();
+        m.fill_lower_triangle_with_upper_triangle();
+        m
+    }
+
+    /// A block matrix of zeros with blocks of the given dimensions
+    pub fn zeros(dims: &[usize], append_one_dim: bool) -> SymmetricBlockMatrix<T> {
+        let mut mat = SymmetricBlockMatrix {
+            matrix: DMatrix::zeros(0, 0),
+            variable_col_offsets: Vec::new(),
+        };
+
+        mat.fill_offsets(dims, append_one_dim);
+
+        let d = mat.rows();
+        mat.matrix = DMatrix::zeros(d, d);
+
+        mat
+    }
+
+    /// The information matrix `Ab' Ab` of a vertically stacked Jacobian `Ab`, whose columns
+    /// are split into blocks of the given dimensions
+    #[allow(non_snake_case)]
+    pub fn from_vertical_stack(
+        dims: &[usize],
+        Ab: &DMatrix<T>,
+        append_one_dim: bool,
+    ) -> SymmetricBlockMatrix<T> {
+        let mut mat = SymmetricBlockMatrix::zeros(dims, append_one_dim);
+        assert_eq!(
+            Ab.ncols(),
+            mat.rows(),
+            "Jacobian does not match the block dimensions"
+        );
+
+        mat.selfadjoint_rank_update(&Ab.transpose(), T::one());
+
+        mat
+    }
+
+    /// Mutable access to a range of blocks, which must lie on or above the block diagonal.
+    ///
+    /// Only the upper triangle of the matrix is kept, so writes to the part of a diagonal
+    /// block below its diagonal are ignored by everything else.
+    pub fn block_mut(
+        &mut self,
+        i: usize,
+        j: usize,
+        rows: usize,
+        cols: usize,
+    ) -> DMatrixSliceMut<'_, T> {
+        assert!(
+            i + rows <= j + 1,
+            "Blocks below the diagonal are not stored, access them transposed"
+        );
+        let ind = self.calc_indices(i, j, rows, cols);
+
+        self.matrix.slice_mut((ind.0, ind.1), (ind.2, ind.3))
+    }
+
+    /// Sets the diagonal block j from the upper triangle of `m`
+    pub fn set_diagonal_block(&mut self, j: usize, m: &DMatrix<T>) {
+        let mut block = self.block_mut(j, j, 1, 1);
+        assert_eq!(block.shape(), m.shape(), "Block has the wrong size");
+
+        for c in 0..m.ncols() {
+            for r in 0..=c {
+                block[(r, c)] = m[(r, c)].clone();
+            }
+        }
+    }
+
+    /// Sets the block (i, j), i != j, storing it transposed if it is below the diagonal
+    pub fn set_off_diagonal_block(&mut self, i: usize, j: usize, m: &DMatrix<T>) {
+        assert_ne!(i, j, "Use set_diagonal_block for diagonal blocks");
+
+        if i < j {
+            self.block_mut(i, j, 1, 1).copy_from(m);
+        } else {
+            self.block_mut(j, i, 1, 1).tr_copy_from(m);
+        }
+    }
+
+    /// Adds the upper triangle of `m` to the diagonal block j
+    pub fn update_diagonal_block(&mut self, j: usize, m: &DMatrix<T>) {
+        let mut block = self.block_mut(j, j, 1, 1);
+        assert_eq!(block.shape(), m.shape(), "Block has the wrong size");
+
+        for c in 0..m.ncols() {
+            for r in 0..=c {
+                block[(r, c)] += m[(r, c)].clone();
+            }
+        }
+    }
+
+    /// Adds `m` to the block (i, j), i != j
+    pub fn update_off_diagonal_block(&mut self, i: usize, j: usize, m: &DMatrix<T>) {
+        assert_ne!(i, j, "Use update_diagonal_block for diagonal blocks");
+
+        if i < j {
+            let mut block = self.block_mut(i, j, 1, 1);
+            block += m;
+        } else {
+            let mut block = self.block_mut(j, i, 1, 1);
+            block += m.transpose();
+        }
+    }
+
+    /// The symmetric rank-k update `self += alpha * A * A'`, touching only the upper triangle
+    #[allow(non_snake_case)]
+    pub fn selfadjoint_rank_update(&mut self, A: &DMatrix<T>, alpha: T) {
+        let n = self.rows();
+        assert_eq!(A.nrows(), n, "Update has the wrong number of rows");
+
+        let mut update = DMatrix::zeros(n, n);
+        update.gemm(alpha, A, &A.transpose(), T::zero());
+
+        for c in 0..n {
+            for r in 0..=c {
+                self.matrix[(r, c)] += update[(r, c)].clone();
+            }
+        }
+    }
+
+    /// In-place Cholesky factorization of the first `n_frontals` blocks.
+    ///
+    /// With the matrix partitioned as [A11 A12; A12' A22], the upper triangle becomes
+    /// [R S; 0 L] with R' R = A11, S = R'^-1 A12 and L = A22 - S' S, the Schur complement.
+    /// Returns false, leaving the matrix untouched, if A11 is not positive definite.
+    #[allow(non_snake_case)]
+    pub fn cholesky_partial(&mut self, n_frontals: usize) -> bool {
+        let n = self.offset(n_frontals);
+        let total = self.rows();
+
+        let mut A11 = self.matrix.slice((0, 0), (n, n)).into_owned();
+        A11.fill_lower_triangle_with_upper_triangle();
+        let R = match na::Cholesky::new(A11) {
+            Some(llt) => llt.l().transpose(),
+            None => return false,
+        };
+
+        let S = R
+            .transpose()
+            .solve_lower_triangular(&self.matrix.slice((0, n), (n, total - n)).into_owned())
+            .unwrap();
+
+        self.matrix.slice_mut((0, 0), (n, n)).copy_from(&R);
+        self.matrix.slice_mut((0, n), (n, total - n)).copy_from(&S);
+
+        let mut L = self.matrix.slice_mut((n, n), (total - n, total - n));
+        L -= S.tr_mul(&S);
+
+        true
+    }
+
+    /// Splits off the first `n_frontals` block rows, e.g. [R S] after `cholesky_partial`, and
+    /// leaves the remaining lower right blocks in `self`.
+    ///
+    /// The returned rows span all columns, with zeros below the block diagonal.
+    pub fn split(&mut self, n_frontals: usize) -> DMatrix<T> {
+        let n = self.offset(n_frontals);
+        let total = self.rows();
+
+        let mut frontal = self.matrix.rows(0, n).into_owned();
+        frontal
+            .slice_mut((0, 0), (n, n))
+            .fill_lower_triangle(T::zero(), 1);
+
+        self.matrix = self
+            .matrix
+            .slice((n, n), (total - n, total - n))
+            .into_owned();
+        self.variable_col_offsets = self.variable_col_offsets[n_frontals..]
+            .iter()
+            .map(|o| o - n)
+            .collect();
+
+        frontal
+    }
 }
 
 impl<T: na::RealField> core::fmt::Debug for SymmetricBlockMatrix<T> {
@@ -120,6 +346,217 @@ impl<T: na::RealField> Default for SymmetricBlockMatrix<T> {
     }
 }
 
+/// A dense matrix of vertically stacked rows, split into column blocks, e.g. the augmented
+/// Jacobian [A_1 ... A_n b] of a linear factor.
+///
+/// All blocks live in one contiguous matrix. Only the rows in `row_start..row_end` are
+/// active, which allows dropping rows from the top after elimination without reallocating.
+pub struct VerticalBlockMatrix<T: na::RealField + core::fmt::Debug = f64> {
+    matrix: na::OMatrix<T, na::Dynamic, na::Dynamic>,
+    variable_col_offsets: Vec<usize>,
+    row_start: usize,
+    row_end: usize,
+}
+
+impl<T: na::RealField + core::fmt::Debug> VerticalBlockMatrix<T> {
+    pub fn new() -> VerticalBlockMatrix<T> {
+        VerticalBlockMatrix {
+            matrix: DMatrix::zeros(0, 0),
+            variable_col_offsets: vec![0],
+            row_start: 0,
+            row_end: 0,
+        }
+    }
+
+    fn fill_offsets(&mut self, dims: &[usize], append_one_dim: bool) {
+        self.variable_col_offsets = Vec::with_capacity(dims.len() + 2);
+        self.variable_col_offsets.push(0);
+
+        let mut offset = 0;
+        for dim in dims.iter().chain(Some(&1).filter(|_| append_one_dim)) {
+            offset += dim;
+            self.variable_col_offsets.push(offset);
+        }
+    }
+
+    /// A matrix of zeros with `rows` rows and blocks of the given dimensions.
+    ///
+    /// If `append_one_dim` is set, a last block of dimension 1 is added for the RHS.
+    pub fn from_dimensions(
+        dims: &[usize],
+        rows: usize,
+        append_one_dim: bool,
+    ) -> VerticalBlockMatrix<T> {
+        let mut mat = VerticalBlockMatrix::new();
+        mat.fill_offsets(dims, append_one_dim);
+
+        mat.matrix = DMatrix::zeros(rows, mat.cols());
+        mat.row_end = rows;
+
+        mat
+    }
+
+    /// Wraps `matrix`, whose columns are split into blocks of the given dimensions
+    pub fn from_matrix(
+        dims: &[usize],
+        matrix: DMatrix<T>,
+        append_one_dim: bool,
+    ) -> VerticalBlockMatrix<T> {
+        let mut mat = VerticalBlockMatrix::new();
+        mat.fill_offsets(dims, append_one_dim);
+
+        assert_eq!(
+            matrix.ncols(),
+            mat.cols(),
+            "Matrix does not match the block dimensions"
+        );
+        mat.row_end = matrix.nrows();
+        mat.matrix = matrix;
+
+        mat
+    }
+
+    /// Number of active rows
+    pub fn rows(&self) -> usize {
+        self.row_end - self.row_start
+    }
+
+    /// Total number of columns of all blocks
+    pub fn cols(&self) -> usize {
+        *self.variable_col_offsets.last().unwrap()
+    }
+
+    pub fn num_blocks(&self) -> usize {
+        self.variable_col_offsets.len() - 1
+    }
+
+    /// Column at which `block` starts, `offset(num_blocks())` is the number of columns
+    pub fn offset(&self, block: usize) -> usize {
+        self.variable_col_offsets[block]
+    }
+
+    /// Dimension of `block`
+    pub fn dim(&self, block: usize) -> usize {
+        self.offset(block + 1) - self.offset(block)
+    }
+
+    /// Row of the underlying matrix that is the first active row
+    pub fn row_start(&self) -> usize {
+        self.row_start
+    }
+
+    pub fn set_row_start(&mut self, row: usize) {
+        assert!(row <= self.row_end, "Row start is past the row end");
+        self.row_start = row;
+    }
+
+    /// Row of the underlying matrix one past the last active row
+    pub fn row_end(&self) -> usize {
+        self.row_end
+    }
+
+    pub fn set_row_end(&mut self, row: usize) {
+        assert!(
+            self.row_start <= row && row <= self.matrix.nrows(),
+            "Last row is out of range"
+        );
+        self.row_end = row;
+    }
+
+    /// The active rows of the blocks `start..end`
+    pub fn range(&self, start: usize, end: usize) -> DMatrixSlice<'_, T> {
+        let (c0, c1) = (self.offset(start), self.offset(end));
+
+        self.matrix
+            .slice((self.row_start, c0), (self.rows(), c1 - c0))
+    }
+
+    pub fn range_mut(&mut self, start: usize, end: usize) -> DMatrixSliceMut<'_, T> {
+        let (c0, c1) = (self.offset(start), self.offset(end));
+        let rows = self.rows();
+
+        self.matrix.slice_mut((self.row_start, c0), (rows, c1 - c0))
+    }
+
+    /// The active rows of `block`
+    pub fn block(&self, block: usize) -> DMatrixSlice<'_, T> {
+        self.range(block, block + 1)
+    }
+
+    pub fn block_mut(&mut self, block: usize) -> DMatrixSliceMut<'_, T> {
+        self.range_mut(block, block + 1)
+    }
+
+    /// The active rows of column `col` of the full matrix, e.g. the RHS of a block of dimension 1
+    pub fn column(&self, col: usize) -> DVectorSlice<'_, T> {
+        self.matrix.generic_slice(
+            (self.row_start, col),
+            (na::Dynamic::new(self.rows()), na::Const::<1>),
+        )
+    }
+
+    /// The active rows of all blocks
+    pub fn full(&self) -> DMatrixSlice<'_, T> {
+        self.range(0, self.num_blocks())
+    }
+
+    pub fn full_mut(&mut self) -> DMatrixSliceMut<'_, T> {
+        let n = self.num_blocks();
+        self.range_mut(0, n)
+    }
+
+    /// In-place Householder QR of the blocks `first_block..first_block + n_blocks`.
+    ///
+    /// The columns of these blocks become upper triangular, with exact zeros below the diagonal,
+    /// and the same reflections are applied to all blocks to their right, so that [A b] becomes
+    /// Q' [A b]. Blocks to the left are untouched.
+    pub fn householder_qr(&mut self, first_block: usize, n_blocks: usize) {
+        let c0 = self.offset(first_block);
+        let c1 = self.offset(first_block + n_blocks);
+        let cols = self.cols();
+
+        for k in 0..(c1 - c0).min(self.rows()) {
+            let (row, col) = (self.row_start + k, c0 + k);
+            let mut tail = self
+                .matrix
+                .slice_mut((row, col), (self.row_end - row, cols - col));
+
+            let mut v = tail.column(0).into_owned();
+            let norm = v.norm();
+            if norm.is_zero() {
+                continue;
+            }
+
+            let alpha = if v[0] > T::zero() { -norm } else { norm };
+            v[0] -= alpha.clone();
+            v.normalize_mut();
+
+            // tail -= 2 v (v' tail)
+            let w = tail.tr_mul(&v);
+            tail.ger(-(T::one() + T::one()), &v, &w, T::one());
+
+            tail.column_mut(0).fill(T::zero());
+            tail[(0, 0)] = alpha;
+        }
+    }
+}
+
+impl<T: na::RealField> core::fmt::Debug for VerticalBlockMatrix<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "VerticalBlockMatrix:\n{:}\n{:?}\nrows {}..{}",
+            self.matrix, self.variable_col_offsets, self.row_start, self.row_end
+        )
+    }
+}
+
+impl<T: na::RealField> Default for VerticalBlockMatrix<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,4 +592,150 @@ mod tests {
         println!("{:}", s.block_(1, 2, 1, 1));
         println!("{:}", s.diagonal(1));
     }
+
+    fn example_jacobian() -> DMatrix<f64> {
+        DMatrix::from_row_slice(
+            5,
+            4,
+            &[
+                2.0, 0.5, 1.0, 3.0, //
+                0.0, 1.0, -1.0, 2.0, //
+                1.0, 0.0, 3.0, -1.0, //
+                0.5, 2.0, 0.0, 1.0, //
+                -1.0, 1.0, 1.0, 0.5,
+            ],
+        )
+    }
+
+    #[test]
+    fn symmetric_block_matrix_set_and_update_blocks() {
+        let mut s = SymmetricBlockMatrix::<f64>::zeros(&[2, 1], true);
+        assert_eq!((s.num_blocks(), s.rows()), (3, 4));
+
+        let d = DMatrix::from_row_slice(2, 2, &[4.0, 1.0, 1.0, 3.0]);
+        s.set_diagonal_block(0, &d);
+        assert_relative_eq!(s.block(0, 0), d);
+
+        // Blocks below the diagonal are stored transposed
+        let m = DMatrix::from_row_slice(1, 2, &[5.0, 6.0]);
+        s.set_off_diagonal_block(1, 0, &m);
+        assert_relative_eq!(s.block(0, 1), m.transpose());
+        assert_relative_eq!(s.block(1, 0), m);
+
+        s.update_off_diagonal_block(0, 1, &m.transpose());
+        s.update_diagonal_block(0, &d);
+        assert_relative_eq!(s.block(1, 0), m * 2.0);
+        assert_relative_eq!(s.block(0, 0), d * 2.0);
+
+        let full = s.selfadjoint_view();
+        assert_relative_eq!(full, full.transpose());
+    }
+
+    #[test]
+    fn symmetric_block_matrix_rank_update() {
+        let ab = example_jacobian();
+
+        let s = SymmetricBlockMatrix::<f64>::from_vertical_stack(&[2, 1], &ab, true);
+        assert_relative_eq!(s.selfadjoint_view(), ab.tr_mul(&ab), epsilon = 1e-12);
+
+        let mut t = SymmetricBlockMatrix::<f64>::zeros(&[2, 1], true);
+        t.selfadjoint_rank_update(&ab.transpose(), 0.5);
+        t.selfadjoint_rank_update(&ab.transpose(), 1.5);
+        assert_relative_eq!(t.selfadjoint_view(), ab.tr_mul(&ab) * 2.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn symmetric_block_matrix_cholesky_partial_and_split() {
+        let ab = example_jacobian();
+        let info = ab.tr_mul(&ab);
+        let mut s = SymmetricBlockMatrix::<f64>::from_vertical_stack(&[2, 1], &ab, true);
+
+        assert!(s.cholesky_partial(1));
+        let frontal = s.split(1);
+        assert_eq!((frontal.nrows(), frontal.ncols()), (2, 4));
+        assert_eq!((s.num_blocks(), s.rows()), (2, 2));
+
+        let R = frontal.columns(0, 2).into_owned();
+        let S = frontal.columns(2, 2).into_owned();
+        assert_eq!(R[(1, 0)], 0.0);
+        assert_relative_eq!(
+            R.tr_mul(&R),
+            info.slice((0, 0), (2, 2)).into_owned(),
+            epsilon = 1e-12
+        );
+        assert_relative_eq!(
+            R.tr_mul(&S),
+            info.slice((0, 2), (2, 2)).into_owned(),
+            epsilon = 1e-12
+        );
+
+        // The remainder is the Schur complement of the frontal block
+        let A11_inv = info.slice((0, 0), (2, 2)).try_inverse().unwrap();
+        let A12 = info.slice((0, 2), (2, 2));
+        let schur = info.slice((2, 2), (2, 2)) - A12.transpose() * A11_inv * A12;
+        assert_relative_eq!(s.selfadjoint_view(), schur, epsilon = 1e-10);
+
+        let mut indefinite = SymmetricBlockMatrix::<f64>::zeros(&[2], false);
+        indefinite.set_diagonal_block(0, &DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 2.0, 1.0]));
+        assert!(!indefinite.cholesky_partial(1));
+    }
+
+    #[test]
+    fn vertical_block_matrix_blocks_and_rows() {
+        let mut m = VerticalBlockMatrix::<f64>::from_matrix(&[2, 1], example_jacobian(), true);
+        assert_eq!((m.num_blocks(), m.rows(), m.cols()), (3, 5, 4));
+        assert_eq!((m.offset(1), m.dim(0), m.dim(2)), (2, 2, 1));
+
+        assert_relative_eq!(
+            m.block(1).into_owned(),
+            example_jacobian().columns(2, 1).into_owned()
+        );
+        assert_relative_eq!(
+            m.column(3).into_owned(),
+            example_jacobian().column(3).into_owned()
+        );
+
+        m.block_mut(2).fill(7.0);
+        assert_eq!(m.range(1, 3)[(4, 1)], 7.0);
+
+        // Moving the first row hides the rows above it
+        m.set_row_start(3);
+        assert_eq!(m.rows(), 2);
+        let mut expected = example_jacobian().rows(3, 2).into_owned();
+        expected.column_mut(3).fill(7.0);
+        assert_relative_eq!(m.full().into_owned(), expected);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn vertical_block_matrix_householder_qr() {
+        let ab = example_jacobian();
+        let mut m = VerticalBlockMatrix::<f64>::from_matrix(&[2, 1], ab.clone(), true);
+
+        m.householder_qr(0, 2);
+        let qr = m.full().into_owned();
+
+        // Q' [A b] keeps the normal equations and zeros the part below the diagonal of A
+        for c in 0..3 {
+            for r in (c + 1)..5 {
+                assert_eq!(qr[(r, c)], 0.0);
+            }
+        }
+        assert_relative_eq!(qr.tr_mul(&qr), ab.tr_mul(&ab), epsilon = 1e-10);
+
+        // Starting at a later block leaves the blocks to its left untouched
+        let mut m = VerticalBlockMatrix::<f64>::from_matrix(&[2, 1], ab.clone(), true);
+        m.set_row_start(2);
+        m.householder_qr(1, 1);
+        let R = m.full().into_owned();
+        assert_relative_eq!(
+            R.columns(0, 2).into_owned(),
+            ab.slice((2, 0), (3, 2)).into_owned()
+        );
+        assert_eq!((R[(1, 2)], R[(2, 2)]), (0.0, 0.0));
+        let tail = ab.slice((2, 2), (3, 2)).into_owned();
+        let R_tail = R.columns(2, 2).into_owned();
+        assert_relative_eq!(R_tail.tr_mul(&R_tail), tail.tr_mul(&tail), epsilon = 1e-10);
+    }
 }
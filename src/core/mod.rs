@@ -1,4 +1,7 @@
 pub mod group;
+pub mod jet;
 pub mod manifold;
 pub mod matrix;
+pub mod numerical_derivative;
+pub mod scalar;
 pub mod vector;
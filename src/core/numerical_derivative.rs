@@ -0,0 +1,196 @@
+//! Central-difference Jacobians of functions between manifolds.
+//!
+//! Perturbations are applied with `retract` around the arguments and measured with `local`
+//! around the value, so the results are directly comparable to the analytic `H` matrices of
+//! the `LieGroup` and `Manifold` methods.
+
+use crate::core::manifold::Manifold;
+use nalgebra::allocator::Allocator;
+use nalgebra::{convert, zero, DefaultAllocator, OMatrix, OVector};
+
+/// A step size that balances truncation and rounding errors for `f64`
+pub const DEFAULT_DELTA: f64 = 1e-5;
+
+/// Numerical derivative of `h` wrpt its argument at `x`
+pub fn numerical_derivative11<X, Y, F>(
+    h: F,
+    x: &X,
+    delta: X::Field,
+) -> OMatrix<X::Field, Y::TangentDim, X::TangentDim>
+where
+    X: Manifold,
+    Y: Manifold<Field = X::Field>,
+    F: Fn(&X) -> Y,
+    DefaultAllocator: Allocator<X::Field, X::TangentDim>
+        + Allocator<X::Field, Y::TangentDim>
+        + Allocator<X::Field, Y::TangentDim, X::TangentDim>,
+{
+    let hx = h(x);
+    let factor = convert::<f64, X::Field>(0.5) / delta;
+
+    let mut res = OMatrix::<X::Field, Y::TangentDim, X::TangentDim>::zeros();
+    let mut d = OVector::<X::Field, X::TangentDim>::zeros();
+    for j in 0..X::dim() {
+        d[j] = delta;
+        let plus = Y::local(&hx, &h(&X::retract(x, &d)));
+        d[j] = -delta;
+        let minus = Y::local(&hx, &h(&X::retract(x, &d)));
+        d[j] = zero();
+
+        res.set_column(j, &((plus - minus) * factor));
+    }
+
+    res
+}
+
+/// Numerical derivative of `h` wrpt its first argument at `(x1, x2)`
+pub fn numerical_derivative21<X1, X2, Y, F>(
+    h: F,
+    x1: &X1,
+    x2: &X2,
+    delta: X1::Field,
+) -> OMatrix<X1::Field, Y::TangentDim, X1::TangentDim>
+where
+    X1: Manifold,
+    Y: Manifold<Field = X1::Field>,
+    F: Fn(&X1, &X2) -> Y,
+    DefaultAllocator: Allocator<X1::Field, X1::TangentDim>
+        + Allocator<X1::Field, Y::TangentDim>
+        + Allocator<X1::Field, Y::TangentDim, X1::TangentDim>,
+{
+    numerical_derivative11(|x: &X1| h(x, x2), x1, delta)
+}
+
+/// Numerical derivative of `h` wrpt its second argument at `(x1, x2)`
+pub fn numerical_derivative22<X1, X2, Y, F>(
+    h: F,
+    x1: &X1,
+    x2: &X2,
+    delta: X2::Field,
+) -> OMatrix<X2::Field, Y::TangentDim, X2::TangentDim>
+where
+    X2: Manifold,
+    Y: Manifold<Field = X2::Field>,
+    F: Fn(&X1, &X2) -> Y,
+    DefaultAllocator: Allocator<X2::Field, X2::TangentDim>
+        + Allocator<X2::Field, Y::TangentDim>
+        + Allocator<X2::Field, Y::TangentDim, X2::TangentDim>,
+{
+    numerical_derivative11(|x: &X2| h(x1, x), x2, delta)
+}
+
+/// Numerical derivative of `h` wrpt its first argument at `(x1, x2, x3)`
+pub fn numerical_derivative31<X1, X2, X3, Y, F>(
+    h: F,
+    x1: &X1,
+    x2: &X2,
+    x3: &X3,
+    delta: X1::Field,
+) -> OMatrix<X1::Field, Y::TangentDim, X1::TangentDim>
+where
+    X1: Manifold,
+    Y: Manifold<Field = X1::Field>,
+    F: Fn(&X1, &X2, &X3) -> Y,
+    DefaultAllocator: Allocator<X1::Field, X1::TangentDim>
+        + Allocator<X1::Field, Y::TangentDim>
+        + Allocator<X1::Field, Y::TangentDim, X1::TangentDim>,
+{
+    numerical_derivative11(|x: &X1| h(x, x2, x3), x1, delta)
+}
+
+/// Numerical derivative of `h` wrpt its second argument at `(x1, x2, x3)`
+pub fn numerical_derivative32<X1, X2, X3, Y, F>(
+    h: F,
+    x1: &X1,
+    x2: &X2,
+    x3: &X3,
+    delta: X2::Field,
+) -> OMatrix<X2::Field, Y::TangentDim, X2::TangentDim>
+where
+    X2: Manifold,
+    Y: Manifold<Field = X2::Field>,
+    F: Fn(&X1, &X2, &X3) -> Y,
+    DefaultAllocator: Allocator<X2::Field, X2::TangentDim>
+        + Allocator<X2::Field, Y::TangentDim>
+        + Allocator<X2::Field, Y::TangentDim, X2::TangentDim>,
+{
+    numerical_derivative11(|x: &X2| h(x1, x, x3), x2, delta)
+}
+
+/// Numerical derivative of `h` wrpt its third argument at `(x1, x2, x3)`
+pub fn numerical_derivative33<X1, X2, X3, Y, F>(
+    h: F,
+    x1: &X1,
+    x2: &X2,
+    x3: &X3,
+    delta: X3::Field,
+) -> OMatrix<X3::Field, Y::TangentDim, X3::TangentDim>
+where
+    X3: Manifold,
+    Y: Manifold<Field = X3::Field>,
+    F: Fn(&X1, &X2, &X3) -> Y,
+    DefaultAllocator: Allocator<X3::Field, X3::TangentDim>
+        + Allocator<X3::Field, Y::TangentDim>
+        + Allocator<X3::Field, Y::TangentDim, X3::TangentDim>,
+{
+    numerical_derivative11(|x: &X3| h(x1, x2, x), x3, delta)
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use crate::core::group::LieGroup;
+    use crate::core::matrix::skew_symmetric_v;
+    use crate::geometry::se3::SE3;
+    use crate::geometry::so3::SO3;
+    use nalgebra::{Matrix3, Matrix6, Vector3, Vector6};
+
+    #[test]
+    fn derivative11_matches_expmap_derivative() {
+        let w = Vector3::new(0.1, -0.4, 0.3);
+
+        let mut expected = Matrix3::zeros();
+        SO3::expmap_with_derivative(&w, Some(&mut expected));
+        let actual = numerical_derivative11(|w: &Vector3<f64>| SO3::expmap(w), &w, DEFAULT_DELTA);
+
+        assert_relative_eq!((actual - expected).norm(), 0.0, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn derivative2x_matches_between_derivatives() {
+        let a = SE3::expmap(&Vector6::new(0.1, -0.4, 0.3, 1.0, 2.0, -0.5));
+        let b = SE3::expmap(&Vector6::new(-0.7, 0.2, 0.5, -1.0, 0.3, 2.0));
+
+        let (mut H1, mut H2) = (Matrix6::zeros(), Matrix6::zeros());
+        a.between_with_derivative(&b, Some(&mut H1), Some(&mut H2));
+
+        let between = |a: &SE3<f64>, b: &SE3<f64>| a.between(b);
+        let actual_H1 = numerical_derivative21(between, &a, &b, DEFAULT_DELTA);
+        let actual_H2 = numerical_derivative22(between, &a, &b, DEFAULT_DELTA);
+
+        assert_relative_eq!((actual_H1 - H1).norm(), 0.0, epsilon = 1e-8);
+        assert_relative_eq!((actual_H2 - H2).norm(), 0.0, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn derivative3x_of_rigid_transform() {
+        let R = SO3::expmap(&Vector3::new(0.1, -0.4, 0.3));
+        let p = Vector3::new(1.0, 2.0, 3.0);
+        let t = Vector3::new(-0.5, 0.2, 0.1);
+
+        let transform = |R: &SO3<f64>, p: &Vector3<f64>, t: &Vector3<f64>| R * p + t;
+
+        let H1 = numerical_derivative31(transform, &R, &p, &t, DEFAULT_DELTA);
+        let H2 = numerical_derivative32(transform, &R, &p, &t, DEFAULT_DELTA);
+        let H3 = numerical_derivative33(transform, &R, &p, &t, DEFAULT_DELTA);
+
+        assert_relative_eq!(
+            (H1 + R.matrix() * skew_symmetric_v(&p)).norm(),
+            0.0,
+            epsilon = 1e-8
+        );
+        assert_relative_eq!((H2 - R.matrix()).norm(), 0.0, epsilon = 1e-8);
+        assert_relative_eq!((H3 - Matrix3::identity()).norm(), 0.0, epsilon = 1e-8);
+    }
+}
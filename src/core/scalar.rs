@@ -0,0 +1,21 @@
+use nalgebra::{convert, RealField};
+
+/// `N::default_epsilon()` raised to the power `p`.
+///
+/// The switch-over points between closed forms and their Taylor expansions are written with this,
+/// so that they scale with the precision of `N` instead of being tuned for `f64` only.
+pub fn epsilon_pow<N: RealField + Copy>(p: f64) -> N {
+    N::default_epsilon().powf(convert(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epsilon_pow_scales_with_precision() {
+        assert_relative_eq!(epsilon_pow::<f64>(1.0), f64::EPSILON);
+        assert_relative_eq!(epsilon_pow::<f64>(0.5), f64::EPSILON.sqrt());
+        assert!(epsilon_pow::<f32>(0.5) as f64 > epsilon_pow::<f64>(0.5));
+    }
+}
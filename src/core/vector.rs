@@ -64,37 +64,26 @@ mod tests {
 
     #[test]
     fn linear_dependent_works() {
-        assert_eq!(
-            linear_dependent(&Vector6::<f64>::x(), &Vector6::x(), 0.01),
-            true
-        );
+        assert!(linear_dependent(&Vector6::<f64>::x(), &Vector6::x(), 0.01));
 
-        assert_eq!(
-            linear_dependent(&Vector3::<f64>::x(), &Vector3::y(), 0.01),
-            false
-        );
+        assert!(!linear_dependent(&Vector3::<f64>::x(), &Vector3::y(), 0.01));
 
-        assert_eq!(
-            linear_dependent(&(Vector3::<f64>::x() * 3.5), &Vector3::x(), 0.01),
-            true
-        );
+        assert!(linear_dependent(
+            &(Vector3::<f64>::x() * 3.5),
+            &Vector3::x(),
+            0.01
+        ));
 
-        assert_eq!(
-            linear_dependent(
-                &(Vector3::<f64>::x() * 3.5 + 0.01 * Vector3::y()),
-                &Vector3::x(),
-                0.001
-            ),
-            false
-        );
+        assert!(!linear_dependent(
+            &(Vector3::<f64>::x() * 3.5 + 0.01 * Vector3::y()),
+            &Vector3::x(),
+            0.001
+        ));
 
-        assert_eq!(
-            linear_dependent(
-                &(Vector3::<f64>::x() * 3.5 + 0.001 * Vector3::y()),
-                &Vector3::x(),
-                0.001
-            ),
-            true
-        );
+        assert!(linear_dependent(
+            &(Vector3::<f64>::x() * 3.5 + 0.001 * Vector3::y()),
+            &Vector3::x(),
+            0.001
+        ));
     }
 }
@@ -1,5 +1,13 @@
+pub mod nav_state;
+pub mod se2;
 pub mod se3;
+pub mod sim3;
+pub mod so2;
 pub mod so3;
 
+pub use nav_state::*;
+pub use se2::*;
 pub use se3::*;
+pub use sim3::*;
+pub use so2::*;
 pub use so3::*;
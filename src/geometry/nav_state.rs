@@ -0,0 +1,387 @@
+pub use crate::core::group::LieGroup;
+pub use crate::core::manifold::Manifold;
+use crate::core::manifold::{group_local_with_derivative, group_retract_with_derivative};
+use crate::geometry::so3::{so3_left_jacobian, SO3};
+use nalgebra::{Matrix3, OMatrix, OVector, RealField, Vector3, Vector6, U9};
+use std::ops::Mul;
+
+/// The 9x9 Jacobians of SE_2(3)
+pub type Matrix9<N> = OMatrix<N, U9, U9>;
+/// Twists of SE_2(3), ordered [omega; rho; nu] for attitude, position and velocity
+pub type Vector9<N> = OVector<N, U9>;
+
+/// Navigation state of a moving body: its attitude, position and velocity in the world frame.
+///
+/// This is the extended special Euclidean group SE_2(3), i.e. the matrices
+/// [R p v; 0 1 0; 0 0 1] under matrix multiplication.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NavState<N: RealField + Copy = f64> {
+    pub attitude: SO3<N>,
+    pub position: Vector3<N>,
+    pub velocity: Vector3<N>,
+}
+
+impl<N: RealField + Copy> NavState<N> {
+    pub fn new(attitude: SO3<N>, position: Vector3<N>, velocity: Vector3<N>) -> Self {
+        NavState {
+            attitude,
+            position,
+            velocity,
+        }
+    }
+
+    pub fn identity() -> Self {
+        NavState {
+            attitude: SO3::identity(),
+            position: Vector3::zeros(),
+            velocity: Vector3::zeros(),
+        }
+    }
+
+    /// Velocity in the body frame
+    pub fn body_velocity(&self) -> Vector3<N> {
+        self.attitude.inverse() * self.velocity
+    }
+
+    fn multiply(&self, rhs: &NavState<N>) -> NavState<N> {
+        NavState {
+            attitude: self.attitude * rhs.attitude,
+            position: self.attitude * rhs.position + self.position,
+            velocity: self.attitude * rhs.velocity + self.velocity,
+        }
+    }
+}
+
+impl<'b, N: RealField + Copy> Mul<&'b NavState<N>> for &NavState<N> {
+    type Output = NavState<N>;
+
+    fn mul(self, rhs: &'b NavState<N>) -> NavState<N> {
+        self.multiply(rhs)
+    }
+}
+
+impl<N: RealField + Copy> Mul<NavState<N>> for &NavState<N> {
+    type Output = NavState<N>;
+
+    fn mul(self, rhs: NavState<N>) -> NavState<N> {
+        self.multiply(&rhs)
+    }
+}
+
+impl<'b, N: RealField + Copy> Mul<&'b NavState<N>> for NavState<N> {
+    type Output = NavState<N>;
+
+    fn mul(self, rhs: &'b NavState<N>) -> NavState<N> {
+        self.multiply(rhs)
+    }
+}
+
+impl<N: RealField + Copy> Mul<NavState<N>> for NavState<N> {
+    type Output = NavState<N>;
+
+    fn mul(self, rhs: NavState<N>) -> NavState<N> {
+        self.multiply(&rhs)
+    }
+}
+
+fn split<N: RealField + Copy>(xi: &Vector9<N>) -> (Vector3<N>, Vector3<N>, Vector3<N>) {
+    (
+        xi.fixed_rows::<3>(0).into_owned(),
+        xi.fixed_rows::<3>(3).into_owned(),
+        xi.fixed_rows::<3>(6).into_owned(),
+    )
+}
+
+fn join<N: RealField + Copy>(omega: &Vector3<N>, rho: &Vector3<N>, nu: &Vector3<N>) -> Vector9<N> {
+    let mut xi = Vector9::zeros();
+    xi.fixed_rows_mut::<3>(0).copy_from(omega);
+    xi.fixed_rows_mut::<3>(3).copy_from(rho);
+    xi.fixed_rows_mut::<3>(6).copy_from(nu);
+    xi
+}
+
+fn se3_twist<N: RealField + Copy>(omega: &Vector3<N>, v: &Vector3<N>) -> Vector6<N> {
+    Vector6::new(omega.x, omega.y, omega.z, v.x, v.y, v.z)
+}
+
+/// Right Jacobian of SE_2(3), i.e. the derivative of the expmap:
+/// `exp(xi + d) = exp(xi) * exp(J * d)` for `d --> 0`.
+///
+/// Position and velocity each couple to the rotation like the translation of SE(3).
+#[allow(non_snake_case)]
+pub fn nav_state_right_jacobian<N: RealField + Copy>(xi: &Vector9<N>) -> Matrix9<N> {
+    use crate::geometry::se3::compute_q_for_expmap_derivative;
+
+    let (omega, rho, nu) = split(xi);
+
+    let mut Jw = Matrix3::zeros();
+    SO3::expmap_with_derivative(&omega, Some(&mut Jw));
+    let Q_rho = compute_q_for_expmap_derivative(&se3_twist(&omega, &rho));
+    let Q_nu = compute_q_for_expmap_derivative(&se3_twist(&omega, &nu));
+
+    let mut J = Matrix9::zeros();
+    J.fixed_slice_mut::<3, 3>(0, 0).copy_from(&Jw);
+    J.fixed_slice_mut::<3, 3>(3, 0).copy_from(&Q_rho);
+    J.fixed_slice_mut::<3, 3>(3, 3).copy_from(&Jw);
+    J.fixed_slice_mut::<3, 3>(6, 0).copy_from(&Q_nu);
+    J.fixed_slice_mut::<3, 3>(6, 6).copy_from(&Jw);
+    J
+}
+
+/// Inverse of the right Jacobian of SE_2(3), i.e. the derivative of the logmap:
+/// `log(exp(xi) * exp(d)) = xi + J * d` for `d --> 0`.
+#[allow(non_snake_case)]
+pub fn nav_state_right_jacobian_inverse<N: RealField + Copy>(xi: &Vector9<N>) -> Matrix9<N> {
+    use crate::geometry::se3::compute_q_for_expmap_derivative;
+    use crate::geometry::so3::logmap_derivative;
+
+    let (omega, rho, nu) = split(xi);
+
+    let Jw = logmap_derivative(&omega);
+    let Q_rho = compute_q_for_expmap_derivative(&se3_twist(&omega, &rho));
+    let Q_nu = compute_q_for_expmap_derivative(&se3_twist(&omega, &nu));
+
+    let mut J = Matrix9::zeros();
+    J.fixed_slice_mut::<3, 3>(0, 0).copy_from(&Jw);
+    J.fixed_slice_mut::<3, 3>(3, 0)
+        .copy_from(&(-Jw * Q_rho * Jw));
+    J.fixed_slice_mut::<3, 3>(3, 3).copy_from(&Jw);
+    J.fixed_slice_mut::<3, 3>(6, 0)
+        .copy_from(&(-Jw * Q_nu * Jw));
+    J.fixed_slice_mut::<3, 3>(6, 6).copy_from(&Jw);
+    J
+}
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> LieGroup<N> for NavState<N> {
+    type D = U9;
+
+    fn between(&self, g: &Self) -> Self {
+        LieGroup::inverse(self) * g
+    }
+
+    fn inverse(&self) -> Self {
+        let R_inv = self.attitude.inverse();
+
+        NavState {
+            attitude: R_inv,
+            position: -(R_inv * self.position),
+            velocity: -(R_inv * self.velocity),
+        }
+    }
+
+    fn adjoint_map(&self) -> Matrix9<N> {
+        use crate::core::matrix::skew_symmetric_v;
+
+        let R = self.attitude.matrix();
+
+        // Twists are ordered [omega; rho; nu], so Ad = [R 0 0; [p]x R R 0; [v]x R 0 R]
+        let mut res = Matrix9::zeros();
+        res.fixed_slice_mut::<3, 3>(0, 0).copy_from(R);
+        res.fixed_slice_mut::<3, 3>(3, 0)
+            .copy_from(&(skew_symmetric_v(&self.position) * R));
+        res.fixed_slice_mut::<3, 3>(3, 3).copy_from(R);
+        res.fixed_slice_mut::<3, 3>(6, 0)
+            .copy_from(&(skew_symmetric_v(&self.velocity) * R));
+        res.fixed_slice_mut::<3, 3>(6, 6).copy_from(R);
+
+        res
+    }
+
+    fn logmap(X: &Self, optionalH: Option<&mut Matrix9<N>>) -> Vector9<N> {
+        use crate::geometry::so3::logmap_derivative;
+
+        let omega = SO3::logmap(&X.attitude, None);
+
+        // The inverse of the left Jacobian of SO(3) maps position and velocity back
+        let Jl_inv = logmap_derivative(&-omega);
+        let log = join(&omega, &(Jl_inv * X.position), &(Jl_inv * X.velocity));
+
+        if let Some(H) = optionalH {
+            *H = nav_state_right_jacobian_inverse(&log);
+        }
+
+        log
+    }
+
+    fn expmap(xi: &Vector9<N>) -> Self {
+        Self::expmap_with_derivative(xi, None)
+    }
+
+    fn expmap_with_derivative(xi: &Vector9<N>, optionalH: Option<&mut Matrix9<N>>) -> Self {
+        if let Some(H) = optionalH {
+            *H = nav_state_right_jacobian(xi);
+        }
+
+        let (omega, rho, nu) = split(xi);
+
+        // The left Jacobian of SO(3) maps position and velocity, as for SE(3)
+        let Jl = so3_left_jacobian(&omega);
+
+        NavState::new(SO3::expmap(&omega), Jl * rho, Jl * nu)
+    }
+}
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> Manifold for NavState<N> {
+    type Field = N;
+    type TangentDim = U9;
+
+    fn local(origin: &Self, other: &Self) -> Vector9<N> {
+        NavState::logmap(&origin.between(other), None)
+    }
+
+    fn retract(origin: &Self, v: &Vector9<N>) -> Self {
+        origin * NavState::expmap(v)
+    }
+
+    fn local_with_derivative(
+        origin: &Self,
+        other: &Self,
+        H1: Option<&mut Matrix9<N>>,
+        H2: Option<&mut Matrix9<N>>,
+    ) -> Vector9<N> {
+        group_local_with_derivative(origin, other, H1, H2)
+    }
+
+    fn retract_with_derivative(
+        origin: &Self,
+        v: &Vector9<N>,
+        H1: Option<&mut Matrix9<N>>,
+        H2: Option<&mut Matrix9<N>>,
+    ) -> Self {
+        group_retract_with_derivative(origin, v, H1, H2)
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod test {
+    use super::*;
+    use crate::core::group::check_group_derivatives;
+    use crate::core::numerical_derivative::{numerical_derivative11, DEFAULT_DELTA};
+    use crate::geometry::se3::SE3;
+
+    fn xi(w: [f64; 3], p: [f64; 3], v: [f64; 3]) -> Vector9<f64> {
+        join(&Vector3::from(w), &Vector3::from(p), &Vector3::from(v))
+    }
+
+    #[test]
+    fn expmap_logmap_invariant() {
+        let w = xi([1., 1.2, 1.3], [1., 1.4, 1.3], [-0.5, 0.2, 0.1]);
+
+        let exp = NavState::expmap(&w);
+
+        assert_relative_eq!(
+            (w - NavState::logmap(&exp, None)).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn expmap_matches_se3() {
+        let w = xi([0.1, -0.3, 0.2], [1., 2., 3.], [-1., 0.5, 2.]);
+        let exp = NavState::expmap(&w);
+
+        let pose = SE3::expmap(&Vector6::new(0.1, -0.3, 0.2, 1., 2., 3.));
+        let vel = SE3::expmap(&Vector6::new(0.1, -0.3, 0.2, -1., 0.5, 2.));
+
+        assert_relative_eq!(
+            (exp.position - pose.translation.vector).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+        assert_relative_eq!(
+            (exp.velocity - vel.translation.vector).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn expmap_logmap_invariant_near_zero() {
+        for theta in [2.4e-3, 2e-3, 1e-5] {
+            let omega = Vector3::new(1.0, -2.0, 2.0) * (theta / 3.0);
+            let w = xi(omega.into(), [1., 1.4, 1.3], [-0.5, 0.2, 0.1]);
+
+            let exp = NavState::expmap(&w);
+
+            assert_relative_eq!(
+                (w - NavState::logmap(&exp, None)).norm(),
+                0.0,
+                epsilon = 1e-13
+            );
+        }
+    }
+
+    #[test]
+    fn inverse_works() {
+        let a = NavState::expmap(&xi([0.3, 0.1, -0.2], [1., -2., 0.5], [0.2, 0.1, 3.0]));
+
+        let id = a * LieGroup::inverse(&a);
+        assert_relative_eq!(NavState::logmap(&id, None).norm(), 0.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn adjoint_map_works() {
+        // X exp(xi) X^-1 = exp(Ad(X) xi)
+        let X = NavState::expmap(&xi([0.3, 0.1, -0.2], [1., -2., 0.5], [0.2, 0.1, 3.0]));
+        let w = xi([-0.2, 0.4, 0.1], [0.3, 0.2, -1.0], [0.5, -0.5, 0.7]);
+
+        let lhs = X * NavState::expmap(&w) * LieGroup::inverse(&X);
+        let rhs = NavState::expmap(&(X.adjoint_map() * w));
+
+        assert_relative_eq!(NavState::local(&lhs, &rhs).norm(), 0.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn expmap_logmap_derivatives() {
+        for w in [
+            xi([0.1, 0.27, -0.2], [1.0, -2.0, 0.5], [0.3, 0.2, -1.0]),
+            xi([1e-6, 0.0, -1e-6], [1.0, -2.0, 0.5], [0.3, 0.2, -1.0]),
+        ] {
+            let mut actual_dexp = Matrix9::zeros();
+            let X = NavState::expmap_with_derivative(&w, Some(&mut actual_dexp));
+            let expected_dexp =
+                numerical_derivative11(|w: &Vector9<f64>| NavState::expmap(w), &w, DEFAULT_DELTA);
+            assert_relative_eq!((actual_dexp - expected_dexp).norm(), 0.0, epsilon = 1e-6);
+
+            let mut actual_dlog = Matrix9::zeros();
+            NavState::logmap(&X, Some(&mut actual_dlog));
+            let expected_dlog = numerical_derivative11(
+                |X: &NavState<f64>| NavState::logmap(X, None),
+                &X,
+                DEFAULT_DELTA,
+            );
+            assert_relative_eq!((actual_dlog - expected_dlog).norm(), 0.0, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn group_derivatives() {
+        let a = NavState::expmap(&xi([0.1, 0.27, -0.2], [1.0, -2.0, 0.5], [0.3, 0.2, -1.0]));
+        let b = NavState::expmap(&xi([-1.0, 0.5, 0.3], [0.2, 0.1, 3.0], [1.0, 0.0, -0.5]));
+        let v = xi([0.3, -0.4, 0.9], [0.5, 0.6, -0.7], [0.1, 0.2, 0.3]);
+
+        check_group_derivatives(&a, &b, &v);
+    }
+
+    #[test]
+    fn works_in_single_precision() {
+        let w =
+            Vector9::<f32>::from_column_slice(&[0.1, -0.4, 0.3, 1.0, 2.0, -0.5, 0.3, 0.2, -1.0]);
+
+        let mut dexp = Matrix9::zeros();
+        let X = NavState::expmap_with_derivative(&w, Some(&mut dexp));
+        let mut dlog = Matrix9::zeros();
+        let log = NavState::logmap(&X, Some(&mut dlog));
+
+        assert_relative_eq!((w - log).norm(), 0.0, epsilon = 1e-4);
+        assert_relative_eq!(
+            (dlog * dexp - Matrix9::identity()).norm(),
+            0.0,
+            epsilon = 1e-4
+        );
+    }
+}
@@ -0,0 +1,304 @@
+pub use crate::core::group::LieGroup;
+pub use crate::core::manifold::Manifold;
+use crate::core::manifold::{group_local_with_derivative, group_retract_with_derivative};
+use crate::core::scalar::epsilon_pow;
+use nalgebra::{convert, Matrix2, Matrix3, OMatrix, OVector, RealField, Vector2, Vector3, U3};
+
+use nalgebra as na;
+
+pub use na::Isometry2 as SE2;
+
+/// Coefficients (sin(w) / w, (1 - cos(w)) / w) of the V matrix that maps the
+/// translational velocity of a twist to the translation of its expmap
+fn v_coefficients<N: RealField + Copy>(w: N) -> (N, N) {
+    if w.abs() > epsilon_pow(1.0 / 3.0) {
+        // 1 - cos(w) = 2 sin(w / 2)^2 without the cancellation for small w
+        let s = (w * convert(0.5)).sin();
+        (w.sin() / w, s * s * convert(2.0) / w)
+    } else {
+        let w2 = w * w;
+        (
+            N::one() - w2 / convert(6.0),
+            w * convert(0.5) - w * w2 / convert(24.0),
+        )
+    }
+}
+
+/// (w / 2) * cot(w / 2), the diagonal of the inverse of the V matrix
+fn half_cot_half<N: RealField + Copy>(w: N) -> N {
+    let half = convert::<f64, N>(0.5);
+    if w.abs() > epsilon_pow(1.0 / 3.0) {
+        half * w / (half * w).tan()
+    } else {
+        N::one() - w * w / convert(12.0)
+    }
+}
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> LieGroup<N> for SE2<N> {
+    type D = U3;
+
+    fn between(&self, g: &Self) -> Self {
+        self.inverse() * g
+    }
+
+    fn inverse(&self) -> Self {
+        // Resolves to the inherent nalgebra inverse
+        SE2::inverse(self)
+    }
+
+    fn adjoint_map(&self) -> OMatrix<N, U3, U3> {
+        let R = self.rotation.to_rotation_matrix();
+        let t = self.translation.vector;
+
+        // Twists are ordered [omega; v], so Ad = [1 0; -J t R] with J the 90 degree rotation
+        let mut res = Matrix3::zeros();
+        res[(0, 0)] = N::one();
+        res[(1, 0)] = t.y;
+        res[(2, 0)] = -t.x;
+        res.fixed_slice_mut::<2, 2>(1, 1).copy_from(R.matrix());
+
+        res
+    }
+
+    fn logmap(P: &Self, optionalH: Option<&mut OMatrix<N, U3, U3>>) -> OVector<N, U3> {
+        let w = P.rotation.angle();
+        let t = P.translation.vector;
+        let half_w = w * convert(0.5);
+
+        // Invert t = V v in closed form
+        let a = half_cot_half(w);
+        let V_inv = Matrix2::new(a, half_w, -half_w, a);
+        let v = V_inv * t;
+
+        let log = Vector3::new(w, v.x, v.y);
+
+        if let Some(H) = optionalH {
+            *H = se2_right_jacobian_inverse(&log);
+        }
+
+        log
+    }
+
+    fn expmap(xi: &OVector<N, U3>) -> Self {
+        Self::expmap_with_derivative(xi, None)
+    }
+
+    fn expmap_with_derivative(
+        xi: &OVector<N, U3>,
+        optionalH: Option<&mut OMatrix<N, U3, U3>>,
+    ) -> Self {
+        if let Some(H) = optionalH {
+            *H = se2_right_jacobian(xi);
+        }
+
+        let w = xi[0];
+        let (s, c) = v_coefficients(w);
+        let V = Matrix2::new(s, -c, c, s);
+        let t = V * Vector2::new(xi[1], xi[2]);
+
+        SE2::new(t, w)
+    }
+}
+
+/// Right Jacobian of SE(2), i.e. the derivative of the expmap:
+/// `exp(xi + d) = exp(xi) * exp(J * d)` for `d --> 0`.
+#[allow(non_snake_case)]
+pub fn se2_right_jacobian<N: RealField + Copy>(xi: &Vector3<N>) -> Matrix3<N> {
+    let (w, v1, v2) = (xi[0], xi[1], xi[2]);
+    let (s, c) = v_coefficients(w);
+    let (zero, one) = (N::zero(), N::one());
+
+    // The coupling terms are (v1 (w - sin(w)) + v2 (cos(w) - 1)) / w^2 and
+    // (v1 (1 - cos(w)) + v2 (w - sin(w))) / w^2
+    let w2 = w * w;
+    let (a, b) = if w.abs() > epsilon_pow(1.0 / 6.0) {
+        // w - sin(w) still cancels to an error of epsilon / w in a, hence the wide series
+        let s = (w * convert(0.5)).sin();
+        ((w - w.sin()) / w2, s * s * convert(2.0) / w2)
+    } else {
+        // The series drop O(w^6) relative terms, which are below epsilon here
+        (
+            w * (convert::<f64, N>(1.0 / 6.0) - w2 / convert(120.0) + w2 * w2 / convert(5040.0)),
+            convert::<f64, N>(0.5) - w2 / convert(24.0) + w2 * w2 / convert(720.0),
+        )
+    };
+
+    Matrix3::new(
+        one,
+        zero,
+        zero,
+        a * v1 - b * v2,
+        s,
+        c,
+        b * v1 + a * v2,
+        -c,
+        s,
+    )
+}
+
+/// Inverse of the right Jacobian of SE(2), i.e. the derivative of the logmap:
+/// `log(exp(xi) * exp(d)) = xi + J * d` for `d --> 0`.
+#[allow(non_snake_case)]
+pub fn se2_right_jacobian_inverse<N: RealField + Copy>(xi: &Vector3<N>) -> Matrix3<N> {
+    let J = se2_right_jacobian(xi);
+
+    // J = [1 0; j A], so J^-1 = [1 0; -A^-1 j, A^-1] with A^-1 in closed form
+    let w = xi[0];
+    let half_w = w * convert(0.5);
+    let a = half_cot_half(w);
+    let A_inv = Matrix2::new(a, -half_w, half_w, a);
+    let j = A_inv * Vector2::new(J[(1, 0)], J[(2, 0)]);
+
+    let mut res = Matrix3::zeros();
+    res[(0, 0)] = N::one();
+    res[(1, 0)] = -j.x;
+    res[(2, 0)] = -j.y;
+    res.fixed_slice_mut::<2, 2>(1, 1).copy_from(&A_inv);
+    res
+}
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> Manifold for SE2<N> {
+    type Field = N;
+    type TangentDim = U3;
+
+    fn local(origin: &Self, other: &Self) -> OVector<N, U3> {
+        SE2::logmap(&origin.between(other), None)
+    }
+
+    fn retract(origin: &Self, v: &OVector<N, U3>) -> Self {
+        origin * SE2::expmap(v)
+    }
+
+    fn local_with_derivative(
+        origin: &Self,
+        other: &Self,
+        H1: Option<&mut OMatrix<N, U3, U3>>,
+        H2: Option<&mut OMatrix<N, U3, U3>>,
+    ) -> OVector<N, U3> {
+        group_local_with_derivative(origin, other, H1, H2)
+    }
+
+    fn retract_with_derivative(
+        origin: &Self,
+        v: &OVector<N, U3>,
+        H1: Option<&mut OMatrix<N, U3, U3>>,
+        H2: Option<&mut OMatrix<N, U3, U3>>,
+    ) -> Self {
+        group_retract_with_derivative(origin, v, H1, H2)
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod test {
+    use super::*;
+    use crate::core::group::check_group_derivatives;
+    use crate::core::numerical_derivative::{numerical_derivative11, DEFAULT_DELTA};
+
+    fn check_derivatives(xi: Vector3<f64>) {
+        let mut actual_dexp = Matrix3::zeros();
+        let P = SE2::expmap_with_derivative(&xi, Some(&mut actual_dexp));
+        let expected_dexp =
+            numerical_derivative11(|xi: &Vector3<f64>| SE2::expmap(xi), &xi, DEFAULT_DELTA);
+        assert_relative_eq!((actual_dexp - expected_dexp).norm(), 0.0, epsilon = 1e-6);
+
+        let mut actual_dlog = Matrix3::zeros();
+        SE2::logmap(&P, Some(&mut actual_dlog));
+        let expected_dlog =
+            numerical_derivative11(|P: &SE2<f64>| SE2::logmap(P, None), &P, DEFAULT_DELTA);
+        assert_relative_eq!((actual_dlog - expected_dlog).norm(), 0.0, epsilon = 1e-6);
+
+        assert_relative_eq!(
+            (se2_right_jacobian_inverse(&xi) * se2_right_jacobian(&xi) - Matrix3::identity())
+                .norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn expmap_logmap_invariant() {
+        let w = Vector3::new(1.2, 1.0, -1.4);
+
+        let exp = SE2::expmap(&w);
+
+        assert_relative_eq!((w - SE2::logmap(&exp, None)).norm(), 0.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn expmap_matches_rotation_about_point() {
+        // Rotating by pi/2 about (0, 1) moves the origin to (1, 1)
+        let w = std::f64::consts::FRAC_PI_2;
+        let exp = SE2::expmap(&Vector3::new(w, w, 0.0));
+
+        assert_relative_eq!(
+            (exp.translation.vector - Vector2::new(1.0, 1.0)).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn expmap_logmap_derivatives() {
+        check_derivatives(Vector3::new(0.3, 1.0, -2.0));
+        check_derivatives(Vector3::new(2.5, -0.4, 0.7));
+    }
+
+    #[test]
+    fn expmap_logmap_derivatives_near_zero() {
+        check_derivatives(Vector3::new(1e-6, 1.0, 2.0));
+        check_derivatives(Vector3::new(0.0, 1.0, 2.0));
+    }
+
+    #[test]
+    fn right_jacobian_near_cutover() {
+        // Reference values of the coupling terms from their series, exact for small w
+        let series = |w: f64| {
+            let w2 = w * w;
+            (
+                w * (1.0 / 6.0 - w2 / 120.0 + w2 * w2 / 5040.0 - w2 * w2 * w2 / 362880.0),
+                0.5 - w2 / 24.0 + w2 * w2 / 720.0 - w2 * w2 * w2 / 40320.0,
+            )
+        };
+
+        // Just above the old and the current switch-over to the series
+        for w in [7e-6, 2.5e-3, 0.1] {
+            let J = se2_right_jacobian(&Vector3::new(w, 1.0, -2.0));
+            let (a, b) = series(w);
+
+            assert_relative_eq!(J[(1, 0)], a + 2.0 * b, epsilon = 1e-13);
+            assert_relative_eq!(J[(2, 0)], b - 2.0 * a, epsilon = 1e-13);
+        }
+    }
+
+    #[test]
+    fn group_derivatives() {
+        let a = SE2::expmap(&Vector3::new(0.4, 1.0, -2.0));
+        let b = SE2::expmap(&Vector3::new(-1.3, 0.2, 3.0));
+        let v = Vector3::new(0.3, -0.4, 0.9);
+
+        check_group_derivatives(&a, &b, &v);
+    }
+
+    #[test]
+    fn works_in_single_precision() {
+        for w in &[
+            Vector3::new(0.7f32, 1.0, -2.0),
+            Vector3::new(1e-3, 1.0, -2.0),
+        ] {
+            let mut dexp = Matrix3::zeros();
+            let T = SE2::expmap_with_derivative(w, Some(&mut dexp));
+            let mut dlog = Matrix3::zeros();
+            let log = SE2::logmap(&T, Some(&mut dlog));
+
+            assert_relative_eq!((w - log).norm(), 0.0, epsilon = 1e-4);
+            assert_relative_eq!(
+                (dlog * dexp - Matrix3::identity()).norm(),
+                0.0,
+                epsilon = 1e-4
+            );
+        }
+    }
+}
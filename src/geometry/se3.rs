@@ -1,77 +1,75 @@
 pub use crate::core::group::LieGroup;
 pub use crate::core::manifold::Manifold;
-use nalgebra::{Matrix6, OMatrix, Vector6, U6};
+use crate::core::manifold::{group_local_with_derivative, group_retract_with_derivative};
+use crate::core::scalar::epsilon_pow;
+use nalgebra::{convert, Matrix3, Matrix6, OMatrix, OVector, RealField, Vector3, Vector6, U6};
 
 use nalgebra as na;
 
 pub use na::Isometry3 as SE3;
 
 #[allow(non_snake_case)]
-impl LieGroup<f64> for SE3<f64> {
+impl<N: RealField + Copy> LieGroup<N> for SE3<N> {
     type D = U6;
 
     fn between(&self, g: &Self) -> Self {
         self.inverse() * g
     }
 
-    fn adjoint_map(&self) -> OMatrix<f64, U6, U6> {
+    fn inverse(&self) -> Self {
+        // Resolves to the inherent nalgebra inverse
+        SE3::inverse(self)
+    }
+
+    fn adjoint_map(&self) -> OMatrix<N, U6, U6> {
         use crate::core::matrix::skew_symmetric;
 
-        let mut res = OMatrix::<f64, U6, U6>::zeros();
+        let mut res = OMatrix::<N, U6, U6>::zeros();
 
         let R = self.rotation.to_rotation_matrix();
 
-        res.fixed_slice_mut::<3, 3>(0, 0).copy_from(&R.matrix());
-        res.fixed_slice_mut::<3, 3>(0, 3).copy_from(
+        // Twists are ordered [omega; v], so Ad = [R 0; [t]x R R]
+        res.fixed_slice_mut::<3, 3>(0, 0).copy_from(R.matrix());
+        res.fixed_slice_mut::<3, 3>(3, 0).copy_from(
             &(skew_symmetric(self.translation.x, self.translation.y, self.translation.z) * R),
         );
-        res.fixed_slice_mut::<3, 3>(3, 3).copy_from(&R.matrix());
+        res.fixed_slice_mut::<3, 3>(3, 3).copy_from(R.matrix());
 
         res
     }
 
-    fn logmap(P: &Self, optionalH: Option<&mut Matrix6<f64>>) -> Vector6<f64> {
-        use crate::core::matrix::*;
+    fn logmap(P: &Self, optionalH: Option<&mut OMatrix<N, U6, U6>>) -> OVector<N, U6> {
         use crate::geometry::so3::*;
 
-        if let Some(_H) = optionalH {
-            unimplemented!("NOT IMPLEMENTED");
-        }
-
         let w = SO3::logmap(&P.rotation.to_rotation_matrix(), None);
-        let T = P.translation.vector;
-        let t = w.norm();
-        if t < 1e-10 {
-            let mut log = Vector6::zeros();
-            log.fixed_slice_mut::<3, 1>(0, 0).copy_from(&w);
-            log.fixed_slice_mut::<3, 1>(3, 0).copy_from(&T);
-            log
-        } else {
-            let W = skew_symmetric_v(&(w / t));
-            // Formula from Agrawal06iros, equation (14)
-            // simplified with Mathematica, and multiplying in T to avoid matrix math
-            let Tan = (0.5 * t).tan();
-            let WT = W * T;
-            let u = T - (0.5 * t) * WT + (1. - t / (2. * Tan)) * (W * WT);
-            let mut log = Vector6::zeros();
-            log.fixed_slice_mut::<3, 1>(0, 0).copy_from(&w);
-            log.fixed_slice_mut::<3, 1>(3, 0).copy_from(&u);
-            log
+
+        // The inverse of the left Jacobian of SO(3) maps the translation back
+        let u = logmap_derivative(&-w) * P.translation.vector;
+        let mut log = Vector6::zeros();
+        log.fixed_slice_mut::<3, 1>(0, 0).copy_from(&w);
+        log.fixed_slice_mut::<3, 1>(3, 0).copy_from(&u);
+
+        if let Some(H) = optionalH {
+            *H = se3_right_jacobian_inverse(&log);
         }
+
+        log
     }
 
-    fn expmap(xi: &Vector6<f64>) -> Self {
+    fn expmap(xi: &OVector<N, U6>) -> Self {
         Self::expmap_with_derivative(xi, None)
     }
 
-    /** Modified from Murray94book version (which assumes w and v normalized?) */
+    /// The translation of the expmap is `V v`, with V the left Jacobian of SO(3)
     #[inline]
-    fn expmap_with_derivative(xi: &Vector6<f64>, optionalH: Option<&mut Matrix6<f64>>) -> Self {
+    fn expmap_with_derivative(
+        xi: &OVector<N, U6>,
+        optionalH: Option<&mut OMatrix<N, U6, U6>>,
+    ) -> Self {
         use crate::geometry::so3::*;
-        use na::Vector3;
 
-        if let Some(_H) = optionalH {
-            unimplemented!("NOT IMPLEMENTED");
+        if let Some(H) = optionalH {
+            *H = se3_right_jacobian(xi);
         }
 
         // get angular velocity omega and translational velocity v from twist xi
@@ -80,34 +78,193 @@ impl LieGroup<f64> for SE3<f64> {
             Vector3::new(xi[3], xi[4], xi[5]),
         );
 
-        let R = SO3::expmap(&omega);
-        let theta2 = omega.dot(&omega);
-        if theta2 > f64::EPSILON {
-            let t_parallel = omega * omega.dot(&v); // translation parallel to axis
-            let omega_cross_v = omega.cross(&v); // points towards axis
-            let t = (omega_cross_v - R * omega_cross_v + t_parallel) / theta2;
-            SE3::from_parts(t.into(), R.into())
-        } else {
-            SE3::from_parts(v.into(), R.into())
-        }
+        let t = so3_left_jacobian(&omega) * v;
+        SE3::from_parts(t.into(), SO3::expmap(&omega).into())
     }
 }
 
-impl Manifold for SE3<f64> {
-    type TangentVector = Vector6<f64>;
+/// The coupling block Q of the right Jacobian of SE(3), from Barfoot14tro eq. (102),
+/// with the signs of the odd-order terms flipped for the right Jacobian
+#[allow(non_snake_case)]
+pub(crate) fn compute_q_for_expmap_derivative<N: RealField + Copy>(xi: &Vector6<N>) -> Matrix3<N> {
+    use crate::core::matrix::skew_symmetric;
+
+    let W = skew_symmetric(xi[0], xi[1], xi[2]);
+    let V = skew_symmetric(xi[3], xi[4], xi[5]);
+
+    let WV = W * V;
+    let VW = V * W;
+    let WVW = WV * W;
+    let WWV = W * WV;
+    let VWW = VW * W;
+    let WVWW = WVW * W;
+    let WWVW = W * WVW;
+
+    let phi2 = xi[0] * xi[0] + xi[1] * xi[1] + xi[2] * xi[2];
+    let c = |x: f64| convert::<f64, N>(x);
+
+    let (a, b, cc) = if phi2 > epsilon_pow(0.2) {
+        let phi = phi2.sqrt();
+        let (s, co) = (phi.sin(), phi.cos());
+        let phi3 = phi2 * phi;
+        let phi4 = phi3 * phi;
+        let phi5 = phi4 * phi;
+
+        let a = (phi - s) / phi3;
+        let b = (N::one() - phi2 / c(2.0) - co) / phi4;
+        let cc = -(b - (phi - s - phi3 / c(6.0)) * c(3.0) / phi5) * c(0.5);
+        (a, b, cc)
+    } else {
+        // Taylor expansion of the coefficients above
+        (
+            c(1.0 / 6.0) - phi2 / c(120.0),
+            -c(1.0 / 24.0) + phi2 / c(720.0),
+            c(1.0 / 120.0) - phi2 / c(2520.0),
+        )
+    };
+
+    -V * c(0.5) + (WV + VW - WVW) * a + (WWV + VWW - WVW * c(3.0)) * b + (WVWW + WWVW) * cc
+}
+
+/// Right Jacobian of SE(3), i.e. the derivative of the expmap:
+/// `exp(xi + d) = exp(xi) * exp(J * d)` for `d --> 0`.
+#[allow(non_snake_case)]
+pub fn se3_right_jacobian<N: RealField + Copy>(xi: &Vector6<N>) -> Matrix6<N> {
+    use crate::geometry::so3::*;
+
+    let mut Jw = Matrix3::zeros();
+    SO3::expmap_with_derivative(&Vector3::new(xi[0], xi[1], xi[2]), Some(&mut Jw));
+    let Q = compute_q_for_expmap_derivative(xi);
 
-    fn local(origin: &Self, other: &Self) -> Self::TangentVector {
-        SE3::logmap(&origin.between(&other), None)
+    let mut J = Matrix6::zeros();
+    J.fixed_slice_mut::<3, 3>(0, 0).copy_from(&Jw);
+    J.fixed_slice_mut::<3, 3>(3, 0).copy_from(&Q);
+    J.fixed_slice_mut::<3, 3>(3, 3).copy_from(&Jw);
+    J
+}
+
+/// Inverse of the right Jacobian of SE(3), i.e. the derivative of the logmap:
+/// `log(exp(xi) * exp(d)) = xi + J * d` for `d --> 0`.
+#[allow(non_snake_case)]
+pub fn se3_right_jacobian_inverse<N: RealField + Copy>(xi: &Vector6<N>) -> Matrix6<N> {
+    use crate::geometry::so3::*;
+
+    let Jw = logmap_derivative(&Vector3::new(xi[0], xi[1], xi[2]));
+    let Q = compute_q_for_expmap_derivative(xi);
+
+    let mut J = Matrix6::zeros();
+    J.fixed_slice_mut::<3, 3>(0, 0).copy_from(&Jw);
+    J.fixed_slice_mut::<3, 3>(3, 0).copy_from(&(-Jw * Q * Jw));
+    J.fixed_slice_mut::<3, 3>(3, 3).copy_from(&Jw);
+    J
+}
+
+/// Left Jacobian of SE(3): `exp(xi + d) = exp(J * d) * exp(xi)` for `d --> 0`.
+pub fn se3_left_jacobian<N: RealField + Copy>(xi: &Vector6<N>) -> Matrix6<N> {
+    se3_right_jacobian(&-xi)
+}
+
+/// Inverse of the left Jacobian of SE(3): `log(exp(d) * exp(xi)) = xi + J * d` for `d --> 0`.
+pub fn se3_left_jacobian_inverse<N: RealField + Copy>(xi: &Vector6<N>) -> Matrix6<N> {
+    se3_right_jacobian_inverse(&-xi)
+}
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> Manifold for SE3<N> {
+    type Field = N;
+    type TangentDim = U6;
+
+    fn local(origin: &Self, other: &Self) -> OVector<N, U6> {
+        SE3::logmap(&origin.between(other), None)
+    }
+
+    fn retract(origin: &Self, v: &OVector<N, U6>) -> Self {
+        origin * SE3::expmap(v)
     }
 
-    fn retract(origin: &Self, v: &Self::TangentVector) -> Self {
-        origin * SE3::expmap(&v)
+    fn local_with_derivative(
+        origin: &Self,
+        other: &Self,
+        H1: Option<&mut OMatrix<N, U6, U6>>,
+        H2: Option<&mut OMatrix<N, U6, U6>>,
+    ) -> OVector<N, U6> {
+        group_local_with_derivative(origin, other, H1, H2)
+    }
+
+    fn retract_with_derivative(
+        origin: &Self,
+        v: &OVector<N, U6>,
+        H1: Option<&mut OMatrix<N, U6, U6>>,
+        H2: Option<&mut OMatrix<N, U6, U6>>,
+    ) -> Self {
+        group_retract_with_derivative(origin, v, H1, H2)
     }
 }
 
 #[cfg(test)]
+#[allow(non_snake_case)]
 mod test {
     use super::*;
+    use crate::core::group::check_group_derivatives;
+    use crate::core::numerical_derivative::{numerical_derivative11, DEFAULT_DELTA};
+
+    fn check_derivatives(xi: Vector6<f64>) {
+        let mut actual_dexp = Matrix6::zeros();
+        let P = SE3::expmap_with_derivative(&xi, Some(&mut actual_dexp));
+        let expected_dexp =
+            numerical_derivative11(|xi: &Vector6<f64>| SE3::expmap(xi), &xi, DEFAULT_DELTA);
+        assert_relative_eq!((actual_dexp - expected_dexp).norm(), 0.0, epsilon = 1e-6);
+
+        let mut actual_dlog = Matrix6::zeros();
+        SE3::logmap(&P, Some(&mut actual_dlog));
+        let expected_dlog =
+            numerical_derivative11(|P: &SE3<f64>| SE3::logmap(P, None), &P, DEFAULT_DELTA);
+        assert_relative_eq!((actual_dlog - expected_dlog).norm(), 0.0, epsilon = 1e-6);
+
+        let expected_left = numerical_derivative11(
+            |d: &Vector6<f64>| SE3::logmap(&(SE3::expmap(&(xi + d)) * SE3::expmap(&-xi)), None),
+            &Vector6::zeros(),
+            DEFAULT_DELTA,
+        );
+        assert_relative_eq!(
+            (se3_left_jacobian(&xi) - expected_left).norm(),
+            0.0,
+            epsilon = 1e-6
+        );
+
+        assert_relative_eq!(
+            (se3_right_jacobian_inverse(&xi) * se3_right_jacobian(&xi) - Matrix6::identity())
+                .norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+        assert_relative_eq!(
+            (se3_left_jacobian_inverse(&xi) * se3_left_jacobian(&xi) - Matrix6::identity()).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn group_derivatives() {
+        let a = SE3::expmap(&Vector6::new(0.1, 0.27, -0.2, 1.0, -2.0, 0.5));
+        let b = SE3::expmap(&Vector6::new(-1.0, 0.5, 0.3, 0.2, 0.1, 3.0));
+        let v = Vector6::new(0.3, -0.4, 0.9, 0.5, 0.6, -0.7);
+
+        check_group_derivatives(&a, &b, &v);
+    }
+
+    #[test]
+    fn expmap_logmap_derivatives() {
+        check_derivatives(Vector6::new(0.1, 0.27, -0.2, 1.0, -2.0, 0.5));
+        check_derivatives(Vector6::new(1.0, 1.2, 1.3, 1.0, 1.4, 1.3));
+    }
+
+    #[test]
+    fn expmap_logmap_derivatives_near_zero() {
+        check_derivatives(Vector6::new(1e-4, -2e-4, 3e-4, 1.0, 2.0, 3.0));
+        check_derivatives(Vector6::new(0.0, 0.0, 0.0, 1.0, 2.0, 3.0));
+    }
 
     #[test]
     fn test_between() {
@@ -138,4 +295,85 @@ mod test {
 
         assert_relative_eq!((w - SE3::logmap(&exp, None)).norm(), 0.0, epsilon = 1e-10);
     }
+
+    #[test]
+    fn expmap_logmap_invariant_near_zero() {
+        // Around the switch-over of the series for V, and well below it
+        for theta in [2.4e-3, 2e-3, 1e-5] {
+            let omega = Vector3::new(1.0, -2.0, 2.0) * (theta / 3.0);
+            let w = Vector6::new(omega.x, omega.y, omega.z, 1.0, -2.0, 0.5);
+
+            let exp = SE3::expmap(&w);
+
+            assert_relative_eq!((w - SE3::logmap(&exp, None)).norm(), 0.0, epsilon = 1e-13);
+        }
+    }
+
+    #[test]
+    fn works_in_single_precision() {
+        for w in &[
+            Vector6::new(0.1f32, -0.4, 0.3, 1.0, 2.0, -0.5),
+            Vector6::new(1e-3, 2e-3, -1e-3, 1.0, 2.0, -0.5),
+        ] {
+            let mut dexp = Matrix6::zeros();
+            let T = SE3::expmap_with_derivative(w, Some(&mut dexp));
+            let mut dlog = Matrix6::zeros();
+            let log = SE3::logmap(&T, Some(&mut dlog));
+
+            assert_relative_eq!((w - log).norm(), 0.0, epsilon = 1e-4);
+            assert_relative_eq!(
+                (dlog * dexp - Matrix6::identity()).norm(),
+                0.0,
+                epsilon = 1e-4
+            );
+        }
+    }
+
+    #[test]
+    fn derivatives_in_single_precision() {
+        // Also just below the switch-over points of the series for f32, at theta = 0.0186
+        // for V and the SO(3) Jacobian, and at theta = 0.2 for the coupling block Q
+        let axis = Vector3::new(1.0f32, -2.0, 2.0) / 3.0;
+        let near_cutovers = [0.07f32, 0.018, 0.19].map(|theta| {
+            let omega = axis * theta;
+            Vector6::new(omega.x, omega.y, omega.z, 1.0, -2.0, 0.5)
+        });
+        for w in [
+            Vector6::new(0.3f32, -0.7, 0.2, 1.0, -2.0, 0.5),
+            Vector6::new(1e-3, 2e-3, -1e-3, 1.0, -2.0, 0.5),
+        ]
+        .iter()
+        .chain(near_cutovers.iter())
+        {
+            let mut dexp = Matrix6::zeros();
+            let T = SE3::expmap_with_derivative(w, Some(&mut dexp));
+            let mut dlog = Matrix6::zeros();
+            SE3::logmap(&T, Some(&mut dlog));
+
+            // f32 central differences are only good to about 1e-3
+            let expected_dexp = numerical_derivative11(|w: &Vector6<f32>| SE3::expmap(w), w, 1e-2);
+            let expected_dlog =
+                numerical_derivative11(|T: &SE3<f32>| SE3::logmap(T, None), &T, 1e-2);
+            assert_relative_eq!((dexp - expected_dexp).norm(), 0.0, epsilon = 1e-3);
+            assert_relative_eq!((dlog - expected_dlog).norm(), 0.0, epsilon = 1e-3);
+
+            // They agree with the double precision Jacobians up to f32 rounding
+            let w = w.cast::<f64>();
+            assert_relative_eq!(
+                (T.translation.vector.cast::<f64>() - SE3::expmap(&w).translation.vector).norm(),
+                0.0,
+                epsilon = 1e-6
+            );
+            assert_relative_eq!(
+                (dexp.cast::<f64>() - se3_right_jacobian(&w)).norm(),
+                0.0,
+                epsilon = 1e-5
+            );
+            assert_relative_eq!(
+                (dlog.cast::<f64>() - se3_right_jacobian_inverse(&w)).norm(),
+                0.0,
+                epsilon = 1e-5
+            );
+        }
+    }
 }
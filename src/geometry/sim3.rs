@@ -0,0 +1,481 @@
+pub use crate::core::group::LieGroup;
+pub use crate::core::manifold::Manifold;
+use crate::core::manifold::{group_local_with_derivative, group_retract_with_derivative};
+use crate::core::matrix::skew_symmetric_v;
+use crate::core::scalar::epsilon_pow;
+use nalgebra::{
+    convert, Complex, Matrix3, OMatrix, OVector, RealField, Translation3, UnitQuaternion, U7,
+};
+
+use nalgebra as na;
+
+pub use na::Similarity3 as Sim3;
+
+/// The 7x7 Jacobians of Sim(3)
+pub type Matrix7<N> = OMatrix<N, U7, U7>;
+/// Twists of Sim(3), ordered [omega; v; lambda] with lambda the log of the scale
+pub type Vector7<N> = OVector<N, U7>;
+
+/// The rotation and scale part [omega]x + lambda * I of the hat operator
+fn rotation_scale_hat<N: RealField + Copy>(xi: &Vector7<N>) -> Matrix3<N> {
+    use crate::core::matrix::skew_symmetric;
+
+    skew_symmetric(xi[0], xi[1], xi[2]) + Matrix3::identity() * xi[6]
+}
+
+/// The moments `m[k]` = integral of tau^k e^(lambda tau) over [0, 1], for k = 0..=7
+fn scale_moments<N: RealField + Copy>(lambda: N) -> [N; 8] {
+    let mut m = [N::zero(); 8];
+    if lambda.abs() > convert(2.0) {
+        // Integration by parts, m_k = (e^lambda - k m_(k-1)) / lambda, is stable for large lambda
+        let e = lambda.exp();
+        m[0] = lambda.exp_m1() / lambda;
+        for k in 1..8 {
+            m[k] = (e - m[k - 1] * convert(k as f64)) / lambda;
+        }
+    } else {
+        // Taylor expansion m_k = sum_j lambda^j / (j! (k + j + 1)), converged after 24 terms
+        let mut term = N::one();
+        for j in 0..24 {
+            for (k, m_k) in m.iter_mut().enumerate() {
+                *m_k += term / convert((k + j + 1) as f64);
+            }
+            term = term * lambda / convert((j + 1) as f64);
+        }
+    }
+    m
+}
+
+/// The coefficients of the V matrix `a I + b W + c W^2`, with `W = [omega]x`, together with
+/// their derivatives wrpt lambda and their derivatives wrpt theta divided by theta
+struct VCoefficients<N> {
+    a: N,
+    b: N,
+    c: N,
+    a_lambda: N,
+    b_lambda: N,
+    c_lambda: N,
+    b_theta: N,
+    c_theta: N,
+}
+
+/// V = integral of e^(tau ([omega]x + lambda I)) over [0, 1], so with exp(tau W) written out,
+/// a = int e^(lambda tau), b = int e^(lambda tau) sin(theta tau) / theta and
+/// c = int e^(lambda tau) (1 - cos(theta tau)) / theta^2
+fn v_coefficients<N: RealField + Copy>(theta2: N, lambda: N) -> VCoefficients<N> {
+    let m = scale_moments(lambda);
+    let c = |x: f64| convert::<f64, N>(x);
+
+    if theta2 > epsilon_pow(0.2) {
+        let theta = theta2.sqrt();
+        // g_k = int tau^k e^(z tau) with z = lambda + i theta holds the cosine and sine moments
+        // in its real and imaginary parts
+        let z = Complex::new(lambda, theta);
+        let ez = Complex::new(theta.cos(), theta.sin()) * lambda.exp();
+        let g0 = (ez - Complex::new(N::one(), N::zero())) / z;
+        let g1 = (ez - g0) / z;
+        let (c0, s0, c1, s1) = (g0.re, g0.im, g1.re, g1.im);
+
+        let cc = (m[0] - c0) / theta2;
+        VCoefficients {
+            a: m[0],
+            b: s0 / theta,
+            c: cc,
+            a_lambda: m[1],
+            b_lambda: s1 / theta,
+            c_lambda: (m[1] - c1) / theta2,
+            b_theta: (theta * c1 - s0) / (theta2 * theta),
+            c_theta: (s1 / theta - cc * c(2.0)) / theta2,
+        }
+    } else {
+        // Taylor expansion of sin and cos under the integrals
+        let theta4 = theta2 * theta2;
+        VCoefficients {
+            a: m[0],
+            b: m[1] - theta2 * m[3] / c(6.0) + theta4 * m[5] / c(120.0),
+            c: m[2] / c(2.0) - theta2 * m[4] / c(24.0) + theta4 * m[6] / c(720.0),
+            a_lambda: m[1],
+            b_lambda: m[2] - theta2 * m[4] / c(6.0) + theta4 * m[6] / c(120.0),
+            c_lambda: m[3] / c(2.0) - theta2 * m[5] / c(24.0) + theta4 * m[7] / c(720.0),
+            b_theta: -m[3] / c(3.0) + theta2 * m[5] / c(30.0),
+            c_theta: -m[4] / c(12.0) + theta2 * m[6] / c(180.0),
+        }
+    }
+}
+
+/// The V matrix mapping the translational velocity of a twist to the translation of its expmap,
+/// V = sum_k M^k / (k + 1)! with M = [omega]x + lambda * I
+#[allow(non_snake_case)]
+fn v_matrix<N: RealField + Copy>(xi: &Vector7<N>) -> Matrix3<N> {
+    let omega = xi.fixed_rows::<3>(0).into_owned();
+    let W = skew_symmetric_v(&omega);
+    let k = v_coefficients(omega.norm_squared(), xi[6]);
+
+    Matrix3::identity() * k.a + W * k.b + W * W * k.c
+}
+
+/// The inverse of the V matrix, which is again of the form `a I + b W + c W^2`.
+///
+/// V has eigenvalues a and a - c theta^2 +- i b theta, which only vanish for lambda = 0 and
+/// theta a non-zero multiple of 2 pi.
+#[allow(non_snake_case)]
+fn v_matrix_inverse<N: RealField + Copy>(xi: &Vector7<N>) -> Matrix3<N> {
+    let omega = xi.fixed_rows::<3>(0).into_owned();
+    let W = skew_symmetric_v(&omega);
+    let theta2 = omega.norm_squared();
+    let k = v_coefficients(theta2, xi[6]);
+
+    let p = k.a - k.c * theta2;
+    let d = p * p + k.b * k.b * theta2;
+
+    Matrix3::identity() / k.a - W * (k.b / d)
+        + W * W * ((k.b * k.b - k.a * k.c + k.c * k.c * theta2) / (k.a * d))
+}
+
+/// The adjoint representation ad(xi) of the Lie algebra, i.e. the matrix of [xi, .]
+#[allow(non_snake_case)]
+pub fn sim3_ad<N: RealField + Copy>(xi: &Vector7<N>) -> Matrix7<N> {
+    use crate::core::matrix::skew_symmetric;
+
+    let W = skew_symmetric(xi[0], xi[1], xi[2]);
+    let U = skew_symmetric(xi[3], xi[4], xi[5]);
+
+    let mut ad = Matrix7::zeros();
+    ad.fixed_slice_mut::<3, 3>(0, 0).copy_from(&W);
+    ad.fixed_slice_mut::<3, 3>(3, 0).copy_from(&U);
+    ad.fixed_slice_mut::<3, 3>(3, 3)
+        .copy_from(&rotation_scale_hat(xi));
+    ad.fixed_slice_mut::<3, 1>(3, 6)
+        .copy_from(&-xi.fixed_rows::<3>(3));
+    ad
+}
+
+/// Right Jacobian of Sim(3), i.e. the derivative of the expmap:
+/// `exp(xi + d) = exp(xi) * exp(J * d)` for `d --> 0`.
+///
+/// With exp(xi) = (R, V v, e^lambda), J = [Jw 0 0; F G h; 0 0 1] where Jw is the right Jacobian
+/// of SO(3) and [F G h] = e^-lambda R^T d(V v) / d(omega, v, lambda).
+#[allow(non_snake_case)]
+pub fn sim3_right_jacobian<N: RealField + Copy>(xi: &Vector7<N>) -> Matrix7<N> {
+    use crate::geometry::so3::*;
+
+    let omega = xi.fixed_rows::<3>(0).into_owned();
+    let v = xi.fixed_rows::<3>(3).into_owned();
+    let lambda = xi[6];
+
+    let mut Jw = Matrix3::zeros();
+    let R = SO3::expmap_with_derivative(&omega, Some(&mut Jw));
+    let W = skew_symmetric_v(&omega);
+    let k = v_coefficients(omega.norm_squared(), lambda);
+
+    let Wv = W * v;
+    let WWv = W * Wv;
+    let V = Matrix3::identity() * k.a + W * k.b + W * W * k.c;
+
+    // d(V v) / d(omega), with d(W W v) / d(omega) = (omega . v) I + omega v^T - 2 v omega^T
+    let dWWv = Matrix3::identity() * omega.dot(&v) + omega * v.transpose()
+        - v * omega.transpose() * convert::<f64, N>(2.0);
+    let dt_domega = Wv * omega.transpose() * k.b_theta - skew_symmetric_v(&v) * k.b
+        + WWv * omega.transpose() * k.c_theta
+        + dWWv * k.c;
+    let dt_dlambda = v * k.a_lambda + Wv * k.b_lambda + WWv * k.c_lambda;
+
+    let sRt = R.matrix().transpose() * (-lambda).exp();
+
+    let mut J = Matrix7::zeros();
+    J.fixed_slice_mut::<3, 3>(0, 0).copy_from(&Jw);
+    J.fixed_slice_mut::<3, 3>(3, 0)
+        .copy_from(&(sRt * dt_domega));
+    J.fixed_slice_mut::<3, 3>(3, 3).copy_from(&(sRt * V));
+    J.fixed_slice_mut::<3, 1>(3, 6)
+        .copy_from(&(sRt * dt_dlambda));
+    J[(6, 6)] = N::one();
+    J
+}
+
+/// Inverse of the right Jacobian of Sim(3), i.e. the derivative of the logmap:
+/// `log(exp(xi) * exp(d)) = xi + J * d` for `d --> 0`.
+///
+/// The right Jacobian is block triangular, so this only inverts Jw and G = e^-lambda R^T V.
+#[allow(non_snake_case)]
+pub fn sim3_right_jacobian_inverse<N: RealField + Copy>(xi: &Vector7<N>) -> Matrix7<N> {
+    use crate::geometry::so3::*;
+
+    let omega = xi.fixed_rows::<3>(0).into_owned();
+    let J = sim3_right_jacobian(xi);
+
+    let Jw_inv = logmap_derivative(&omega);
+    let G_inv = v_matrix_inverse(xi) * SO3::expmap(&omega).matrix() * xi[6].exp();
+    let F = J.fixed_slice::<3, 3>(3, 0);
+    let h = J.fixed_slice::<3, 1>(3, 6);
+
+    let mut res = Matrix7::zeros();
+    res.fixed_slice_mut::<3, 3>(0, 0).copy_from(&Jw_inv);
+    res.fixed_slice_mut::<3, 3>(3, 0)
+        .copy_from(&(-G_inv * F * Jw_inv));
+    res.fixed_slice_mut::<3, 3>(3, 3).copy_from(&G_inv);
+    res.fixed_slice_mut::<3, 1>(3, 6).copy_from(&(-G_inv * h));
+    res[(6, 6)] = N::one();
+    res
+}
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> LieGroup<N> for Sim3<N> {
+    type D = U7;
+
+    fn between(&self, g: &Self) -> Self {
+        self.inverse() * g
+    }
+
+    fn inverse(&self) -> Self {
+        // Resolves to the inherent nalgebra inverse
+        Sim3::inverse(self)
+    }
+
+    fn adjoint_map(&self) -> Matrix7<N> {
+        use crate::core::matrix::skew_symmetric_v;
+
+        let R = self.isometry.rotation.to_rotation_matrix();
+        let t = self.isometry.translation.vector;
+        let s = self.scaling();
+
+        // Twists are ordered [omega; v; lambda], so Ad = [R 0 0; [t]x R sR -t; 0 0 1]
+        let mut res = Matrix7::zeros();
+        res.fixed_slice_mut::<3, 3>(0, 0).copy_from(R.matrix());
+        res.fixed_slice_mut::<3, 3>(3, 0)
+            .copy_from(&(skew_symmetric_v(&t) * R.matrix()));
+        res.fixed_slice_mut::<3, 3>(3, 3)
+            .copy_from(&(R.matrix() * s));
+        res.fixed_slice_mut::<3, 1>(3, 6).copy_from(&-t);
+        res[(6, 6)] = N::one();
+
+        res
+    }
+
+    fn logmap(S: &Self, optionalH: Option<&mut Matrix7<N>>) -> Vector7<N> {
+        use crate::geometry::so3::*;
+
+        let w = SO3::logmap(&S.isometry.rotation.to_rotation_matrix(), None);
+        let lambda = S.scaling().ln();
+
+        let mut log = Vector7::zeros();
+        log.fixed_rows_mut::<3>(0).copy_from(&w);
+        log[6] = lambda;
+
+        let u = v_matrix_inverse(&log) * S.isometry.translation.vector;
+        log.fixed_rows_mut::<3>(3).copy_from(&u);
+
+        if let Some(H) = optionalH {
+            *H = sim3_right_jacobian_inverse(&log);
+        }
+
+        log
+    }
+
+    fn expmap(xi: &Vector7<N>) -> Self {
+        Self::expmap_with_derivative(xi, None)
+    }
+
+    fn expmap_with_derivative(xi: &Vector7<N>, optionalH: Option<&mut Matrix7<N>>) -> Self {
+        use crate::geometry::so3::*;
+
+        if let Some(H) = optionalH {
+            *H = sim3_right_jacobian(xi);
+        }
+
+        let R = SO3::expmap(&xi.fixed_rows::<3>(0).into_owned());
+        let t = v_matrix(xi) * xi.fixed_rows::<3>(3);
+
+        Sim3::from_parts(
+            Translation3::from(t),
+            UnitQuaternion::from_rotation_matrix(&R),
+            xi[6].exp(),
+        )
+    }
+}
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> Manifold for Sim3<N> {
+    type Field = N;
+    type TangentDim = U7;
+
+    fn local(origin: &Self, other: &Self) -> Vector7<N> {
+        Sim3::logmap(&origin.between(other), None)
+    }
+
+    fn retract(origin: &Self, v: &Vector7<N>) -> Self {
+        origin * Sim3::expmap(v)
+    }
+
+    fn local_with_derivative(
+        origin: &Self,
+        other: &Self,
+        H1: Option<&mut Matrix7<N>>,
+        H2: Option<&mut Matrix7<N>>,
+    ) -> Vector7<N> {
+        group_local_with_derivative(origin, other, H1, H2)
+    }
+
+    fn retract_with_derivative(
+        origin: &Self,
+        v: &Vector7<N>,
+        H1: Option<&mut Matrix7<N>>,
+        H2: Option<&mut Matrix7<N>>,
+    ) -> Self {
+        group_retract_with_derivative(origin, v, H1, H2)
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod test {
+    use super::*;
+    use crate::core::group::check_group_derivatives;
+    use crate::core::numerical_derivative::{numerical_derivative11, DEFAULT_DELTA};
+    use crate::geometry::se3::SE3;
+    use na::{Matrix4, Point3, Vector6};
+
+    fn xi(w: [f64; 3], v: [f64; 3], lambda: f64) -> Vector7<f64> {
+        Vector7::from_column_slice(&[w[0], w[1], w[2], v[0], v[1], v[2], lambda])
+    }
+
+    #[test]
+    fn expmap_logmap_invariant() {
+        let w = xi([1., 1.2, 1.3], [1., 1.4, 1.3], 0.4);
+
+        let exp = Sim3::expmap(&w);
+
+        assert_relative_eq!((w - Sim3::logmap(&exp, None)).norm(), 0.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn expmap_matches_se3_without_scale() {
+        let w = xi([0.1, -0.3, 0.2], [1., 2., 3.], 0.0);
+        let v = Vector6::new(0.1, -0.3, 0.2, 1., 2., 3.);
+
+        let p = Point3::new(0.5, -1.0, 2.0);
+        let expected = SE3::expmap(&v) * p;
+        let actual = Sim3::expmap(&w) * p;
+
+        assert_relative_eq!((expected - actual).norm(), 0.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn expmap_scales_points() {
+        let w = xi([0., 0., 0.], [0., 0., 0.], 2.0_f64.ln());
+        let p = Point3::new(0.5, -1.0, 2.0);
+
+        assert_relative_eq!(
+            (Sim3::expmap(&w) * p - p * 2.0).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn adjoint_map_works() {
+        // T exp(xi) T^-1 = exp(Ad(T) xi)
+        let T = Sim3::expmap(&xi([0.3, 0.1, -0.2], [1., -2., 0.5], 0.3));
+        let w = xi([-0.2, 0.4, 0.1], [0.3, 0.2, -1.0], -0.2);
+
+        let lhs = T * Sim3::expmap(&w) * T.inverse();
+        let rhs = Sim3::expmap(&(T.adjoint_map() * w));
+
+        assert_relative_eq!(
+            (lhs.to_homogeneous() - rhs.to_homogeneous()).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn expmap_logmap_derivatives() {
+        for w in [
+            xi([0.1, 0.27, -0.2], [1.0, -2.0, 0.5], 0.3),
+            xi([0.0, 0.0, 0.0], [1.0, -2.0, 0.5], 0.0),
+            xi([1e-6, 0.0, 0.0], [1.0, -2.0, 0.5], -1e-6),
+            xi([1e-6, 0.0, 0.0], [1.0, -2.0, 0.5], 2.5),
+            xi([0.02, 0.01, -0.01], [1.0, -2.0, 0.5], -3.0),
+            xi([0.8, -1.1, 0.4], [1.0, -2.0, 0.5], 1e-7),
+            xi([0.8, -1.1, 0.4], [1.0, -2.0, 0.5], -2.5),
+        ] {
+            let mut actual_dexp = Matrix7::zeros();
+            let S = Sim3::expmap_with_derivative(&w, Some(&mut actual_dexp));
+            let expected_dexp =
+                numerical_derivative11(|w: &Vector7<f64>| Sim3::expmap(w), &w, DEFAULT_DELTA);
+            assert_relative_eq!((actual_dexp - expected_dexp).norm(), 0.0, epsilon = 1e-6);
+
+            let mut actual_dlog = Matrix7::zeros();
+            Sim3::logmap(&S, Some(&mut actual_dlog));
+            let expected_dlog =
+                numerical_derivative11(|S: &Sim3<f64>| Sim3::logmap(S, None), &S, DEFAULT_DELTA);
+            assert_relative_eq!((actual_dlog - expected_dlog).norm(), 0.0, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn expmap_matches_matrix_exponential() {
+        for w in [
+            xi([0.1, 0.27, -0.2], [1.0, -2.0, 0.5], 0.3),
+            xi([0.0, 0.0, 0.0], [1.0, -2.0, 0.5], 0.0),
+            xi([1e-5, 0.0, 0.0], [1.0, -2.0, 0.5], 3.0),
+            xi([0.02, 0.01, -0.01], [1.0, -2.0, 0.5], -3.0),
+            xi([0.8, -1.1, 0.4], [1.0, -2.0, 0.5], 1e-7),
+            xi([2.0, -1.1, 0.4], [1.0, -2.0, 0.5], -1.0),
+        ] {
+            let mut hat = Matrix4::zeros();
+            hat.fixed_slice_mut::<3, 3>(0, 0)
+                .copy_from(&rotation_scale_hat(&w));
+            hat.fixed_slice_mut::<3, 1>(0, 3)
+                .copy_from(&w.fixed_rows::<3>(3));
+
+            assert_relative_eq!(
+                (Sim3::expmap(&w).to_homogeneous() - hat.exp()).norm(),
+                0.0,
+                epsilon = 1e-10
+            );
+            assert_relative_eq!(
+                (sim3_right_jacobian_inverse(&w) * sim3_right_jacobian(&w) - Matrix7::identity())
+                    .norm(),
+                0.0,
+                epsilon = 1e-10
+            );
+        }
+    }
+
+    #[test]
+    fn group_derivatives() {
+        let a = Sim3::expmap(&xi([0.1, 0.27, -0.2], [1.0, -2.0, 0.5], 0.3));
+        let b = Sim3::expmap(&xi([-1.0, 0.5, 0.3], [0.2, 0.1, 3.0], -0.5));
+        let v = xi([0.3, -0.4, 0.9], [0.5, 0.6, -0.7], 0.2);
+
+        check_group_derivatives(&a, &b, &v);
+    }
+
+    #[test]
+    fn between_works() {
+        let a = Sim3::expmap(&xi([0.1, 0.27, -0.2], [1.0, -2.0, 0.5], 0.3));
+        let b = Sim3::expmap(&xi([-1.0, 0.5, 0.3], [0.2, 0.1, 3.0], -0.5));
+
+        let p = Point3::new(0.5, -1.0, 2.0);
+        assert_relative_eq!((a * a.between(&b) * p - b * p).norm(), 0.0, epsilon = 1e-10);
+        assert_relative_eq!(a.between(&b).scaling(), (-0.8f64).exp(), epsilon = 1e-10);
+    }
+
+    #[test]
+    fn works_in_single_precision() {
+        let w = Vector7::<f32>::from_column_slice(&[0.1, -0.4, 0.3, 1.0, 2.0, -0.5, 0.2]);
+
+        let mut dexp = Matrix7::zeros();
+        let S = Sim3::expmap_with_derivative(&w, Some(&mut dexp));
+        let mut dlog = Matrix7::zeros();
+        let log = Sim3::logmap(&S, Some(&mut dlog));
+
+        assert_relative_eq!((w - log).norm(), 0.0, epsilon = 1e-4);
+        assert_relative_eq!(
+            (dlog * dexp - Matrix7::identity()).norm(),
+            0.0,
+            epsilon = 1e-4
+        );
+    }
+}
@@ -0,0 +1,131 @@
+pub use crate::core::group::LieGroup;
+pub use crate::core::manifold::Manifold;
+use crate::core::manifold::{group_local_with_derivative, group_retract_with_derivative};
+use nalgebra::{Matrix1, OMatrix, OVector, RealField, Vector1, U1};
+
+pub use nalgebra::Rotation2 as SO2;
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> LieGroup<N> for SO2<N> {
+    type D = U1;
+
+    fn between(&self, g: &Self) -> Self {
+        self.inverse() * g
+    }
+
+    fn inverse(&self) -> Self {
+        // Resolves to the inherent nalgebra inverse
+        SO2::inverse(self)
+    }
+
+    /// SO(2) is commutative, so the adjoint is the identity
+    fn adjoint_map(&self) -> OMatrix<N, U1, U1> {
+        Matrix1::identity()
+    }
+
+    fn logmap(R: &Self, optionalH: Option<&mut OMatrix<N, U1, U1>>) -> OVector<N, U1> {
+        if let Some(H) = optionalH {
+            *H = Matrix1::identity();
+        }
+
+        Vector1::new(R.angle())
+    }
+
+    fn expmap(omega: &OVector<N, U1>) -> Self {
+        Self::expmap_with_derivative(omega, None)
+    }
+
+    fn expmap_with_derivative(
+        omega: &OVector<N, U1>,
+        optionalH: Option<&mut OMatrix<N, U1, U1>>,
+    ) -> Self {
+        if let Some(H) = optionalH {
+            *H = Matrix1::identity();
+        }
+
+        SO2::new(omega[0])
+    }
+}
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> Manifold for SO2<N> {
+    type Field = N;
+    type TangentDim = U1;
+
+    fn local(origin: &Self, other: &Self) -> OVector<N, U1> {
+        SO2::logmap(&origin.between(other), None)
+    }
+
+    fn retract(origin: &Self, v: &OVector<N, U1>) -> Self {
+        origin * SO2::expmap(v)
+    }
+
+    fn local_with_derivative(
+        origin: &Self,
+        other: &Self,
+        H1: Option<&mut OMatrix<N, U1, U1>>,
+        H2: Option<&mut OMatrix<N, U1, U1>>,
+    ) -> OVector<N, U1> {
+        group_local_with_derivative(origin, other, H1, H2)
+    }
+
+    fn retract_with_derivative(
+        origin: &Self,
+        v: &OVector<N, U1>,
+        H1: Option<&mut OMatrix<N, U1, U1>>,
+        H2: Option<&mut OMatrix<N, U1, U1>>,
+    ) -> Self {
+        group_retract_with_derivative(origin, v, H1, H2)
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod test {
+    use super::*;
+    use crate::core::group::check_group_derivatives;
+    use crate::core::numerical_derivative::{numerical_derivative11, DEFAULT_DELTA};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn expmap_logmap_invariant() {
+        let w = Vector1::new(0.3);
+
+        let exp = SO2::expmap(&w);
+
+        assert_relative_eq!((w - SO2::logmap(&exp, None)).norm(), 0.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn logmap_wraps_angle() {
+        let exp = SO2::expmap(&Vector1::new(2.0 * PI + 0.1));
+
+        assert_relative_eq!(SO2::logmap(&exp, None)[0], 0.1, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn expmap_logmap_derivatives() {
+        let w = Vector1::new(0.7);
+
+        let mut actual_dexp = Matrix1::zeros();
+        let R = SO2::expmap_with_derivative(&w, Some(&mut actual_dexp));
+        let expected_dexp =
+            numerical_derivative11(|w: &Vector1<f64>| SO2::expmap(w), &w, DEFAULT_DELTA);
+        assert_relative_eq!((actual_dexp - expected_dexp).norm(), 0.0, epsilon = 1e-6);
+
+        let mut actual_dlog = Matrix1::zeros();
+        SO2::logmap(&R, Some(&mut actual_dlog));
+        let expected_dlog =
+            numerical_derivative11(|R: &SO2<f64>| SO2::logmap(R, None), &R, DEFAULT_DELTA);
+        assert_relative_eq!((actual_dlog - expected_dlog).norm(), 0.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn group_derivatives() {
+        let a = SO2::new(0.4);
+        let b = SO2::new(-1.3);
+        let v = Vector1::new(0.25);
+
+        check_group_derivatives(&a, &b, &v);
+    }
+}
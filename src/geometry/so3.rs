@@ -1,151 +1,339 @@
 pub use crate::core::group::LieGroup;
 pub use crate::core::manifold::Manifold;
-use nalgebra::{Matrix3, OMatrix, Vector3, U3};
+use crate::core::manifold::{group_local_with_derivative, group_retract_with_derivative};
+use crate::core::scalar::epsilon_pow;
+use nalgebra::{
+    convert, Matrix3, OMatrix, OVector, Quaternion, RealField, UnitQuaternion, Vector3, U3,
+};
 
 pub use nalgebra::Rotation3 as SO3;
-use std::f64::consts::PI;
 
 #[allow(non_snake_case)]
-impl LieGroup<f64> for SO3<f64> {
+impl<N: RealField + Copy> LieGroup<N> for SO3<N> {
     type D = U3;
 
     fn between(&self, g: &Self) -> Self {
         self.inverse() * g
     }
 
-    fn adjoint_map(&self) -> OMatrix<f64, U3, U3> {
+    fn inverse(&self) -> Self {
+        // Resolves to the inherent nalgebra inverse
+        SO3::inverse(self)
+    }
+
+    fn adjoint_map(&self) -> OMatrix<N, U3, U3> {
         *self.matrix()
     }
 
-    fn logmap(R: &Self, optionalH: Option<&mut Matrix3<f64>>) -> Vector3<f64> {
+    fn logmap(R: &Self, optionalH: Option<&mut OMatrix<N, U3, U3>>) -> OVector<N, U3> {
         let (R11, R12, R13) = (R[(0, 0)], R[(0, 1)], R[(0, 2)]);
         let (R21, R22, R23) = (R[(1, 0)], R[(1, 1)], R[(1, 2)]);
         let (R31, R32, R33) = (R[(2, 0)], R[(2, 1)], R[(2, 2)]);
 
         let tr = R.into_inner().trace();
 
-        let omega: Vector3<f64>;
+        let (one, two, half) = (N::one(), convert::<f64, N>(2.0), convert::<f64, N>(0.5));
+
+        let omega: Vector3<N>;
 
         // when trace == -1, i.e., when theta = +-pi, +-3pi, +-5pi, etc.
         // we do something special
-        if tr + 1.0 < 1e-3 {
+        if tr + one < epsilon_pow(0.2) {
             if (R33 > R22) && (R33 > R11) {
                 // R33 is the largest diagonal, a=3, b=1, c=2
                 let W = R21 - R12;
-                let Q1 = 2.0 + 2.0 * R33;
+                let Q1 = two + two * R33;
                 let Q2 = R31 + R13;
                 let Q3 = R23 + R32;
                 let r = Q1.sqrt();
-                let one_over_r = 1.0 / r;
+                let one_over_r = one / r;
                 let norm = (Q1 * Q1 + Q2 * Q2 + Q3 * Q3 + W * W).sqrt();
-                let sgn_w = if W < 0.0 { -1.0 } else { 1.0 };
-                let mag = PI - (2.0 * sgn_w * W) / norm;
-                let scale = 0.5 * one_over_r * mag;
-                omega = sgn_w * scale * Vector3::new(Q2, Q3, Q1);
+                let sgn_w = if W < N::zero() { -one } else { one };
+                let mag = N::pi() - (two * sgn_w * W) / norm;
+                let scale = half * one_over_r * mag;
+                omega = Vector3::new(Q2, Q3, Q1) * (sgn_w * scale);
             } else if R22 > R11 {
                 // R22 is the largest diagonal, a=2, b=3, c=1
                 let W = R13 - R31;
-                let Q1 = 2.0 + 2.0 * R22;
+                let Q1 = two + two * R22;
                 let Q2 = R23 + R32;
                 let Q3 = R12 + R21;
                 let r = Q1.sqrt();
-                let one_over_r = 1.0 / r;
+                let one_over_r = one / r;
                 let norm = (Q1 * Q1 + Q2 * Q2 + Q3 * Q3 + W * W).sqrt();
-                let sgn_w = if W < 0.0 { -1.0 } else { 1.0 };
-                let mag = PI - (2.0 * sgn_w * W) / norm;
-                let scale = 0.5 * one_over_r * mag;
-                omega = sgn_w * scale * Vector3::new(Q3, Q1, Q2);
+                let sgn_w = if W < N::zero() { -one } else { one };
+                let mag = N::pi() - (two * sgn_w * W) / norm;
+                let scale = half * one_over_r * mag;
+                omega = Vector3::new(Q3, Q1, Q2) * (sgn_w * scale);
             } else {
                 // R11 is the largest diagonal, a=1, b=2, c=3
                 let W = R32 - R23;
-                let Q1 = 2.0 + 2.0 * R11;
+                let Q1 = two + two * R11;
                 let Q2 = R12 + R21;
                 let Q3 = R31 + R13;
                 let r = Q1.sqrt();
-                let one_over_r = 1.0 / r;
+                let one_over_r = one / r;
                 let norm = (Q1 * Q1 + Q2 * Q2 + Q3 * Q3 + W * W).sqrt();
-                let sgn_w = if W < 0.0 { -1.0 } else { 1.0 };
-                let mag = PI - (2.0 * sgn_w * W) / norm;
-                let scale = 0.5 * one_over_r * mag;
-                omega = sgn_w * scale * Vector3::new(Q1, Q2, Q3);
+                let sgn_w = if W < N::zero() { -one } else { one };
+                let mag = N::pi() - (two * sgn_w * W) / norm;
+                let scale = half * one_over_r * mag;
+                omega = Vector3::new(Q1, Q2, Q3) * (sgn_w * scale);
             }
         } else {
-            let magnitude: f64;
-            let tr_3 = tr - 3.0; // could be non-negative if the matrix is off orthogonal
-            if tr_3 < -1e-6 {
+            let tr_3 = tr - convert(3.0); // could be non-negative if the matrix is off orthogonal
+            let magnitude = if tr_3 < -epsilon_pow::<N>(1.0 / 3.0) {
                 // this is the normal case -1 < trace < 3
-                let theta = ((tr - 1.0) / 2.0).acos();
-                magnitude = theta / (2.0 * theta.sin());
+                let theta = ((tr - one) / two).acos();
+                theta / (two * theta.sin())
             } else {
                 // when theta near 0, +-2pi, +-4pi, etc. (trace near 3.0)
                 // use Taylor expansion: theta \approx 1/2-(t-3)/12 + O((t-3)^2)
                 // see https://github.com/borglab/gtsam/issues/746 for details
-                magnitude = 0.5 - tr_3 / 12.0 + tr_3 * tr_3 / 60.0;
-            }
-            omega = magnitude * Vector3::new(R32 - R23, R13 - R31, R21 - R12);
+                half - tr_3 / convert(12.0) + tr_3 * tr_3 / convert(60.0)
+            };
+            omega = Vector3::new(R32 - R23, R13 - R31, R21 - R12) * magnitude;
         }
 
-        if let Some(_H) = optionalH {
-            unimplemented!("optionalH NOT IMPLEMENTED");
-            // *H = LogmapDerivative(omega);
+        if let Some(H) = optionalH {
+            *H = logmap_derivative(&omega);
         }
 
         omega
     }
 
-    fn expmap(omega: &Vector3<f64>) -> Self {
+    fn expmap(omega: &OVector<N, U3>) -> Self {
         Self::expmap_with_derivative(omega, None)
     }
 
     #[inline]
-    fn expmap_with_derivative(omega: &Vector3<f64>, optionalH: Option<&mut Matrix3<f64>>) -> Self {
+    fn expmap_with_derivative(
+        omega: &OVector<N, U3>,
+        optionalH: Option<&mut OMatrix<N, U3, U3>>,
+    ) -> Self {
+        use crate::core::matrix::skew_symmetric_v;
+
         let theta2 = omega.dot(omega);
-        let nearZero = theta2 <= 1e-5;
-        let (wx, wy, wz) = (omega.x, omega.y, omega.z);
-        let W = Matrix3::new(0.0, -wz, wy, wz, 0.0, -wx, -wy, wx, 0.0);
+        // The series below drop O(theta^4) terms, which are below epsilon under this cutover
+        let nearZero = theta2 <= epsilon_pow(0.5);
+        let W = skew_symmetric_v(omega);
+        let (one, two, half) = (N::one(), convert::<f64, N>(2.0), convert::<f64, N>(0.5));
+
+        if let Some(H) = optionalH {
+            // The right Jacobian is the left Jacobian at -omega
+            *H = so3_left_jacobian(&-omega);
+        }
 
         if !nearZero {
             let theta = theta2.sqrt();
-            let sin_theta = theta.sin();
-            let s2 = (theta / 2.).sin();
-            let one_minus_cos = 2.0 * s2 * s2;
+            let s2 = (theta * half).sin();
+            let one_minus_cos = two * s2 * s2;
             let K = W / theta;
-            let KK = K * K;
-
-            if let Some(H) = optionalH {
-                let a = one_minus_cos / theta;
-                let b = 1.0 - sin_theta / theta;
-                let dexp_ = Matrix3::identity() - a * K + b * KK;
 
-                *H = dexp_;
-            }
-
-            SO3::from_matrix(&(Matrix3::identity() + sin_theta * K + one_minus_cos * KK))
+            // The closed form is orthogonal by construction
+            SO3::from_matrix_unchecked(
+                Matrix3::identity() + K * theta.sin() + K * K * one_minus_cos,
+            )
         } else {
-            if let Some(H) = optionalH {
-                *H = Matrix3::identity() - 0.5 * W;
-            }
+            // Go through the unit quaternion [cos(theta/2), sin(theta/2)/theta * omega] instead
+            // of re-orthogonalizing I + W + W^2/2, which loses the derivatives of autodiff scalars
+            let c = one - theta2 / convert(8.0) + theta2 * theta2 / convert(384.0);
+            let s = half - theta2 / convert(48.0);
+            let q = Quaternion::new(c, omega.x * s, omega.y * s, omega.z * s);
 
-            SO3::from_matrix(&(Matrix3::identity() + W))
+            UnitQuaternion::new_normalize(q).to_rotation_matrix()
         }
     }
 }
 
-impl Manifold for SO3<f64> {
-    type TangentVector = Vector3<f64>;
+/// Derivative of the logmap, i.e. the inverse of the right Jacobian of SO(3):
+/// for `omega = log(R)`, `log(R * exp(d)) = omega + H * d` for `d --> 0`.
+#[allow(non_snake_case)]
+pub fn logmap_derivative<N: RealField + Copy>(omega: &Vector3<N>) -> Matrix3<N> {
+    use crate::core::matrix::skew_symmetric_v;
+
+    let theta2 = omega.dot(omega);
+    let W = skew_symmetric_v(omega);
+    let half = convert::<f64, N>(0.5);
+
+    // The coefficient is 1/theta^2 - (1 + cos(theta)) / (2 * theta * sin(theta)),
+    // which we write with cot(theta/2) so that it stays finite as theta --> pi
+    let c = if theta2 <= epsilon_pow(1.0 / 3.0) {
+        // Taylor expansion: 1/12 + theta^2/720 + theta^4/30240 + O(theta^6)
+        convert::<f64, N>(1.0 / 12.0) + theta2 / convert(720.0) + theta2 * theta2 / convert(30240.0)
+    } else {
+        let theta = theta2.sqrt();
+        N::one() / theta2 - half / (theta * (half * theta).tan())
+    };
+
+    Matrix3::identity() + W * half + W * W * c
+}
+
+/// Coefficients `((1 - cos(theta)) / theta^2, (theta - sin(theta)) / theta^3)` of the
+/// Jacobians of the expmap, `J_r = I - a W + b W^2` and `J_l = I + a W + b W^2`
+fn expmap_derivative_coefficients<N: RealField + Copy>(theta2: N) -> (N, N) {
+    let half = convert::<f64, N>(0.5);
+
+    // The series drop O(theta^4) terms, which are below epsilon under this cutover
+    if theta2 <= epsilon_pow(0.5) {
+        (
+            half - theta2 / convert(24.0),
+            convert::<f64, N>(1.0 / 6.0) - theta2 / convert(120.0),
+        )
+    } else {
+        let theta = theta2.sqrt();
+        let s2 = (half * theta).sin();
+        (
+            (s2 * s2 + s2 * s2) / theta2,
+            (theta - theta.sin()) / (theta2 * theta),
+        )
+    }
+}
+
+/// Left Jacobian of SO(3): `exp(omega + d) = exp(J * d) * exp(omega)` for `d --> 0`.
+///
+/// This is also the V matrix that maps the translational velocity of an SE(3) twist
+/// to the translation of its expmap.
+#[allow(non_snake_case)]
+pub fn so3_left_jacobian<N: RealField + Copy>(omega: &Vector3<N>) -> Matrix3<N> {
+    use crate::core::matrix::skew_symmetric_v;
+
+    let W = skew_symmetric_v(omega);
+    let (a, b) = expmap_derivative_coefficients(omega.dot(omega));
+
+    Matrix3::identity() + W * a + W * W * b
+}
+
+#[allow(non_snake_case)]
+impl<N: RealField + Copy> Manifold for SO3<N> {
+    type Field = N;
+    type TangentDim = U3;
 
-    fn local(origin: &Self, other: &Self) -> Self::TangentVector {
-        SO3::logmap(&origin.between(&other), None)
+    fn local(origin: &Self, other: &Self) -> OVector<N, U3> {
+        SO3::logmap(&origin.between(other), None)
     }
 
-    fn retract(origin: &Self, v: &Self::TangentVector) -> Self {
-        origin * SO3::expmap(&v)
+    fn retract(origin: &Self, v: &OVector<N, U3>) -> Self {
+        origin * SO3::expmap(v)
+    }
+
+    fn local_with_derivative(
+        origin: &Self,
+        other: &Self,
+        H1: Option<&mut OMatrix<N, U3, U3>>,
+        H2: Option<&mut OMatrix<N, U3, U3>>,
+    ) -> OVector<N, U3> {
+        group_local_with_derivative(origin, other, H1, H2)
+    }
+
+    fn retract_with_derivative(
+        origin: &Self,
+        v: &OVector<N, U3>,
+        H1: Option<&mut OMatrix<N, U3, U3>>,
+        H2: Option<&mut OMatrix<N, U3, U3>>,
+    ) -> Self {
+        group_retract_with_derivative(origin, v, H1, H2)
     }
 }
 
 #[cfg(test)]
+#[allow(non_snake_case)]
 mod test {
     use super::*;
+    use crate::core::group::check_group_derivatives;
+    use crate::core::numerical_derivative::{numerical_derivative11, DEFAULT_DELTA};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn logmap_derivative_works_1() {
+        let R = SO3::expmap(&Vector3::new(0.1, 0.27, -0.2));
+        let mut actual = Matrix3::zeros();
+
+        SO3::logmap(&R, Some(&mut actual));
+
+        let expected =
+            numerical_derivative11(|R: &SO3<f64>| SO3::logmap(R, None), &R, DEFAULT_DELTA);
+        assert_relative_eq!((actual - expected).norm(), 0.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn logmap_derivative_works_2() {
+        let R = SO3::expmap(&Vector3::new(1.0, 2.0, -1.5));
+        let mut actual = Matrix3::zeros();
+
+        SO3::logmap(&R, Some(&mut actual));
+
+        let expected =
+            numerical_derivative11(|R: &SO3<f64>| SO3::logmap(R, None), &R, DEFAULT_DELTA);
+        assert_relative_eq!((actual - expected).norm(), 0.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn logmap_derivative_near_identity() {
+        let R = SO3::expmap(&Vector3::new(1e-4, -2e-4, 3e-4));
+        let mut actual = Matrix3::zeros();
+
+        SO3::logmap(&R, Some(&mut actual));
+
+        let expected =
+            numerical_derivative11(|R: &SO3<f64>| SO3::logmap(R, None), &R, DEFAULT_DELTA);
+        assert_relative_eq!((actual - expected).norm(), 0.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn logmap_derivative_near_pi() {
+        let w = Vector3::new(1.0, 2.0, 3.0).normalize() * (PI - 5e-2);
+        let R = SO3::expmap(&w);
+        let mut actual = Matrix3::zeros();
+
+        SO3::logmap(&R, Some(&mut actual));
+
+        let expected =
+            numerical_derivative11(|R: &SO3<f64>| SO3::logmap(R, None), &R, DEFAULT_DELTA);
+        assert_relative_eq!((actual - expected).norm(), 0.0, epsilon = 1e-5);
+
+        // At exactly pi the derivative must stay finite and still invert dexp
+        let w = Vector3::new(1.0, 2.0, 3.0).normalize() * PI;
+        let mut dexp = Matrix3::zeros();
+        SO3::expmap_with_derivative(&w, Some(&mut dexp));
+        let dlog = logmap_derivative(&w);
+
+        assert_relative_eq!(
+            (dlog * dexp - Matrix3::identity()).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn logmap_derivative_inverts_expmap_derivative() {
+        let w = Vector3::new(0.4, -1.1, 0.7);
+        let mut dexp = Matrix3::zeros();
+        let mut dlog = Matrix3::zeros();
+
+        let R = SO3::expmap_with_derivative(&w, Some(&mut dexp));
+        SO3::logmap(&R, Some(&mut dlog));
+
+        assert_relative_eq!(
+            (dlog * dexp - Matrix3::identity()).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn group_derivatives() {
+        let a = SO3::expmap(&Vector3::new(0.1, 0.27, -0.2));
+        let b = SO3::expmap(&Vector3::new(-1.0, 0.5, 0.3));
+        let v = Vector3::new(0.3, -0.4, 0.9);
+
+        check_group_derivatives(&a, &b, &v);
+        let inv = LieGroup::inverse(&a);
+        assert_relative_eq!(
+            (inv.matrix() - a.matrix().transpose()).norm(),
+            0.0,
+            epsilon = 1e-10
+        );
+    }
 
     #[test]
     fn test_manifold_local() {
@@ -168,4 +356,48 @@ mod test {
             epsilon = 1e-10
         );
     }
+
+    #[test]
+    fn works_in_single_precision() {
+        for w in &[
+            Vector3::new(0.1f32, -0.4, 0.3),
+            Vector3::new(1e-3, 2e-3, -1e-3),
+            Vector3::new(0.0, 0.0, 3.1),
+        ] {
+            let mut dexp = Matrix3::zeros();
+            let R = SO3::expmap_with_derivative(w, Some(&mut dexp));
+            let mut dlog = Matrix3::zeros();
+            let log = SO3::logmap(&R, Some(&mut dlog));
+
+            assert_relative_eq!((w - log).norm(), 0.0, epsilon = 1e-4);
+            assert_relative_eq!(
+                (dlog * dexp - Matrix3::identity()).norm(),
+                0.0,
+                epsilon = 1e-4
+            );
+        }
+    }
+    #[test]
+    fn matches_double_precision_near_cutover() {
+        // The series switch over at theta = 0.0186 for f32
+        for theta in [0.07f32, 0.019, 0.018, 1e-3] {
+            let w = Vector3::new(1.0f32, -2.0, 2.0) * (theta / 3.0);
+            let mut dexp = Matrix3::zeros();
+            let R = SO3::expmap_with_derivative(&w, Some(&mut dexp));
+
+            let w = w.cast::<f64>();
+            let mut expected_dexp = Matrix3::zeros();
+            let expected_R = SO3::expmap_with_derivative(&w, Some(&mut expected_dexp));
+            assert_relative_eq!(
+                (R.matrix().cast::<f64>() - expected_R.matrix()).norm(),
+                0.0,
+                epsilon = 1e-6
+            );
+            assert_relative_eq!(
+                (dexp.cast::<f64>() - expected_dexp).norm(),
+                0.0,
+                epsilon = 1e-6
+            );
+        }
+    }
 }
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+/// A Bayes net is a collection of conditionals in elimination order, i.e. the parents of every
+/// conditional are frontals of conditionals after it.
+#[derive(Debug)]
+pub struct BayesNet<ConditionalType: ?Sized> {
+    pub conditionals: Vec<Arc<ConditionalType>>,
+}
+
+impl<ConditionalType: ?Sized> BayesNet<ConditionalType> {
+    pub fn new() -> Self {
+        BayesNet {
+            conditionals: vec![],
+        }
+    }
+
+    pub fn push(&mut self, conditional: ConditionalType)
+    where
+        ConditionalType: Sized,
+    {
+        self.conditionals.push(Arc::new(conditional));
+    }
+
+    pub fn push_shared(&mut self, conditional: Arc<ConditionalType>) {
+        self.conditionals.push(conditional);
+    }
+
+    /// Appends the conditionals of `other`, which were eliminated after those of `self`
+    pub fn append(&mut self, other: &mut Self) {
+        self.conditionals.append(&mut other.conditionals);
+    }
+
+    pub fn len(&self) -> usize {
+        self.conditionals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditionals.is_empty()
+    }
+
+    /// The conditionals in elimination order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &ConditionalType> + '_ {
+        self.conditionals.iter().map(|c| c.as_ref())
+    }
+}
+
+impl<ConditionalType: ?Sized> Default for BayesNet<ConditionalType> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
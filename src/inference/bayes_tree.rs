@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+/// A clique of a Bayes tree holds the conditional of its frontals given its separator, whose
+/// keys are all frontals of its ancestors
+#[derive(Debug)]
+pub struct BayesTreeClique<ConditionalType> {
+    pub conditional: Arc<ConditionalType>,
+    pub children: Vec<BayesTreeClique<ConditionalType>>,
+}
+
+/// A Bayes tree is a tree of cliques, the result of multifrontal elimination
+#[derive(Debug)]
+pub struct BayesTree<ConditionalType> {
+    pub roots: Vec<BayesTreeClique<ConditionalType>>,
+}
+
+impl<ConditionalType> BayesTreeClique<ConditionalType> {
+    /// Number of cliques in the subtree of this clique
+    pub fn size(&self) -> usize {
+        let mut size = 0;
+        let mut stack = vec![self];
+        while let Some(clique) = stack.pop() {
+            size += 1;
+            stack.extend(clique.children.iter());
+        }
+        size
+    }
+}
+
+impl<ConditionalType> Drop for BayesTreeClique<ConditionalType> {
+    /// Drops the subtree with an explicit stack, as Bayes trees can be as deep as they have
+    /// cliques
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut clique) = stack.pop() {
+            stack.append(&mut clique.children);
+        }
+    }
+}
+
+impl<ConditionalType> BayesTree<ConditionalType> {
+    pub fn new() -> Self {
+        BayesTree { roots: vec![] }
+    }
+
+    /// Number of cliques
+    pub fn size(&self) -> usize {
+        self.roots.iter().map(|c| c.size()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+}
+
+impl<ConditionalType> Default for BayesTree<ConditionalType> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -4,9 +4,9 @@ use std::sync::Arc;
 /// A cluster is just a collection of factors
 #[derive(Debug)]
 pub struct Cluster<Graph: FactorGraph> {
-    frontals: Vec<u64>,
-    children: Vec<Box<Self>>,
-    factors: Graph,
+    pub frontals: Vec<u64>,
+    pub children: Vec<Box<Self>>,
+    pub factors: Graph,
 }
 
 /// A Cluster Tree is associated with a Factor graph and is defined as in Koller-Friedman:
@@ -14,14 +14,14 @@ pub struct Cluster<Graph: FactorGraph> {
 /// $`f_i`$ is associated with a single cluster and $`\text{scope}(f_i) \sub C_k`$.
 #[derive(Debug)]
 pub struct ClusterTree<Graph: FactorGraph> {
-    roots: Vec<Box<Cluster<Graph>>>,
+    pub roots: Vec<Box<Cluster<Graph>>>,
 }
 
 impl<Graph> Cluster<Graph>
 where
     Graph: FactorGraph,
 {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Cluster {
             frontals: vec![],
             children: vec![],
@@ -29,7 +29,7 @@ where
         }
     }
 
-    fn from_single_key(key: u64, factors: &Vec<Arc<Graph::FactorType>>) -> Self {
+    pub fn from_single_key(key: u64, factors: &[Arc<Graph::FactorType>]) -> Self {
         let mut fg = Graph::new();
         for f in factors {
             fg.insert_shared(f.clone());
@@ -41,7 +41,7 @@ where
         }
     }
 
-    fn merge(&mut self, other: &mut Self) {
+    pub fn merge(&mut self, other: &mut Self) {
         self.frontals.append(&mut other.frontals);
         self.factors.merge(&mut other.factors);
         self.children.append(&mut other.children);
@@ -50,52 +50,86 @@ where
     /// Add a child cluster
     /// Apparently this operation taints the Cluster
     /// as the cluster frontals will be wrong
-    fn add_child(&mut self, child: Box<Self>) {
+    pub fn add_child(&mut self, child: Box<Self>) {
         self.children.push(child);
     }
 
     /// Do the merge
     /// This fixes a tainted cluster
-    fn merge_children(&mut self, merge: Vec<bool>) {
-        let mut old_children = std::mem::replace(&mut self.children, Vec::new());
-
-        let mut j = 0;
-        for mut child in old_children {
-            if merge[j] {
-                self.merge(&mut *child);
+    ///
+    /// Afterwards the frontals of merged children come first, those of each child in their own
+    /// order, and the frontals this cluster had before come last, reversed
+    pub fn merge_children(&mut self, merge: Vec<bool>) {
+        let old_children = std::mem::take(&mut self.children);
+
+        for (mut child, merge) in old_children.into_iter().zip(merge) {
+            if merge {
+                // Reversed, so that the final reversal restores their order
+                child.frontals.reverse();
+                self.merge(&mut child);
             } else {
                 self.add_child(child);
             }
-            j += 1;
         }
 
         self.frontals.reverse();
     }
 }
 
+impl<Graph> Drop for Cluster<Graph>
+where
+    Graph: FactorGraph,
+{
+    /// Drops the subtree with an explicit stack, as cluster trees can be as deep as they
+    /// have clusters
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut cluster) = stack.pop() {
+            stack.append(&mut cluster.children);
+        }
+    }
+}
+
+impl<Graph> Default for Cluster<Graph>
+where
+    Graph: FactorGraph,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<Graph> ClusterTree<Graph>
 where
     Graph: FactorGraph,
 {
-    fn new() -> Self {
+    pub fn new() -> Self {
         ClusterTree { roots: Vec::new() }
     }
 }
 
+impl<Graph> Default for ClusterTree<Graph>
+where
+    Graph: FactorGraph,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::inference::cluster_tree::{Cluster, ClusterTree};
     use crate::inference::factor_graph::*;
-    use std::default::Default;
 
     use crate::inference::factor_graph::tests::TestFactor;
 
     #[test]
     fn test_cluster_tree() {
-        let mut cluster = Cluster::<SimpleFactorGraph<TestFactor>>::new();
+        let cluster = Cluster::<SimpleFactorGraph<TestFactor>>::new();
         println!("{:?}", cluster);
 
-        let mut cluster_tree = ClusterTree::<SimpleFactorGraph<TestFactor>>::new();
+        let cluster_tree = ClusterTree::<SimpleFactorGraph<TestFactor>>::new();
         println!("{:?}", cluster_tree);
 
         let mut factors = SimpleFactorGraph::new();
@@ -103,7 +137,7 @@ mod tests {
             inner: "cluster0".into(),
             _keys: [0].into(),
         });
-        let mut cluster_1 =
+        let cluster_1 =
             Cluster::<SimpleFactorGraph<TestFactor>>::from_single_key(0, &factors.factors);
         println!("{:#?}", cluster_1)
     }
@@ -116,7 +150,7 @@ mod tests {
             _keys: [0].into(),
         });
         let mut cluster_0 =
-            Cluster::<SimpleFactorGraph<TestFactor>>::from_single_key(0, &mut factors_0.factors);
+            Cluster::<SimpleFactorGraph<TestFactor>>::from_single_key(0, &factors_0.factors);
 
         let mut factors_1 = SimpleFactorGraph::new();
         factors_1.insert(TestFactor {
@@ -124,7 +158,7 @@ mod tests {
             _keys: [1].into(),
         });
         let mut cluster_1 =
-            Cluster::<SimpleFactorGraph<TestFactor>>::from_single_key(1, &mut factors_1.factors);
+            Cluster::<SimpleFactorGraph<TestFactor>>::from_single_key(1, &factors_1.factors);
 
         cluster_0.merge(&mut cluster_1);
 
@@ -136,8 +170,8 @@ mod tests {
             _keys: [2].into(),
         });
 
-        let mut cluster_2 =
-            Cluster::<SimpleFactorGraph<TestFactor>>::from_single_key(2, &mut factors_2.factors);
+        let cluster_2 =
+            Cluster::<SimpleFactorGraph<TestFactor>>::from_single_key(2, &factors_2.factors);
 
         cluster_0.add_child(Box::new(cluster_2));
 
@@ -149,8 +183,8 @@ mod tests {
             _keys: [3].into(),
         });
 
-        let mut cluster_3 =
-            Cluster::<SimpleFactorGraph<TestFactor>>::from_single_key(3, &mut factors_3.factors);
+        let cluster_3 =
+            Cluster::<SimpleFactorGraph<TestFactor>>::from_single_key(3, &factors_3.factors);
 
         cluster_0.add_child(Box::new(cluster_3));
 
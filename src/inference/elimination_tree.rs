@@ -0,0 +1,150 @@
+use crate::inference::factor::KeyType;
+use crate::inference::variable_index::VariableIndex;
+use crate::inference::FactorGraph;
+use std::sync::Arc;
+
+/// A node eliminates a single key, using its own factors and the factors left by eliminating
+/// its children
+#[derive(Debug)]
+pub struct EliminationTreeNode<Graph: FactorGraph> {
+    pub key: KeyType,
+    pub factors: Vec<Arc<Graph::FactorType>>,
+    pub children: Vec<Box<Self>>,
+}
+
+impl<Graph: FactorGraph> Drop for EliminationTreeNode<Graph> {
+    /// Drops the subtree with an explicit stack, as elimination trees can be as deep as they
+    /// have nodes
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
+}
+
+/// The elimination tree of a factor graph for an ordering: the parent of every node is the
+/// first key eliminated after it that its elimination creates a dependency on.
+///
+/// Every factor is attached to the node of the first of its keys in the ordering. Factors on
+/// none of the keys of the ordering are kept aside, so orderings may cover only some keys.
+#[derive(Debug)]
+pub struct EliminationTree<Graph: FactorGraph> {
+    pub roots: Vec<Box<EliminationTreeNode<Graph>>>,
+    pub remaining_factors: Vec<Arc<Graph::FactorType>>,
+}
+
+impl<Graph: FactorGraph> EliminationTree<Graph> {
+    pub fn new(graph: &Graph, index: &VariableIndex, ordering: &[KeyType]) -> Self {
+        let n = ordering.len();
+        let factors = graph.shared_factors();
+
+        let mut parents: Vec<Option<usize>> = vec![None; n];
+        let mut node_factors: Vec<Vec<Arc<Graph::FactorType>>> = vec![Vec::new(); n];
+        // The last position in the ordering at which each factor was seen
+        let mut previous: Vec<Option<usize>> = vec![None; factors.len()];
+
+        for (j, key) in ordering.iter().enumerate() {
+            for &i in index.factors(*key) {
+                match previous[i] {
+                    None => node_factors[j].push(factors[i].clone()),
+                    Some(first) => {
+                        let mut root = first;
+                        while let Some(parent) = parents[root] {
+                            root = parent;
+                        }
+                        if root != j {
+                            parents[root] = Some(j);
+                        }
+                    }
+                }
+                previous[i] = Some(j);
+            }
+        }
+
+        let remaining_factors = factors
+            .iter()
+            .zip(previous.iter())
+            .filter(|(_, p)| p.is_none())
+            .map(|(f, _)| f.clone())
+            .collect();
+
+        // Children always come before their parents in the ordering, so every node is complete
+        // by the time it is attached to its parent
+        let mut nodes: Vec<Option<Box<EliminationTreeNode<Graph>>>> = ordering
+            .iter()
+            .zip(node_factors)
+            .map(|(key, factors)| {
+                Some(Box::new(EliminationTreeNode {
+                    key: *key,
+                    factors,
+                    children: Vec::new(),
+                }))
+            })
+            .collect();
+
+        let mut roots = Vec::new();
+        for j in 0..n {
+            let node = nodes[j].take().unwrap();
+            match parents[j] {
+                Some(p) => nodes[p].as_mut().unwrap().children.push(node),
+                None => roots.push(node),
+            }
+        }
+
+        EliminationTree {
+            roots,
+            remaining_factors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::factor_graph::tests::{symbolic_chain, TestFactor};
+    use crate::inference::factor_graph::SimpleFactorGraph;
+
+    #[test]
+    fn elimination_tree_of_chain() {
+        let graph = symbolic_chain();
+        let index = VariableIndex::new(&graph);
+
+        let tree = EliminationTree::new(&graph, &index, &[0, 1, 2, 3]);
+        assert_eq!(tree.roots.len(), 1);
+        assert!(tree.remaining_factors.is_empty());
+
+        // 3 <- 2 <- 1 <- 0, with each factor at its first key
+        let mut node = &tree.roots[0];
+        let mut keys = vec![];
+        let mut n_factors = 0;
+        loop {
+            keys.push(node.key);
+            n_factors += node.factors.len();
+            match node.children.first() {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+        assert_eq!(keys, vec![3, 2, 1, 0]);
+        assert_eq!(n_factors, 5);
+    }
+
+    #[test]
+    fn partial_elimination_tree() {
+        let mut graph = SimpleFactorGraph::new();
+        for keys in [vec![0, 1], vec![0, 2], vec![3]] {
+            graph.insert(TestFactor {
+                inner: format!("{:?}", keys),
+                _keys: keys,
+            });
+        }
+        let index = VariableIndex::new(&graph);
+
+        // Eliminating 1 and 2 only leaves two separate trees, both depending on 0
+        let tree = EliminationTree::new(&graph, &index, &[1, 2]);
+        assert_eq!(tree.roots.len(), 2);
+        assert_eq!(tree.remaining_factors.len(), 1);
+        assert_eq!(tree.remaining_factors[0]._keys, vec![3]);
+    }
+}
@@ -11,7 +11,7 @@ impl Factor for ExpressionFactor {
         unimplemented!()
     }
 
-    fn key_at(&self, index: usize) -> Result<KeyType, std::io::Error> {
+    fn key_at(&self, _index: usize) -> Result<KeyType, std::io::Error> {
         unimplemented!()
     }
 }
@@ -2,22 +2,83 @@ extern crate alloc;
 
 pub type KeyType = u64;
 
-pub struct KeyIterator<'a> {
-    owner: &'a dyn Factor,
+/// Iterates over the keys of a factor in order
+pub struct KeyIterator<'a, F: Factor + ?Sized> {
+    owner: &'a F,
     index: usize,
 }
 
-impl<'a> Iterator for KeyIterator<'a> {
+impl<'a, F: Factor + ?Sized> Iterator for KeyIterator<'a, F> {
     type Item = KeyType;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.owner.key_at(self.index - 1).ok()
+        if self.index >= self.owner.num_keys() {
+            return None;
+        }
+
+        let key = self.owner.key_at(self.index).ok();
+        self.index += 1;
+        key
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.owner.num_keys().saturating_sub(self.index);
+        (remaining, Some(remaining))
     }
 }
 
+impl<'a, F: Factor + ?Sized> ExactSizeIterator for KeyIterator<'a, F> {}
+
 pub trait Factor: std::fmt::Debug {
     fn num_keys(&self) -> usize;
     fn key_at(&self, index: usize) -> Result<KeyType, std::io::Error>;
+
+    /// Dimension of the variable at position `index`, if the factor knows it
+    fn dim_at(&self, _index: usize) -> Option<usize> {
+        None
+    }
+}
+
+/// Key access for every factor, including trait objects such as `dyn GaussianLikeFactor`
+pub trait FactorKeys: Factor {
+    fn key_iter(&self) -> KeyIterator<'_, Self>;
+
+    fn involves(&self, key: KeyType) -> bool {
+        self.key_iter().any(|k| k == key)
+    }
+}
+
+impl<F: Factor + ?Sized> FactorKeys for F {
+    fn key_iter(&self) -> KeyIterator<'_, Self> {
+        KeyIterator {
+            owner: self,
+            index: 0,
+        }
+    }
 }
 
 pub trait NonlinearFactor: Factor {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::factor_graph::tests::TestFactor;
+
+    #[test]
+    fn key_iterator_visits_every_key() {
+        let factor = TestFactor {
+            inner: "".into(),
+            _keys: vec![4, 2, 7],
+        };
+
+        let keys = factor.key_iter();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys.collect::<Vec<_>>(), vec![4, 2, 7]);
+        assert!(factor.involves(2));
+        assert!(!factor.involves(3));
+
+        // Through a trait object, and without keys
+        let empty: &dyn Factor = &TestFactor::default();
+        assert_eq!(empty.key_iter().next(), None);
+    }
+}
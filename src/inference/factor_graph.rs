@@ -1,6 +1,11 @@
 extern crate alloc;
 
-use crate::inference::Factor;
+use crate::inference::elimination_tree::EliminationTree;
+use crate::inference::factor::KeyType;
+use crate::inference::junction_tree::JunctionTree;
+use crate::inference::variable_index::VariableIndex;
+use crate::inference::{BayesNet, BayesTree, Factor};
+use alloc::collections::BTreeSet;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 
@@ -16,7 +21,7 @@ pub trait FactorGraph
 where
     Self: Sized,
 {
-    type FactorType: ?Sized;
+    type FactorType: ?Sized + Factor;
 
     fn new() -> Self;
 
@@ -33,6 +38,9 @@ where
         Self::FactorType: Sized;
 
     fn size(&self) -> usize;
+
+    /// The factors of the graph, which is also how graphs of unsized factors are iterated
+    fn shared_factors(&self) -> &[Arc<Self::FactorType>];
 }
 
 #[derive(Debug)]
@@ -81,6 +89,10 @@ where
     fn size(&self) -> usize {
         self.factors.len()
     }
+
+    fn shared_factors(&self) -> &[Arc<Self::FactorType>] {
+        &self.factors
+    }
 }
 
 impl<'a, FACTOR> Iterator for FactorIterator<'a, SimpleFactorGraph<FACTOR>>
@@ -93,17 +105,129 @@ where
         match self.owner.factors.get(self.index) {
             Some(a) => {
                 self.index += 1;
-                Some(&(*a))
+                Some(a)
             }
             None => None,
         }
     }
 }
 
-pub trait EliminateableFactorGraph<FactorType>: FactorGraph
-where
-    FactorType: Factor,
-{
+/// Whether `ordering` only holds keys of the graph of `index`, each at most once
+fn is_valid_ordering(index: &VariableIndex, ordering: &[KeyType]) -> bool {
+    let mut seen = BTreeSet::new();
+    ordering
+        .iter()
+        .all(|key| index.contains(*key) && seen.insert(*key))
+}
+
+/// A factor graph whose variables can be eliminated one by one, turning it into a Bayes net.
+///
+/// How a set of factors is eliminated is up to the eliminate function passed in, e.g. QR or
+/// Cholesky for Gaussian graphs, which makes the elimination algorithms themselves generic.
+pub trait EliminateableFactorGraph: FactorGraph {
+    type ConditionalType;
+
+    /// Eliminates the keys of `ordering` one after the other, returning the Bayes net of their
+    /// conditionals in elimination order and the graph of the factors left on the other keys.
+    ///
+    /// `eliminate` receives the factors that involve a key and that key as the frontal, and
+    /// returns the conditional of the frontal given the rest and the new factor on the rest.
+    /// If it fails, e.g. on a singular system, the whole elimination returns `None`, as it does
+    /// when a key of `ordering` is not in the graph or appears twice.
+    #[allow(clippy::type_complexity)]
+    fn eliminate_sequential<E>(
+        &self,
+        ordering: &[KeyType],
+        eliminate: E,
+    ) -> Option<(BayesNet<Self::ConditionalType>, Self)>
+    where
+        E: Fn(&Self, &[KeyType]) -> Option<(Self::ConditionalType, Arc<Self::FactorType>)>,
+    {
+        // The variable index finds the factors of each key, the eliminated ones are taken out
+        let mut index = VariableIndex::new(self);
+        let mut factors: Vec<Option<Arc<Self::FactorType>>> =
+            self.shared_factors().iter().cloned().map(Some).collect();
+        let mut bayes_net = BayesNet::new();
+
+        for key in ordering {
+            if !index.contains(*key) {
+                return None;
+            }
+            let mut involved = Self::new();
+            for &i in index.factors(*key) {
+                if let Some(factor) = factors[i].take() {
+                    involved.insert_shared(factor);
+                }
+            }
+
+            // Every key of the graph has factors until it is eliminated
+            if involved.size() == 0 {
+                return None;
+            }
+
+            let (conditional, factor) = eliminate(&involved, &[*key])?;
+            bayes_net.push(conditional);
+
+            let mut new_factor = Self::new();
+            new_factor.insert_shared(factor.clone());
+            index.augment(&new_factor);
+            factors.push(Some(factor));
+        }
+
+        let mut graph = Self::new();
+        for factor in factors.into_iter().flatten() {
+            graph.insert_shared(factor);
+        }
+
+        Some((bayes_net, graph))
+    }
+
+    /// Eliminates the keys of `ordering` along the junction tree of the graph, returning the
+    /// Bayes tree of the cliques and the graph of the factors left on the other keys.
+    ///
+    /// `eliminate` works as for `eliminate_sequential`, but is given all frontals of a clique.
+    /// Returns `None` if it fails or if a key of `ordering` is not in the graph or appears twice.
+    #[allow(clippy::type_complexity)]
+    fn eliminate_multifrontal<E>(
+        &self,
+        ordering: &[KeyType],
+        eliminate: E,
+    ) -> Option<(BayesTree<Self::ConditionalType>, Self)>
+    where
+        E: Fn(&Self, &[KeyType]) -> Option<(Self::ConditionalType, Arc<Self::FactorType>)>,
+    {
+        let index = VariableIndex::new(self);
+        if !is_valid_ordering(&index, ordering) {
+            return None;
+        }
+        let elimination_tree = EliminationTree::new(self, &index, ordering);
+
+        JunctionTree::new(elimination_tree).eliminate(eliminate)
+    }
+
+    /// `eliminate_multifrontal` with independent subtrees of the junction tree eliminated
+    /// concurrently, see `JunctionTree::eliminate_parallel`
+    #[allow(clippy::type_complexity)]
+    fn eliminate_multifrontal_parallel<E>(
+        &self,
+        ordering: &[KeyType],
+        eliminate: E,
+        threshold: usize,
+    ) -> Option<(BayesTree<Self::ConditionalType>, Self)>
+    where
+        Self: Sync,
+        Self::FactorType: Send + Sync,
+        Self::ConditionalType: Send + Sync,
+        E: Fn(&Self, &[KeyType]) -> Option<(Self::ConditionalType, Arc<Self::FactorType>)> + Sync,
+    {
+        let index = VariableIndex::new(self);
+        if !is_valid_ordering(&index, ordering) {
+            return None;
+        }
+        let elimination_tree = EliminationTree::new(self, &index, ordering);
+
+        JunctionTree::new(elimination_tree).eliminate_parallel(eliminate, threshold)
+    }
 }
 
 #[cfg(test)]
@@ -192,6 +316,119 @@ pub mod tests {
         }
     }
 
+    /// The structure of a conditional, as left by symbolic elimination
+    #[derive(Debug, PartialEq)]
+    pub struct SymbolicConditional {
+        pub frontals: Vec<KeyType>,
+        pub parents: Vec<KeyType>,
+    }
+
+    impl EliminateableFactorGraph for SimpleFactorGraph<TestFactor> {
+        type ConditionalType = SymbolicConditional;
+    }
+
+    /// Eliminates `frontals` by only tracking which keys are involved
+    pub fn eliminate_symbolic(
+        factors: &SimpleFactorGraph<TestFactor>,
+        frontals: &[KeyType],
+    ) -> Option<(SymbolicConditional, Arc<TestFactor>)> {
+        let mut parents: Vec<KeyType> = factors
+            .factors
+            .iter()
+            .flat_map(|f| f._keys.iter().cloned())
+            .filter(|key| !frontals.contains(key))
+            .collect();
+        parents.sort_unstable();
+        parents.dedup();
+
+        let factor = TestFactor {
+            inner: format!("eliminated {:?}", frontals),
+            _keys: parents.clone(),
+        };
+        let conditional = SymbolicConditional {
+            frontals: frontals.to_vec(),
+            parents,
+        };
+
+        Some((conditional, Arc::new(factor)))
+    }
+
+    /// The chain 0 - 1 - 2 - 3 with an extra factor between 0 and 2
+    pub fn symbolic_chain() -> SimpleFactorGraph<TestFactor> {
+        let mut fg = SimpleFactorGraph::new();
+        for keys in [vec![0, 1], vec![1, 2], vec![2, 3], vec![0, 2], vec![3]] {
+            fg.insert(TestFactor {
+                inner: format!("{:?}", keys),
+                _keys: keys,
+            });
+        }
+        fg
+    }
+
+    #[test]
+    fn sequential_elimination() {
+        let fg = symbolic_chain();
+
+        let (bayes_net, remaining) = fg
+            .eliminate_sequential(&[0, 1, 2, 3], eliminate_symbolic)
+            .unwrap();
+        let parents: Vec<_> = bayes_net.iter().map(|c| c.parents.clone()).collect();
+        assert_eq!(parents, vec![vec![1, 2], vec![2], vec![3], vec![]]);
+
+        // Only the empty factor left by the last key remains
+        assert_eq!(remaining.size(), 1);
+        assert_eq!(remaining.factors[0].num_keys(), 0);
+    }
+
+    #[test]
+    fn partial_sequential_elimination() {
+        let fg = symbolic_chain();
+
+        let (bayes_net, remaining) = fg
+            .eliminate_sequential(&[1, 0], eliminate_symbolic)
+            .unwrap();
+        assert_eq!(
+            bayes_net.conditionals[0].as_ref(),
+            &SymbolicConditional {
+                frontals: vec![1],
+                parents: vec![0, 2]
+            }
+        );
+        assert_eq!(bayes_net.conditionals[1].parents, vec![2]);
+
+        // The factors on 2 and 3 are untouched, plus the one left by eliminating 0
+        let mut keys: Vec<_> = remaining.factors.iter().map(|f| f._keys.clone()).collect();
+        keys.sort();
+        assert_eq!(keys, vec![vec![2], vec![2, 3], vec![3]]);
+    }
+
+    #[test]
+    fn multifrontal_elimination() {
+        let fg = symbolic_chain();
+
+        let (bayes_tree, remaining) = fg
+            .eliminate_multifrontal(&[0, 1, 2, 3], eliminate_symbolic)
+            .unwrap();
+        assert_eq!(bayes_tree.size(), 2);
+        assert_eq!(remaining.size(), 1);
+
+        let root = &bayes_tree.roots[0];
+        assert_eq!(
+            root.conditional.as_ref(),
+            &SymbolicConditional {
+                frontals: vec![2, 3],
+                parents: vec![]
+            }
+        );
+        assert_eq!(
+            root.children[0].conditional.as_ref(),
+            &SymbolicConditional {
+                frontals: vec![0, 1],
+                parents: vec![2]
+            }
+        );
+    }
+
     #[test]
     fn test_trait_object() {
         let mut fg = SimpleFactorGraph::<dyn Factor> { factors: vec![] };
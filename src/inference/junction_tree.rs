@@ -1 +1,476 @@
+use crate::inference::bayes_tree::{BayesTree, BayesTreeClique};
+use crate::inference::cluster_tree::{Cluster, ClusterTree};
+use crate::inference::elimination_tree::{EliminationTree, EliminationTreeNode};
+use crate::inference::factor::KeyType;
+use crate::inference::{FactorGraph, FactorKeys};
+use rayon::prelude::*;
+use std::collections::BTreeSet;
+use std::sync::Arc;
 
+/// Subtrees with fewer frontals than this are eliminated sequentially by default
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 64;
+
+/// A junction tree is an elimination tree whose nodes are merged into clusters of frontals
+/// that are eliminated together, namely wherever a child's separator is exactly its parent's
+/// frontals and separator.
+#[derive(Debug)]
+pub struct JunctionTree<Graph: FactorGraph> {
+    pub tree: ClusterTree<Graph>,
+    pub remaining_factors: Vec<Arc<Graph::FactorType>>,
+}
+
+/// A node of the elimination tree on the stack of `build_cluster`, waiting for its children
+struct BuildFrame<Graph: FactorGraph> {
+    key: KeyType,
+    cluster: Cluster<Graph>,
+    /// The keys of its factors and the separators of the children built so far
+    keys: BTreeSet<KeyType>,
+    child_separators: Vec<usize>,
+    children: std::vec::IntoIter<Box<EliminationTreeNode<Graph>>>,
+}
+
+impl<Graph: FactorGraph> BuildFrame<Graph> {
+    fn new(mut node: EliminationTreeNode<Graph>) -> Self {
+        let mut keys = BTreeSet::new();
+        for factor in node.factors.iter() {
+            keys.extend(factor.key_iter());
+        }
+
+        BuildFrame {
+            key: node.key,
+            cluster: Cluster::from_single_key(node.key, &node.factors),
+            keys,
+            child_separators: Vec::new(),
+            children: std::mem::take(&mut node.children).into_iter(),
+        }
+    }
+
+    fn add_child(&mut self, child: Box<Cluster<Graph>>, separator: BTreeSet<KeyType>) {
+        self.keys.extend(separator.iter());
+        self.child_separators.push(separator.len());
+        self.cluster.add_child(child);
+    }
+
+    /// The cluster once all children are added, and its separator
+    fn finish(mut self) -> (Box<Cluster<Graph>>, BTreeSet<KeyType>) {
+        self.keys.remove(&self.key);
+
+        // A child whose separator is all of this cluster is absorbed into it
+        let n_parents = self.keys.len();
+        let mut n_frontals = 1;
+        let merge = self
+            .cluster
+            .children
+            .iter()
+            .zip(self.child_separators)
+            .map(|(child, n_child_parents)| {
+                let merge = n_parents + n_frontals == n_child_parents;
+                if merge {
+                    n_frontals += child.frontals.len();
+                }
+                merge
+            })
+            .collect();
+        self.cluster.merge_children(merge);
+
+        (Box::new(self.cluster), self.keys)
+    }
+}
+
+/// Turns `node` and its descendants into clusters, returning the root cluster and its
+/// separator, i.e. the keys it depends on that are eliminated later.
+///
+/// The tree is walked with an explicit stack, as elimination trees can be as deep as they
+/// have nodes.
+fn build_cluster<Graph: FactorGraph>(
+    node: EliminationTreeNode<Graph>,
+) -> (Box<Cluster<Graph>>, BTreeSet<KeyType>) {
+    let mut stack = vec![BuildFrame::new(node)];
+    loop {
+        let top = stack.last_mut().unwrap();
+        if let Some(child) = top.children.next() {
+            stack.push(BuildFrame::new(*child));
+            continue;
+        }
+
+        let (cluster, separator) = stack.pop().unwrap().finish();
+        match stack.last_mut() {
+            Some(parent) => parent.add_child(cluster, separator),
+            None => return (cluster, separator),
+        }
+    }
+}
+
+/// A clique and the factor its elimination leaves on its separator
+type Eliminated<Graph, C> = (BayesTreeClique<C>, Arc<<Graph as FactorGraph>::FactorType>);
+
+/// Eliminates the frontals of `cluster` given the results of eliminating its children
+fn eliminate_frontals<Graph, C, E>(
+    cluster: &Cluster<Graph>,
+    eliminated_children: impl Iterator<Item = Eliminated<Graph, C>>,
+    eliminate: &E,
+) -> Option<Eliminated<Graph, C>>
+where
+    Graph: FactorGraph,
+    E: Fn(&Graph, &[KeyType]) -> Option<(C, Arc<Graph::FactorType>)>,
+{
+    let mut factors = Graph::new();
+    for factor in cluster.factors.shared_factors() {
+        factors.insert_shared(factor.clone());
+    }
+
+    let mut children = Vec::with_capacity(cluster.children.len());
+    for (clique, factor) in eliminated_children {
+        factors.insert_shared(factor);
+        children.push(clique);
+    }
+
+    let (conditional, factor) = eliminate(&factors, &cluster.frontals)?;
+
+    Some((
+        BayesTreeClique {
+            conditional: Arc::new(conditional),
+            children,
+        },
+        factor,
+    ))
+}
+
+/// A cluster waiting for its children to be eliminated
+struct EliminationFrame<'a, Graph: FactorGraph, C> {
+    cluster: &'a Cluster<Graph>,
+    children: Vec<Eliminated<Graph, C>>,
+}
+
+impl<'a, Graph: FactorGraph, C> EliminationFrame<'a, Graph, C> {
+    fn new(cluster: &'a Cluster<Graph>) -> Self {
+        EliminationFrame {
+            cluster,
+            children: Vec::with_capacity(cluster.children.len()),
+        }
+    }
+}
+
+/// Eliminates the frontals of `cluster` after all its children, returning its clique and the
+/// factor left on its separator. The tree is walked with an explicit stack.
+fn eliminate_cluster<Graph, C, E>(
+    cluster: &Cluster<Graph>,
+    eliminate: &E,
+) -> Option<Eliminated<Graph, C>>
+where
+    Graph: FactorGraph,
+    E: Fn(&Graph, &[KeyType]) -> Option<(C, Arc<Graph::FactorType>)>,
+{
+    let mut stack = vec![EliminationFrame::new(cluster)];
+    loop {
+        let top = stack.last_mut().unwrap();
+        let cluster = top.cluster;
+        if let Some(child) = cluster.children.get(top.children.len()) {
+            stack.push(EliminationFrame::new(child));
+            continue;
+        }
+
+        let top = stack.pop().unwrap();
+        let eliminated = eliminate_frontals(top.cluster, top.children.into_iter(), eliminate)?;
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(eliminated),
+            None => return Some(eliminated),
+        }
+    }
+}
+
+/// The number of clusters and of frontals in the subtree of every cluster of a tree, indexed
+/// in pre-order: the first child of the cluster at `i` is at `i + 1`, and every other child
+/// follows the subtree of the one before it
+struct SubtreeSizes {
+    clusters: Vec<usize>,
+    frontals: Vec<usize>,
+}
+
+impl SubtreeSizes {
+    fn new<Graph: FactorGraph>(root: &Cluster<Graph>) -> Self {
+        // Every cluster in pre-order with the index of its parent
+        let mut parents = Vec::new();
+        let mut frontals = Vec::new();
+        let mut stack = vec![(root, None)];
+        while let Some((cluster, parent)) = stack.pop() {
+            let i = parents.len();
+            parents.push(parent);
+            frontals.push(cluster.frontals.len());
+            stack.extend(cluster.children.iter().rev().map(|c| (c.as_ref(), Some(i))));
+        }
+
+        // Children come after their parents
+        let mut clusters = vec![1; parents.len()];
+        for i in (1..parents.len()).rev() {
+            let parent = parents[i].unwrap();
+            clusters[parent] += clusters[i];
+            frontals[parent] += frontals[i];
+        }
+
+        SubtreeSizes { clusters, frontals }
+    }
+
+    /// The children of the cluster at `index` with their indices
+    fn children<'a, Graph: FactorGraph>(
+        &'a self,
+        cluster: &'a Cluster<Graph>,
+        index: usize,
+    ) -> impl Iterator<Item = (&'a Cluster<Graph>, usize)> + 'a {
+        cluster.children.iter().scan(index + 1, move |next, child| {
+            let i = *next;
+            *next += self.clusters[i];
+            Some((child.as_ref(), i))
+        })
+    }
+}
+
+/// A cluster of at least `threshold` frontals waiting for its children to be eliminated.
+///
+/// All children but one are eliminated concurrently when the frame is made. If exactly one
+/// child is that large too, it is left to the caller, so that long paths of large clusters
+/// are walked with an explicit stack and only forks recurse.
+struct ParallelFrame<'a, Graph: FactorGraph, C> {
+    cluster: &'a Cluster<Graph>,
+    children: Vec<Option<Eliminated<Graph, C>>>,
+    /// The position and index of the child left to the caller
+    deferred: Option<(usize, usize)>,
+}
+
+impl<'a, Graph, C> ParallelFrame<'a, Graph, C>
+where
+    Graph: FactorGraph + Sync,
+    Graph::FactorType: Send + Sync,
+    C: Send + Sync,
+{
+    fn new<E>(
+        cluster: &'a Cluster<Graph>,
+        sizes: &SubtreeSizes,
+        index: usize,
+        eliminate: &E,
+        threshold: usize,
+    ) -> Option<Self>
+    where
+        E: Fn(&Graph, &[KeyType]) -> Option<(C, Arc<Graph::FactorType>)> + Sync,
+    {
+        let children: Vec<_> = sizes.children(cluster, index).collect();
+        let mut large = children
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, i))| sizes.frontals[*i] >= threshold)
+            .map(|(position, (_, i))| (position, *i));
+        let deferred = match (large.next(), large.next()) {
+            (Some(child), None) => Some(child),
+            _ => None,
+        };
+
+        let children = children
+            .par_iter()
+            .enumerate()
+            .map(|(position, (child, i))| match deferred {
+                Some((deferred, _)) if deferred == position => Some(None),
+                _ => eliminate_cluster_parallel(child, sizes, *i, eliminate, threshold).map(Some),
+            })
+            .collect::<Option<_>>()?;
+
+        Some(ParallelFrame {
+            cluster,
+            children,
+            deferred,
+        })
+    }
+}
+
+/// `eliminate_cluster` with the children of every subtree of at least `threshold` frontals
+/// eliminated concurrently. The factors of a cluster are gathered in the same order as in
+/// `eliminate_cluster`, so the results are identical.
+fn eliminate_cluster_parallel<Graph, C, E>(
+    cluster: &Cluster<Graph>,
+    sizes: &SubtreeSizes,
+    index: usize,
+    eliminate: &E,
+    threshold: usize,
+) -> Option<Eliminated<Graph, C>>
+where
+    Graph: FactorGraph + Sync,
+    Graph::FactorType: Send + Sync,
+    C: Send + Sync,
+    E: Fn(&Graph, &[KeyType]) -> Option<(C, Arc<Graph::FactorType>)> + Sync,
+{
+    if sizes.frontals[index] < threshold {
+        return eliminate_cluster(cluster, eliminate);
+    }
+
+    let mut stack = vec![ParallelFrame::new(
+        cluster, sizes, index, eliminate, threshold,
+    )?];
+    loop {
+        let top = stack.last_mut().unwrap();
+        if let Some((position, i)) = top.deferred {
+            let child = top.cluster.children[position].as_ref();
+            stack.push(ParallelFrame::new(child, sizes, i, eliminate, threshold)?);
+            continue;
+        }
+
+        let top = stack.pop().unwrap();
+        let children = top.children.into_iter().map(Option::unwrap);
+        let eliminated = eliminate_frontals(top.cluster, children, eliminate)?;
+        match stack.last_mut() {
+            Some(parent) => {
+                let (position, _) = parent.deferred.take().unwrap();
+                parent.children[position] = Some(eliminated);
+            }
+            None => return Some(eliminated),
+        }
+    }
+}
+
+impl<Graph: FactorGraph> JunctionTree<Graph> {
+    pub fn new(elimination_tree: EliminationTree<Graph>) -> Self {
+        let mut tree = ClusterTree::new();
+        for root in elimination_tree.roots {
+            let (cluster, _) = build_cluster(*root);
+            tree.roots.push(cluster);
+        }
+
+        JunctionTree {
+            tree,
+            remaining_factors: elimination_tree.remaining_factors,
+        }
+    }
+
+    /// Multifrontal elimination: every cluster is eliminated with `eliminate` once all its
+    /// children are, giving a Bayes tree with one clique per cluster and the graph of the
+    /// factors left on keys that were not eliminated
+    pub fn eliminate<C, E>(&self, eliminate: E) -> Option<(BayesTree<C>, Graph)>
+    where
+        E: Fn(&Graph, &[KeyType]) -> Option<(C, Arc<Graph::FactorType>)>,
+    {
+        let mut bayes_tree = BayesTree::new();
+        let mut remaining = Graph::new();
+
+        for root in self.tree.roots.iter() {
+            let (clique, factor) = eliminate_cluster(root, &eliminate)?;
+            bayes_tree.roots.push(clique);
+            remaining.insert_shared(factor);
+        }
+        for factor in self.remaining_factors.iter() {
+            remaining.insert_shared(factor.clone());
+        }
+
+        Some((bayes_tree, remaining))
+    }
+
+    /// `eliminate` with independent subtrees eliminated concurrently on the rayon thread pool.
+    /// Subtrees with fewer than `threshold` frontals stay on one thread, and the result is
+    /// identical to that of `eliminate`.
+    pub fn eliminate_parallel<C, E>(
+        &self,
+        eliminate: E,
+        threshold: usize,
+    ) -> Option<(BayesTree<C>, Graph)>
+    where
+        Graph: Sync,
+        Graph::FactorType: Send + Sync,
+        C: Send + Sync,
+        E: Fn(&Graph, &[KeyType]) -> Option<(C, Arc<Graph::FactorType>)> + Sync,
+    {
+        let eliminated: Vec<_> = self
+            .tree
+            .roots
+            .par_iter()
+            .map(|root| {
+                let sizes = SubtreeSizes::new(root);
+                eliminate_cluster_parallel(root, &sizes, 0, &eliminate, threshold)
+            })
+            .collect::<Option<_>>()?;
+
+        let mut bayes_tree = BayesTree::new();
+        let mut remaining = Graph::new();
+        for (clique, factor) in eliminated {
+            bayes_tree.roots.push(clique);
+            remaining.insert_shared(factor);
+        }
+        for factor in self.remaining_factors.iter() {
+            remaining.insert_shared(factor.clone());
+        }
+
+        Some((bayes_tree, remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::factor_graph::tests::{eliminate_symbolic, symbolic_chain, TestFactor};
+    use crate::inference::factor_graph::SimpleFactorGraph;
+    use crate::inference::variable_index::VariableIndex;
+
+    fn junction_tree(
+        graph: &SimpleFactorGraph<TestFactor>,
+        ordering: &[KeyType],
+    ) -> JunctionTree<SimpleFactorGraph<TestFactor>> {
+        let index = VariableIndex::new(graph);
+        JunctionTree::new(EliminationTree::new(graph, &index, ordering))
+    }
+
+    #[test]
+    fn chain_merges_into_cliques() {
+        let tree = junction_tree(&symbolic_chain(), &[0, 1, 2, 3]);
+
+        // The cliques are {0, 1 | 2} and {2, 3}
+        assert_eq!(tree.tree.roots.len(), 1);
+        let root = &tree.tree.roots[0];
+        assert_eq!(root.frontals, vec![2, 3]);
+        assert_eq!(root.factors.size(), 2);
+
+        assert_eq!(root.children.len(), 1);
+        let child = &root.children[0];
+        assert_eq!(child.frontals, vec![0, 1]);
+        assert_eq!(child.factors.size(), 3);
+        assert!(child.children.is_empty());
+    }
+
+    #[test]
+    fn star_keeps_separate_cliques() {
+        // Leaves 1, 2 and 3 all attached to the hub 0
+        let mut graph = SimpleFactorGraph::new();
+        for keys in [vec![0, 1], vec![0, 2], vec![0, 3], vec![1]] {
+            graph.insert(TestFactor {
+                inner: format!("{:?}", keys),
+                _keys: keys,
+            });
+        }
+
+        let tree = junction_tree(&graph, &[1, 2, 3, 0]);
+        assert_eq!(tree.tree.roots.len(), 1);
+
+        // The first leaf merges into the hub, the others stay cliques with separator {0}
+        let root = &tree.tree.roots[0];
+        assert_eq!(root.frontals, vec![1, 0]);
+        let children: Vec<_> = root.children.iter().map(|c| c.frontals.clone()).collect();
+        assert_eq!(children, vec![vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn parallel_elimination_matches_sequential() {
+        let mut graph = symbolic_chain();
+        for key in 4..40 {
+            graph.insert(TestFactor {
+                inner: format!("{:?}", [key % 4, key]),
+                _keys: vec![key % 4, key],
+            });
+        }
+        let ordering: Vec<KeyType> = (0..40).rev().collect();
+        let tree = junction_tree(&graph, &ordering);
+
+        let (sequential, _) = tree.eliminate(eliminate_symbolic).unwrap();
+        for threshold in [0, 4, DEFAULT_PARALLEL_THRESHOLD] {
+            let (parallel, remaining) = tree
+                .eliminate_parallel(eliminate_symbolic, threshold)
+                .unwrap();
+            assert_eq!(format!("{:?}", parallel), format!("{:?}", sequential));
+            assert_eq!(remaining.size(), sequential.roots.len());
+        }
+    }
+}
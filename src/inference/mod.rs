@@ -1,10 +1,21 @@
+pub mod bayes_net;
+pub mod bayes_tree;
 pub mod cluster_tree;
 pub mod conditional;
+pub mod elimination_tree;
 pub mod expression;
 pub mod factor;
 pub mod factor_graph;
 pub mod junction_tree;
+pub mod nested_dissection;
+pub mod ordering;
+pub mod variable_index;
 
+pub use bayes_net::BayesNet;
+pub use bayes_tree::{BayesTree, BayesTreeClique};
 pub use conditional::Conditional;
-pub use factor::Factor;
+pub use factor::{Factor, FactorKeys};
 pub use factor_graph::{EliminateableFactorGraph, FactorGraph};
+pub use nested_dissection::NestedDissection;
+pub use ordering::Ordering;
+pub use variable_index::VariableIndex;
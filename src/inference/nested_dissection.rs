@@ -0,0 +1,559 @@
+//! Nested-dissection orderings.
+//!
+//! The graph of the keys is split recursively by small vertex separators, found by multilevel
+//! bisection: the graph is coarsened by heavy-edge matching, the coarsest graph is bisected by
+//! greedy growing, and the bisection is refined on the way back to the original graph. Every
+//! separator is eliminated after the two halves it separates, so the separators form a tree
+//! that is directly a cluster tree for multifrontal elimination.
+
+use crate::inference::cluster_tree::{Cluster, ClusterTree};
+use crate::inference::factor::KeyType;
+use crate::inference::junction_tree::JunctionTree;
+use crate::inference::ordering::Ordering;
+use crate::inference::variable_index::VariableIndex;
+use crate::inference::{FactorGraph, FactorKeys};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Parts with at most this many keys are not dissected further
+pub const DEFAULT_LEAF_SIZE: usize = 16;
+
+/// Graphs are coarsened until they have at most this many nodes
+const COARSEST_SIZE: usize = 32;
+
+/// A node of the separator tree: its keys are eliminated after those of all its children
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeparatorTree {
+    pub keys: Vec<KeyType>,
+    pub children: Vec<SeparatorTree>,
+}
+
+/// A nested-dissection ordering together with its separator tree
+#[derive(Debug, Clone)]
+pub struct NestedDissection {
+    pub ordering: Ordering,
+    pub roots: Vec<SeparatorTree>,
+}
+
+/// An undirected graph with node and edge weights
+struct WeightedGraph {
+    node_weights: Vec<usize>,
+    adjacency: Vec<Vec<(usize, usize)>>,
+}
+
+impl WeightedGraph {
+    fn len(&self) -> usize {
+        self.node_weights.len()
+    }
+
+    fn total_weight(&self) -> usize {
+        self.node_weights.iter().sum()
+    }
+
+    /// Merges matched pairs of nodes, preferring the heaviest edges, and returns the coarse
+    /// graph with the coarse node of every node
+    fn coarsen(&self) -> (WeightedGraph, Vec<usize>) {
+        let mut coarse_of = vec![usize::MAX; self.len()];
+        let mut n_coarse = 0;
+
+        for v in 0..self.len() {
+            if coarse_of[v] != usize::MAX {
+                continue;
+            }
+            let mate = self.adjacency[v]
+                .iter()
+                .filter(|(u, _)| coarse_of[*u] == usize::MAX && *u != v)
+                .max_by_key(|(u, w)| (*w, usize::MAX - *u))
+                .map(|(u, _)| *u);
+
+            coarse_of[v] = n_coarse;
+            if let Some(u) = mate {
+                coarse_of[u] = n_coarse;
+            }
+            n_coarse += 1;
+        }
+
+        let mut node_weights = vec![0; n_coarse];
+        let mut edges: Vec<BTreeMap<usize, usize>> = vec![BTreeMap::new(); n_coarse];
+        for v in 0..self.len() {
+            let cv = coarse_of[v];
+            node_weights[cv] += self.node_weights[v];
+            for &(u, w) in self.adjacency[v].iter() {
+                let cu = coarse_of[u];
+                if cu != cv {
+                    *edges[cv].entry(cu).or_insert(0) += w;
+                }
+            }
+        }
+
+        let coarse = WeightedGraph {
+            node_weights,
+            adjacency: edges.into_iter().map(|e| e.into_iter().collect()).collect(),
+        };
+
+        (coarse, coarse_of)
+    }
+
+    /// The node farthest from `start` in breadth-first order
+    fn farthest(&self, start: usize) -> usize {
+        let mut visited = vec![false; self.len()];
+        let mut queue = VecDeque::from(vec![start]);
+        visited[start] = true;
+
+        let mut last = start;
+        while let Some(v) = queue.pop_front() {
+            last = v;
+            for &(u, _) in self.adjacency[v].iter() {
+                if !visited[u] {
+                    visited[u] = true;
+                    queue.push_back(u);
+                }
+            }
+        }
+
+        last
+    }
+
+    /// Grows part 0 breadth-first from a pseudo-peripheral node until it holds half the weight
+    fn grow_bisection(&self) -> Vec<u8> {
+        let mut part = vec![1; self.len()];
+        let half = self.total_weight() / 2;
+
+        let mut weight = 0;
+        let mut visited = vec![false; self.len()];
+        let mut queue = VecDeque::new();
+        let mut next_seed = 0;
+
+        while weight < half {
+            let v = match queue.pop_front() {
+                Some(v) => v,
+                None => {
+                    // Start from a new component
+                    while visited[next_seed] {
+                        next_seed += 1;
+                    }
+                    let seed = self.farthest(next_seed);
+                    let seed = if visited[seed] { next_seed } else { seed };
+                    visited[seed] = true;
+                    seed
+                }
+            };
+
+            part[v] = 0;
+            weight += self.node_weights[v];
+            for &(u, _) in self.adjacency[v].iter() {
+                if !visited[u] {
+                    visited[u] = true;
+                    queue.push_back(u);
+                }
+            }
+        }
+
+        part
+    }
+
+    /// Greedily moves boundary nodes to the other part while that reduces the cut and keeps
+    /// both parts within the balance tolerance
+    fn refine(&self, part: &mut [u8]) {
+        let total = self.total_weight();
+        let max_weight = total - total * 9 / 20;
+
+        let mut weights = [0, 0];
+        for v in 0..self.len() {
+            weights[part[v] as usize] += self.node_weights[v];
+        }
+
+        for _ in 0..4 {
+            let mut moved = false;
+            for v in 0..self.len() {
+                let (mut internal, mut external) = (0, 0);
+                for &(u, w) in self.adjacency[v].iter() {
+                    if part[u] == part[v] {
+                        internal += w;
+                    } else {
+                        external += w;
+                    }
+                }
+
+                let to = 1 - part[v] as usize;
+                if external > internal && weights[to] + self.node_weights[v] <= max_weight {
+                    weights[to] += self.node_weights[v];
+                    weights[1 - to] -= self.node_weights[v];
+                    part[v] = to as u8;
+                    moved = true;
+                }
+            }
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    /// Multilevel bisection
+    fn bisect(&self) -> Vec<u8> {
+        if self.len() <= COARSEST_SIZE {
+            let mut part = self.grow_bisection();
+            self.refine(&mut part);
+            return part;
+        }
+
+        let (coarse, coarse_of) = self.coarsen();
+        if coarse.len() * 10 > self.len() * 9 {
+            // Matching stalls, e.g. on stars
+            let mut part = self.grow_bisection();
+            self.refine(&mut part);
+            return part;
+        }
+
+        let coarse_part = coarse.bisect();
+        let mut part: Vec<u8> = coarse_of.iter().map(|c| coarse_part[*c]).collect();
+        self.refine(&mut part);
+        part
+    }
+}
+
+/// Dissects the subgraph of `nodes` of `adjacency`, returning its separator forest in which
+/// the keys of every node are in elimination order
+fn dissect(
+    nodes: &[usize],
+    adjacency: &[Vec<usize>],
+    keys: &[KeyType],
+    leaf_size: usize,
+) -> Vec<SeparatorTree> {
+    let leaf = || {
+        vec![SeparatorTree {
+            keys: nodes.iter().map(|v| keys[*v]).collect(),
+            children: vec![],
+        }]
+    };
+    if nodes.is_empty() {
+        return Vec::new();
+    }
+    if nodes.len() <= leaf_size {
+        return leaf();
+    }
+
+    let local: BTreeMap<usize, usize> = nodes.iter().enumerate().map(|(i, v)| (*v, i)).collect();
+    let graph = WeightedGraph {
+        node_weights: vec![1; nodes.len()],
+        adjacency: nodes
+            .iter()
+            .map(|v| {
+                adjacency[*v]
+                    .iter()
+                    .filter_map(|u| local.get(u).map(|i| (*i, 1)))
+                    .collect()
+            })
+            .collect(),
+    };
+    let part = graph.bisect();
+
+    // Either side of the cut covers every cut edge, so the smaller one is a vertex separator
+    let mut boundaries = [BTreeSet::new(), BTreeSet::new()];
+    for v in 0..graph.len() {
+        if graph.adjacency[v].iter().any(|(u, _)| part[*u] != part[v]) {
+            boundaries[part[v] as usize].insert(v);
+        }
+    }
+    let separator = if boundaries[0].len() <= boundaries[1].len() {
+        &boundaries[0]
+    } else {
+        &boundaries[1]
+    };
+
+    let halves: Vec<Vec<usize>> = (0..2)
+        .map(|p| {
+            (0..graph.len())
+                .filter(|v| part[*v] == p && !separator.contains(v))
+                .map(|v| nodes[v])
+                .collect()
+        })
+        .collect();
+    if halves.iter().any(|h| h.len() == nodes.len()) || halves.iter().all(|h| h.is_empty()) {
+        // No progress, e.g. on a clique
+        return leaf();
+    }
+
+    let mut children = Vec::new();
+    for half in halves.iter().filter(|h| !h.is_empty()) {
+        children.extend(dissect(half, adjacency, keys, leaf_size));
+    }
+
+    if separator.is_empty() {
+        // Disconnected halves are independent trees
+        return children;
+    }
+
+    vec![SeparatorTree {
+        keys: separator.iter().map(|v| keys[nodes[*v]]).collect(),
+        children,
+    }]
+}
+
+/// Appends the keys of `tree` in elimination order, children first
+fn collect_ordering(tree: &SeparatorTree, ordering: &mut Vec<KeyType>) {
+    for child in tree.children.iter() {
+        collect_ordering(child, ordering);
+    }
+    ordering.extend(tree.keys.iter());
+}
+
+impl NestedDissection {
+    pub fn new(index: &VariableIndex) -> Self {
+        NestedDissection::with_leaf_size(index, DEFAULT_LEAF_SIZE)
+    }
+
+    /// Nested dissection of the keys of `index`, stopping at parts of at most `leaf_size` keys
+    pub fn with_leaf_size(index: &VariableIndex, leaf_size: usize) -> Self {
+        let keys: Vec<KeyType> = index.keys().collect();
+
+        // Two keys are adjacent if they share a factor
+        let mut factor_keys: Vec<Vec<usize>> = vec![Vec::new(); index.num_factors()];
+        for (i, key) in keys.iter().enumerate() {
+            for &f in index.factors(*key) {
+                factor_keys[f].push(i);
+            }
+        }
+        let mut adjacency: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); keys.len()];
+        for factor in factor_keys.iter() {
+            for &v in factor {
+                adjacency[v].extend(factor.iter().filter(|u| **u != v));
+            }
+        }
+        let adjacency: Vec<Vec<usize>> = adjacency
+            .into_iter()
+            .map(|a| a.into_iter().collect())
+            .collect();
+
+        let nodes: Vec<usize> = (0..keys.len()).collect();
+        let roots = dissect(&nodes, &adjacency, &keys, leaf_size.max(1));
+
+        let mut ordering = Vec::with_capacity(keys.len());
+        for root in roots.iter() {
+            collect_ordering(root, &mut ordering);
+        }
+        debug_assert_eq!(ordering.len(), keys.len());
+
+        NestedDissection {
+            ordering: Ordering::new(ordering),
+            roots,
+        }
+    }
+
+    /// The cluster tree of `graph` with one cluster per node of the separator tree, in which
+    /// every factor belongs to the cluster of the first of its keys to be eliminated, as a
+    /// junction tree ready for multifrontal elimination.
+    ///
+    /// `graph` must be the graph whose variable index was dissected.
+    pub fn junction_tree<Graph: FactorGraph>(&self, graph: &Graph) -> JunctionTree<Graph> {
+        let positions = self.ordering.positions();
+
+        // The separator tree node of every key, numbered in pre-order
+        let mut node_of = BTreeMap::new();
+        let mut n_nodes = 0;
+        fn number(tree: &SeparatorTree, node_of: &mut BTreeMap<KeyType, usize>, n: &mut usize) {
+            for key in tree.keys.iter() {
+                node_of.insert(*key, *n);
+            }
+            *n += 1;
+            for child in tree.children.iter() {
+                number(child, node_of, n);
+            }
+        }
+        for root in self.roots.iter() {
+            number(root, &mut node_of, &mut n_nodes);
+        }
+
+        let mut node_factors = vec![Vec::new(); n_nodes];
+        let mut remaining_factors = Vec::new();
+        for factor in graph.shared_factors() {
+            match factor.key_iter().min_by_key(|key| positions[key]) {
+                Some(first) => node_factors[node_of[&first]].push(factor.clone()),
+                None => remaining_factors.push(factor.clone()),
+            }
+        }
+
+        fn build<Graph: FactorGraph>(
+            tree: &SeparatorTree,
+            node_factors: &mut Vec<Vec<std::sync::Arc<Graph::FactorType>>>,
+            n: &mut usize,
+        ) -> Box<Cluster<Graph>> {
+            let mut cluster: Cluster<Graph> = Cluster::new();
+            cluster.frontals = tree.keys.clone();
+            for factor in std::mem::take(&mut node_factors[*n]) {
+                cluster.factors.insert_shared(factor);
+            }
+            *n += 1;
+
+            for child in tree.children.iter() {
+                cluster.add_child(build(child, node_factors, n));
+            }
+
+            Box::new(cluster)
+        }
+
+        let mut tree = ClusterTree::new();
+        let mut n = 0;
+        for root in self.roots.iter() {
+            tree.roots.push(build(root, &mut node_factors, &mut n));
+        }
+
+        JunctionTree {
+            tree,
+            remaining_factors,
+        }
+    }
+}
+
+impl Ordering {
+    /// Nested-dissection ordering, see `NestedDissection`
+    pub fn nested_dissection(index: &VariableIndex) -> Self {
+        NestedDissection::new(index).ordering
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::factor_graph::tests::{eliminate_symbolic, TestFactor};
+    use crate::inference::factor_graph::SimpleFactorGraph;
+    use crate::inference::FactorGraph;
+
+    fn insert(graph: &mut SimpleFactorGraph<TestFactor>, keys: Vec<KeyType>) {
+        graph.insert(TestFactor {
+            inner: format!("{:?}", keys),
+            _keys: keys,
+        });
+    }
+
+    /// A `size` by `size` grid with a factor on every edge and a prior on the corner
+    fn grid(size: u64) -> SimpleFactorGraph<TestFactor> {
+        let mut graph = SimpleFactorGraph::new();
+        insert(&mut graph, vec![0]);
+        for i in 0..size {
+            for j in 0..size {
+                let key = i * size + j;
+                if j + 1 < size {
+                    insert(&mut graph, vec![key, key + 1]);
+                }
+                if i + 1 < size {
+                    insert(&mut graph, vec![key, key + size]);
+                }
+            }
+        }
+        graph
+    }
+
+    fn count_keys(tree: &SeparatorTree) -> usize {
+        tree.keys.len() + tree.children.iter().map(count_keys).sum::<usize>()
+    }
+
+    #[test]
+    fn grid_ordering_has_less_fill() {
+        let graph = grid(16);
+        let index = VariableIndex::new(&graph);
+        let dissection = NestedDissection::with_leaf_size(&index, 8);
+
+        let mut keys = dissection.ordering.to_vec();
+        keys.sort_unstable();
+        assert_eq!(keys, (0..256).collect::<Vec<_>>());
+
+        assert_eq!(dissection.roots.len(), 1);
+        let root = &dissection.roots[0];
+        assert_eq!(count_keys(root), 256);
+        assert_eq!(root.children.len(), 2);
+        assert!(root.keys.len() <= 24);
+
+        let natural = Ordering::natural(&index).fill_statistics(&index).unwrap();
+        let nested = dissection.ordering.fill_statistics(&index).unwrap();
+        assert!(nested.fill_in < natural.fill_in);
+        assert!(nested.flops < natural.flops);
+    }
+
+    #[test]
+    fn components_are_separate_trees() {
+        let mut graph = SimpleFactorGraph::new();
+        for offset in [0, 100] {
+            for key in offset..offset + 20 {
+                insert(&mut graph, vec![key, key + 1]);
+            }
+        }
+        let index = VariableIndex::new(&graph);
+        let dissection = NestedDissection::with_leaf_size(&index, 4);
+
+        assert!(dissection.roots.len() >= 2);
+        assert!(dissection
+            .roots
+            .iter()
+            .all(|root| root.keys.iter().all(|k| *k < 100) || root.keys.iter().all(|k| *k >= 100)));
+        assert_eq!(dissection.roots.iter().map(count_keys).sum::<usize>(), 42);
+    }
+
+    #[test]
+    fn small_graphs_are_a_single_leaf() {
+        let graph = grid(3);
+        let index = VariableIndex::new(&graph);
+        let dissection = NestedDissection::new(&index);
+
+        assert_eq!(
+            dissection.roots,
+            vec![SeparatorTree {
+                keys: (0..9).collect(),
+                children: vec![]
+            }]
+        );
+        assert_eq!(
+            Ordering::nested_dissection(&index).to_vec(),
+            (0..9).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn empty_graphs_have_no_trees() {
+        let graph = SimpleFactorGraph::<TestFactor>::new();
+        let index = VariableIndex::new(&graph);
+        let dissection = NestedDissection::new(&index);
+
+        assert!(dissection.roots.is_empty());
+        assert!(dissection.ordering.to_vec().is_empty());
+    }
+
+    #[test]
+    fn junction_tree_follows_separators() {
+        let graph = grid(8);
+        let index = VariableIndex::new(&graph);
+        let dissection = NestedDissection::with_leaf_size(&index, 4);
+        let junction_tree = dissection.junction_tree(&graph);
+
+        fn check<G: FactorGraph>(cluster: &Cluster<G>, tree: &SeparatorTree) -> usize {
+            assert_eq!(cluster.frontals, tree.keys);
+            assert_eq!(cluster.children.len(), tree.children.len());
+            cluster.factors.size()
+                + cluster
+                    .children
+                    .iter()
+                    .zip(tree.children.iter())
+                    .map(|(c, t)| check(c, t))
+                    .sum::<usize>()
+        }
+        let n_factors: usize = junction_tree
+            .tree
+            .roots
+            .iter()
+            .zip(dissection.roots.iter())
+            .map(|(c, t)| check(c, t))
+            .sum();
+        assert_eq!(n_factors, graph.size());
+        assert!(junction_tree.remaining_factors.is_empty());
+
+        // One clique per separator tree node, with nothing left over
+        fn count_nodes(tree: &SeparatorTree) -> usize {
+            1 + tree.children.iter().map(count_nodes).sum::<usize>()
+        }
+        let (bayes_tree, remaining) = junction_tree.eliminate(eliminate_symbolic).unwrap();
+        assert_eq!(
+            bayes_tree.size(),
+            dissection.roots.iter().map(count_nodes).sum::<usize>()
+        );
+        assert!(remaining.factors.iter().all(|f| f.key_iter().len() == 0));
+    }
+}
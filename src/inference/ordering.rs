@@ -0,0 +1,357 @@
+use crate::inference::factor::KeyType;
+use crate::inference::variable_index::VariableIndex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Deref;
+
+/// An elimination order of keys.
+///
+/// Dereferences to `[KeyType]`, so it can be passed wherever elimination takes an ordering.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Ordering {
+    keys: Vec<KeyType>,
+}
+
+/// Fill-in of the square root information matrix `R` that eliminating in some order creates,
+/// counted in blocks of one key by one key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillStatistics {
+    /// Nonzero blocks of `R`, including the diagonal
+    pub nnz_r: usize,
+    /// Nonzero blocks of `R` that are zero in the upper triangle of `A' A`
+    pub fill_in: usize,
+    /// Largest number of keys in a conditional, i.e. a frontal and its separator
+    pub max_clique: usize,
+    /// Sum of the squared clique sizes, proportional to the work of dense elimination
+    pub flops: usize,
+}
+
+/// Which degree the minimum degree heuristic minimizes
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Degree {
+    /// The number of keys a key would be connected to after elimination
+    Exact,
+    /// The COLAMD bound: the sum over the factors of a key of their other keys
+    Approximate,
+}
+
+/// The structure of a partially eliminated graph, in which every factor is a set of keys and
+/// eliminating a key merges all factors on it into one factor on its separator
+struct QuotientGraph {
+    rows: Vec<Option<BTreeSet<KeyType>>>,
+    key_rows: BTreeMap<KeyType, BTreeSet<usize>>,
+}
+
+impl QuotientGraph {
+    fn new(index: &VariableIndex) -> Self {
+        let mut rows = vec![Some(BTreeSet::new()); index.num_factors()];
+        let mut key_rows = BTreeMap::new();
+
+        for key in index.keys() {
+            for &i in index.factors(key) {
+                rows[i].as_mut().unwrap().insert(key);
+            }
+            key_rows.insert(key, index.factors(key).iter().cloned().collect());
+        }
+
+        QuotientGraph { rows, key_rows }
+    }
+
+    fn degree(&self, key: KeyType, degree: Degree) -> usize {
+        let rows = self.key_rows[&key]
+            .iter()
+            .map(|&i| self.rows[i].as_ref().unwrap());
+
+        match degree {
+            Degree::Exact => {
+                let mut neighbors = BTreeSet::new();
+                for row in rows {
+                    neighbors.extend(row.iter().cloned());
+                }
+                neighbors.len().saturating_sub(1)
+            }
+            Degree::Approximate => rows.map(|row| row.len() - 1).sum(),
+        }
+    }
+
+    /// Eliminates `key`, returning its separator, or `None` if `key` is not left in the graph
+    fn eliminate(&mut self, key: KeyType) -> Option<BTreeSet<KeyType>> {
+        let mut separator = BTreeSet::new();
+        for i in self.key_rows.remove(&key)? {
+            let row = self.rows[i].take().unwrap();
+            for k in row.iter() {
+                if let Some(rows) = self.key_rows.get_mut(k) {
+                    rows.remove(&i);
+                }
+            }
+            separator.extend(row);
+        }
+        separator.remove(&key);
+
+        if !separator.is_empty() {
+            let i = self.rows.len();
+            for k in separator.iter() {
+                self.key_rows.get_mut(k).unwrap().insert(i);
+            }
+            self.rows.push(Some(separator.clone()));
+        }
+
+        Some(separator)
+    }
+}
+
+/// Greedy minimum degree: repeatedly eliminates the key with the smallest degree among those of
+/// the lowest constraint group left, breaking ties by key
+fn minimum_degree(
+    index: &VariableIndex,
+    groups: &BTreeMap<KeyType, usize>,
+    degree: Degree,
+) -> Ordering {
+    let mut graph = QuotientGraph::new(index);
+    let group = |key: KeyType| groups.get(&key).cloned().unwrap_or(0);
+
+    let mut scores = BTreeMap::new();
+    let mut queue = BTreeSet::new();
+    for key in index.keys() {
+        let score = (group(key), graph.degree(key, degree), key);
+        scores.insert(key, score);
+        queue.insert(score);
+    }
+
+    let mut keys = Vec::with_capacity(index.num_keys());
+    while let Some(&score) = queue.iter().next() {
+        let key = score.2;
+        queue.remove(&score);
+        keys.push(key);
+
+        // Only the degrees of the keys in the separator change, and the queue only holds keys
+        // that are left in the graph
+        for k in graph.eliminate(key).unwrap() {
+            let old = scores[&k];
+            let new = (old.0, graph.degree(k, degree), k);
+            queue.remove(&old);
+            queue.insert(new);
+            scores.insert(k, new);
+        }
+    }
+
+    Ordering { keys }
+}
+
+impl Ordering {
+    pub fn new(keys: Vec<KeyType>) -> Self {
+        Ordering { keys }
+    }
+
+    /// All keys in increasing order
+    pub fn natural(index: &VariableIndex) -> Self {
+        Ordering {
+            keys: index.keys().collect(),
+        }
+    }
+
+    /// Column approximate minimum degree, which orders the columns of the Jacobian for a sparse
+    /// QR or Cholesky factor without forming `A' A`
+    pub fn colamd(index: &VariableIndex) -> Self {
+        minimum_degree(index, &BTreeMap::new(), Degree::Approximate)
+    }
+
+    /// Minimum degree on the graph of `A' A`, with exact external degrees
+    pub fn amd(index: &VariableIndex) -> Self {
+        minimum_degree(index, &BTreeMap::new(), Degree::Exact)
+    }
+
+    /// COLAMD in which the keys of a lower group are all eliminated before those of a higher
+    /// group. Keys without a group are in group 0.
+    pub fn colamd_constrained(index: &VariableIndex, groups: &BTreeMap<KeyType, usize>) -> Self {
+        minimum_degree(index, groups, Degree::Approximate)
+    }
+
+    /// COLAMD with `last` eliminated after all other keys, e.g. to keep the latest pose at
+    /// the root of the Bayes tree. If `force_order` is set they are eliminated in the given
+    /// order, and otherwise in the order COLAMD chooses among them.
+    pub fn colamd_constrained_last(
+        index: &VariableIndex,
+        last: &[KeyType],
+        force_order: bool,
+    ) -> Self {
+        let groups = last
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (*key, if force_order { i + 1 } else { 1 }))
+            .collect();
+
+        Ordering::colamd_constrained(index, &groups)
+    }
+
+    pub fn into_vec(self) -> Vec<KeyType> {
+        self.keys
+    }
+
+    /// The position of every key in the ordering
+    pub fn positions(&self) -> BTreeMap<KeyType, usize> {
+        self.keys.iter().enumerate().map(|(i, k)| (*k, i)).collect()
+    }
+
+    /// The fill-in of eliminating the graph of `index` in this order, or `None` if a key of the
+    /// ordering is not in `index` or appears twice
+    pub fn fill_statistics(&self, index: &VariableIndex) -> Option<FillStatistics> {
+        let mut graph = QuotientGraph::new(index);
+
+        // The off-diagonal blocks of A' A, each pair of keys once
+        let mut edges = BTreeSet::new();
+        for key in index.keys() {
+            for &i in index.factors(key) {
+                for &other in graph.rows[i].as_ref().unwrap() {
+                    if key < other {
+                        edges.insert((key, other));
+                    }
+                }
+            }
+        }
+
+        let mut stats = FillStatistics {
+            nnz_r: 0,
+            fill_in: 0,
+            max_clique: 0,
+            flops: 0,
+        };
+        for key in self.keys.iter() {
+            let separator = graph.eliminate(*key)?;
+            let clique = separator.len() + 1;
+
+            stats.nnz_r += clique;
+            stats.fill_in += separator
+                .iter()
+                .filter(|k| !edges.contains(&(*key.min(k), *key.max(k))))
+                .count();
+            stats.max_clique = stats.max_clique.max(clique);
+            stats.flops += clique * clique;
+        }
+
+        Some(stats)
+    }
+}
+
+impl Deref for Ordering {
+    type Target = [KeyType];
+
+    fn deref(&self) -> &[KeyType] {
+        &self.keys
+    }
+}
+
+impl From<Vec<KeyType>> for Ordering {
+    fn from(keys: Vec<KeyType>) -> Self {
+        Ordering { keys }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::factor_graph::tests::{eliminate_symbolic, symbolic_chain, TestFactor};
+    use crate::inference::factor_graph::SimpleFactorGraph;
+    use crate::inference::{EliminateableFactorGraph, FactorGraph};
+
+    /// A hub 0 connected to the leaves 1 to 5, each of which also has a unary factor
+    fn star() -> SimpleFactorGraph<TestFactor> {
+        let mut graph = SimpleFactorGraph::new();
+        for leaf in 1..=5 {
+            for keys in [vec![0, leaf], vec![leaf]] {
+                graph.insert(TestFactor {
+                    inner: format!("{:?}", keys),
+                    _keys: keys,
+                });
+            }
+        }
+        graph
+    }
+
+    #[test]
+    fn natural_ordering() {
+        let index = VariableIndex::new(&star());
+        assert_eq!(&*Ordering::natural(&index), &[0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn minimum_degree_avoids_fill() {
+        let index = VariableIndex::new(&star());
+
+        // Eliminating the hub first connects all leaves
+        let natural = Ordering::natural(&index).fill_statistics(&index).unwrap();
+        assert_eq!(natural.fill_in, 10);
+        assert_eq!(natural.max_clique, 6);
+
+        for ordering in [Ordering::colamd(&index), Ordering::amd(&index)] {
+            assert_eq!(ordering.len(), 6);
+            // The hub goes once at most one leaf is left
+            assert!(!ordering[..4].contains(&0));
+
+            let stats = ordering.fill_statistics(&index).unwrap();
+            assert_eq!(stats.fill_in, 0);
+            assert_eq!(stats.nnz_r, 11);
+            assert_eq!(stats.max_clique, 2);
+            assert!(stats.flops < natural.flops);
+        }
+    }
+
+    #[test]
+    fn chain_has_no_fill() {
+        let graph = symbolic_chain();
+        let index = VariableIndex::new(&graph);
+
+        let ordering = Ordering::colamd(&index);
+        assert_eq!(ordering.fill_statistics(&index).unwrap().fill_in, 0);
+
+        // Orderings are used directly for elimination
+        let (bayes_net, _) = graph
+            .eliminate_sequential(&ordering, eliminate_symbolic)
+            .unwrap();
+        assert_eq!(bayes_net.len(), 4);
+        assert!(bayes_net.iter().all(|c| c.parents.len() <= 2));
+    }
+
+    #[test]
+    fn fill_statistics_reject_bad_orderings() {
+        let index = VariableIndex::new(&star());
+
+        assert!(Ordering::new(vec![1, 2, 7])
+            .fill_statistics(&index)
+            .is_none());
+        assert!(Ordering::new(vec![1, 2, 1])
+            .fill_statistics(&index)
+            .is_none());
+        assert!(Ordering::new(vec![1, 2]).fill_statistics(&index).is_some());
+    }
+
+    #[test]
+    fn constrained_orderings() {
+        let index = VariableIndex::new(&star());
+
+        let ordering = Ordering::colamd_constrained_last(&index, &[3], false);
+        assert_eq!(ordering.last(), Some(&3));
+        assert_eq!(ordering.len(), 6);
+
+        let ordering = Ordering::colamd_constrained_last(&index, &[2, 0, 1], true);
+        assert_eq!(&ordering[3..], &[2, 0, 1]);
+
+        let mut groups = BTreeMap::new();
+        groups.insert(4, 2);
+        groups.insert(5, 1);
+        let ordering = Ordering::colamd_constrained(&index, &groups);
+        assert_eq!(&ordering[4..], &[5, 4]);
+        assert_eq!(ordering.positions()[&4], 5);
+    }
+
+    #[test]
+    fn removed_factors_do_not_count() {
+        let graph = star();
+        let mut index = VariableIndex::new(&graph);
+        assert_eq!(graph.size(), 10);
+
+        // Without the factors between the hub and leaves 1 and 2 those leaves are isolated
+        index.remove(&[0, 2]);
+        let stats = Ordering::natural(&index).fill_statistics(&index).unwrap();
+        assert_eq!(stats.fill_in, 3);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::inference::factor::KeyType;
+use crate::inference::{Factor, FactorGraph, FactorKeys};
+use std::collections::BTreeMap;
+
+/// For every key, the indices of the factors of a graph that involve it, and its dimension if
+/// the factors know it
+#[derive(Debug, Clone, Default)]
+pub struct VariableIndex {
+    index: BTreeMap<KeyType, Vec<usize>>,
+    dims: BTreeMap<KeyType, usize>,
+    n_factors: usize,
+}
+
+impl VariableIndex {
+    pub fn new<Graph: FactorGraph>(graph: &Graph) -> Self {
+        let mut index = VariableIndex::default();
+        index.augment(graph);
+        index
+    }
+
+    /// Adds the factors of `new_factors`, which get the indices after those already indexed,
+    /// e.g. because they were appended to the indexed graph
+    pub fn augment<Graph: FactorGraph>(&mut self, new_factors: &Graph) {
+        for factor in new_factors.shared_factors() {
+            let i = self.n_factors;
+
+            for (j, key) in factor.key_iter().enumerate() {
+                self.index.entry(key).or_default().push(i);
+
+                if let Some(dim) = factor.dim_at(j) {
+                    let d = self.dims.entry(key).or_insert(dim);
+                    assert_eq!(*d, dim, "Key {} has inconsistent dimensions", key);
+                }
+            }
+
+            self.n_factors += 1;
+        }
+    }
+
+    /// Removes the factors with the given indices from the lists of all keys.
+    ///
+    /// The indices of the other factors are unchanged, and keys left without factors are kept
+    /// until `remove_unused_keys`.
+    pub fn remove(&mut self, factor_indices: &[usize]) {
+        for factors in self.index.values_mut() {
+            factors.retain(|i| !factor_indices.contains(i));
+        }
+    }
+
+    /// Drops the keys that no factor involves anymore
+    pub fn remove_unused_keys(&mut self) {
+        let unused: Vec<KeyType> = self
+            .index
+            .iter()
+            .filter(|(_, factors)| factors.is_empty())
+            .map(|(key, _)| *key)
+            .collect();
+
+        for key in unused {
+            self.index.remove(&key);
+            self.dims.remove(&key);
+        }
+    }
+
+    /// Indices of the factors involving `key`, in increasing order
+    pub fn factors(&self, key: KeyType) -> &[usize] {
+        self.index
+            .get(&key)
+            .unwrap_or_else(|| panic!("Key {} is not in the variable index", key))
+    }
+
+    /// Dimension of `key`, if its factors know it
+    pub fn dim(&self, key: KeyType) -> Option<usize> {
+        self.dims.get(&key).cloned()
+    }
+
+    /// Dimensions of all keys whose factors know them
+    pub fn dims(&self) -> &BTreeMap<KeyType, usize> {
+        &self.dims
+    }
+
+    pub fn contains(&self, key: KeyType) -> bool {
+        self.index.contains_key(&key)
+    }
+
+    /// All keys in increasing order
+    pub fn keys(&self) -> impl Iterator<Item = KeyType> + '_ {
+        self.index.keys().cloned()
+    }
+
+    pub fn num_keys(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Number of factors indexed so far, including removed ones
+    pub fn num_factors(&self) -> usize {
+        self.n_factors
+    }
+
+    /// Total number of (key, factor) pairs
+    pub fn num_entries(&self) -> usize {
+        self.index.values().map(|factors| factors.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::factor_graph::tests::{symbolic_chain, TestFactor};
+    use crate::inference::factor_graph::SimpleFactorGraph;
+    use crate::linear::jacobian::JacobianFactor;
+    use crate::linear::GaussianFactorGraph;
+    use nalgebra::{DMatrix, DVector};
+
+    #[test]
+    fn factors_per_key() {
+        let index = VariableIndex::new(&symbolic_chain());
+
+        assert_eq!((index.num_keys(), index.num_factors()), (4, 5));
+        assert_eq!(index.factors(0), &[0, 3]);
+        assert_eq!(index.factors(2), &[1, 2, 3]);
+        assert_eq!(index.keys().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert!(!index.contains(4));
+    }
+
+    #[test]
+    fn augment_and_remove() {
+        let mut index = VariableIndex::new(&symbolic_chain());
+        assert_eq!(index.num_entries(), 9);
+
+        let mut new_factors = SimpleFactorGraph::new();
+        new_factors.insert(TestFactor {
+            inner: "new".into(),
+            _keys: vec![3, 4],
+        });
+        index.augment(&new_factors);
+        assert_eq!(index.num_factors(), 6);
+        assert_eq!(index.factors(3), &[2, 4, 5]);
+        assert_eq!(index.factors(4), &[5]);
+
+        index.remove(&[5, 0]);
+        assert_eq!(index.factors(0), &[3]);
+        assert_eq!(index.factors(4), &[] as &[usize]);
+        assert_eq!(index.num_keys(), 5);
+
+        index.remove_unused_keys();
+        assert!(!index.contains(4));
+        assert_eq!(index.num_keys(), 4);
+    }
+
+    #[test]
+    fn dimensions_of_gaussian_factors() {
+        let mut graph = GaussianFactorGraph::new();
+        graph.add(JacobianFactor::new(
+            vec![(1, DMatrix::zeros(2, 3)), (2, DMatrix::zeros(2, 1))],
+            DVector::zeros(2),
+            None,
+        ));
+
+        let index = VariableIndex::new(&graph);
+        assert_eq!(index.dim(1), Some(3));
+        assert_eq!(index.dim(2), Some(1));
+        assert_eq!(index.dim(3), None);
+
+        // Symbolic factors have no dimensions
+        assert!(VariableIndex::new(&symbolic_chain()).dims().is_empty());
+    }
+}
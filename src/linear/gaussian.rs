@@ -10,7 +10,7 @@ impl Factor for GaussianFactor {
         todo!()
     }
 
-    fn key_at(&self, index: usize) -> Result<KeyType, std::io::Error> {
+    fn key_at(&self, _index: usize) -> Result<KeyType, std::io::Error> {
         todo!()
     }
 }
@@ -0,0 +1,131 @@
+use crate::inference::BayesNet;
+use crate::linear::gaussian_conditional::GaussianConditional;
+use crate::linear::vector_values::VectorValues;
+use rand::Rng;
+
+/// A Bayes net of Gaussian conditionals, e.g. the result of eliminating a `GaussianFactorGraph`
+pub type GaussianBayesNet = BayesNet<GaussianConditional>;
+
+impl BayesNet<GaussianConditional> {
+    /// Back-substitution in reverse elimination order, starting from `given`, which must hold
+    /// the values of all parents that are not frontals of the net. The result contains `given`
+    /// and all frontals.
+    pub fn optimize_given(&self, given: &VectorValues) -> VectorValues {
+        let mut solution = given.clone();
+        for conditional in self.iter().rev() {
+            for (key, value) in conditional.solve(&solution).iter() {
+                solution.insert(key, value.clone());
+            }
+        }
+        solution
+    }
+
+    /// The most probable values of all frontals by back-substitution
+    pub fn optimize(&self) -> VectorValues {
+        self.optimize_given(&VectorValues::new())
+    }
+
+    /// Solves `R' x = gx` for the upper triangular `R` of the whole net, by transposed
+    /// back-substitution in elimination order
+    pub fn back_substitute_transpose(&self, gx: &VectorValues) -> VectorValues {
+        let mut gy = gx.clone();
+        for conditional in self.iter() {
+            conditional.solve_transpose(&mut gy);
+        }
+        gy
+    }
+
+    /// A sample of all frontals, by ancestral sampling in reverse elimination order
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> VectorValues {
+        let mut sample = VectorValues::new();
+        for conditional in self.iter().rev() {
+            for (key, value) in conditional.sample(&sample, rng).iter() {
+                sample.insert(key, value.clone());
+            }
+        }
+        sample
+    }
+
+    /// Sum of the errors of all conditionals
+    pub fn error(&self, x: &VectorValues) -> f64 {
+        self.iter().map(|c| c.error(x)).sum()
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use na::{DMatrix, DVector};
+    use nalgebra as na;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// p(x0 | x1) p(x1), whose stacked R is [2 1 1; 0 3 0; 0 0 4]
+    fn example() -> GaussianBayesNet {
+        let mut bn = GaussianBayesNet::new();
+        bn.push(GaussianConditional::from_blocks(
+            vec![(0, DMatrix::from_row_slice(2, 2, &[2.0, 1.0, 0.0, 3.0]))],
+            vec![(1, DMatrix::from_row_slice(2, 1, &[1.0, 0.0]))],
+            DVector::from_vec(vec![5.0, 6.0]),
+            None,
+        ));
+        bn.push(GaussianConditional::from_blocks(
+            vec![(1, DMatrix::from_element(1, 1, 4.0))],
+            vec![],
+            DVector::from_vec(vec![4.0]),
+            None,
+        ));
+        bn
+    }
+
+    fn stacked_r() -> DMatrix<f64> {
+        DMatrix::from_row_slice(3, 3, &[2.0, 1.0, 1.0, 0.0, 3.0, 0.0, 0.0, 0.0, 4.0])
+    }
+
+    #[test]
+    fn back_substitution() {
+        let bn = example();
+        assert_eq!(bn.len(), 2);
+
+        let x = bn.optimize();
+        let expected = stacked_r()
+            .solve_upper_triangular(&DVector::from_vec(vec![5.0, 6.0, 4.0]))
+            .unwrap();
+        assert_relative_eq!(x.vector(&[0, 1]), expected);
+        assert_relative_eq!(bn.error(&x), 0.0);
+
+        // With x1 given, only x0 is solved for
+        let mut given = VectorValues::new();
+        given.insert(1, DVector::from_vec(vec![3.0]));
+        let mut partial = GaussianBayesNet::new();
+        partial.push_shared(bn.conditionals[0].clone());
+        let y = partial.optimize_given(&given);
+        assert_relative_eq!(y.at(1)[0], 3.0);
+        assert_relative_eq!(y.at(0).clone(), DVector::from_vec(vec![0.0, 2.0]));
+    }
+
+    #[test]
+    fn transposed_back_substitution() {
+        let bn = example();
+        let mut gx = VectorValues::new();
+        gx.insert(0, DVector::from_vec(vec![1.0, 2.0]));
+        gx.insert(1, DVector::from_vec(vec![3.0]));
+
+        let gy = bn.back_substitute_transpose(&gx);
+        let expected = stacked_r()
+            .tr_solve_upper_triangular(&gx.vector(&[0, 1]))
+            .unwrap();
+        assert_relative_eq!(gy.vector(&[0, 1]), expected);
+    }
+
+    #[test]
+    fn ancestral_sampling() {
+        let bn = example();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let sample = bn.sample(&mut rng);
+        assert_eq!(sample.keys().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(sample.dim(), 3);
+    }
+}
@@ -0,0 +1,39 @@
+use crate::inference::{BayesTree, BayesTreeClique};
+use crate::linear::gaussian_conditional::GaussianConditional;
+use crate::linear::vector_values::VectorValues;
+
+/// A Bayes tree of Gaussian conditionals, the result of multifrontal elimination of a
+/// `GaussianFactorGraph`
+pub type GaussianBayesTree = BayesTree<GaussianConditional>;
+
+/// Solves the frontals of `clique` given its separator in `solution`, then its subtree.
+///
+/// Every clique is solved before its children, walking the tree with an explicit stack.
+fn back_substitute(clique: &BayesTreeClique<GaussianConditional>, solution: &mut VectorValues) {
+    let mut stack = vec![clique];
+    while let Some(clique) = stack.pop() {
+        for (key, value) in clique.conditional.solve(solution).iter() {
+            solution.insert(key, value.clone());
+        }
+
+        // Reversed, so that the children are solved in order
+        stack.extend(clique.children.iter().rev());
+    }
+}
+
+impl BayesTree<GaussianConditional> {
+    /// Back-substitution from the roots down, starting from `given`, which must hold the
+    /// values of the separators of the roots. The result contains `given` and all frontals.
+    pub fn optimize_given(&self, given: &VectorValues) -> VectorValues {
+        let mut solution = given.clone();
+        for root in self.roots.iter() {
+            back_substitute(root, &mut solution);
+        }
+        solution
+    }
+
+    /// The most probable values of all frontals by recursive back-substitution
+    pub fn optimize(&self) -> VectorValues {
+        self.optimize_given(&VectorValues::new())
+    }
+}
@@ -0,0 +1,352 @@
+use crate::core::matrix::VerticalBlockMatrix;
+use crate::inference::factor::{Factor, KeyType};
+use crate::inference::Conditional;
+use crate::linear::jacobian::JacobianFactor;
+use crate::linear::noise_model::{Diagonal, NoiseModel};
+use crate::linear::vector_values::VectorValues;
+use nalgebra as na;
+use rand::Rng;
+use rand_distr::StandardNormal;
+use std::io::ErrorKind;
+
+/// A Gaussian density on the frontal variables `x_f` given the parents `x_p`, of the form
+/// `R x_f + S x_p = d` with diagonal noise `sigmas` on the right hand side.
+///
+/// `R` is upper triangular, and [R S d] is stored as a `VerticalBlockMatrix` with one block
+/// per frontal key, then one per parent key, then the RHS.
+#[derive(Debug)]
+#[allow(non_snake_case)]
+pub struct GaussianConditional {
+    keys: Vec<KeyType>,
+    n_frontals: usize,
+    Rd: VerticalBlockMatrix<f64>,
+    model: Option<Diagonal<na::Dynamic>>,
+}
+
+#[allow(non_snake_case)]
+impl GaussianConditional {
+    /// A conditional from [R S d], whose blocks belong to `keys`, the first `n_frontals` of
+    /// which are the frontals
+    pub fn new(
+        keys: Vec<KeyType>,
+        n_frontals: usize,
+        Rd: VerticalBlockMatrix<f64>,
+        model: Option<Diagonal<na::Dynamic>>,
+    ) -> Self {
+        assert!(n_frontals <= keys.len(), "More frontals than keys");
+        assert_eq!(
+            Rd.num_blocks(),
+            keys.len() + 1,
+            "Need one block per key plus the RHS"
+        );
+        assert_eq!(Rd.offset(n_frontals), Rd.rows(), "R must be square");
+        if let Some(model) = &model {
+            assert_eq!(
+                model.dim(),
+                Rd.rows(),
+                "Noise model has the wrong dimension"
+            );
+        }
+
+        GaussianConditional {
+            keys,
+            n_frontals,
+            Rd,
+            model,
+        }
+    }
+
+    /// A conditional from the blocks of `R` for every frontal key, of `S` for every parent key
+    /// and the RHS `d`
+    pub fn from_blocks(
+        frontals: Vec<(KeyType, na::DMatrix<f64>)>,
+        parents: Vec<(KeyType, na::DMatrix<f64>)>,
+        d: na::DVector<f64>,
+        model: Option<Diagonal<na::Dynamic>>,
+    ) -> Self {
+        let n_frontals = frontals.len();
+        let terms: Vec<_> = frontals.into_iter().chain(parents).collect();
+
+        let dims: Vec<usize> = terms.iter().map(|(_, A)| A.ncols()).collect();
+        let mut Rd = VerticalBlockMatrix::from_dimensions(&dims, d.len(), true);
+        for (j, (key, A)) in terms.iter().enumerate() {
+            assert_eq!(
+                A.nrows(),
+                d.len(),
+                "Block of key {} has the wrong rows",
+                key
+            );
+            Rd.block_mut(j).copy_from(A);
+        }
+        Rd.block_mut(dims.len()).copy_from(&d);
+
+        let keys = terms.into_iter().map(|(key, _)| key).collect();
+
+        GaussianConditional::new(keys, n_frontals, Rd, model)
+    }
+
+    pub fn keys(&self) -> &[KeyType] {
+        &self.keys
+    }
+
+    pub fn frontal_keys(&self) -> &[KeyType] {
+        &self.keys[..self.n_frontals]
+    }
+
+    pub fn parent_keys(&self) -> &[KeyType] {
+        &self.keys[self.n_frontals..]
+    }
+
+    /// Total dimension of the frontals, i.e. the size of `R`
+    pub fn rows(&self) -> usize {
+        self.Rd.rows()
+    }
+
+    /// The upper triangular `R`
+    pub fn r(&self) -> na::DMatrixSlice<'_, f64> {
+        self.Rd.range(0, self.n_frontals)
+    }
+
+    /// `S`, with the blocks of the parents side by side
+    pub fn s(&self) -> na::DMatrixSlice<'_, f64> {
+        self.Rd.range(self.n_frontals, self.keys.len())
+    }
+
+    pub fn d(&self) -> na::DVectorSlice<'_, f64> {
+        self.Rd.column(self.Rd.offset(self.keys.len()))
+    }
+
+    pub fn model(&self) -> Option<&Diagonal<na::Dynamic>> {
+        self.model.as_ref()
+    }
+
+    /// Splits the frontal solution into one vector per frontal key
+    fn split_frontals(&self, xf: &na::DVector<f64>) -> VectorValues {
+        let mut values = VectorValues::new();
+        for (j, key) in self.frontal_keys().iter().enumerate() {
+            values.insert(
+                *key,
+                xf.rows(self.Rd.offset(j), self.Rd.dim(j)).into_owned(),
+            );
+        }
+        values
+    }
+
+    /// `d - S x_p`
+    fn rhs(&self, parents: &VectorValues) -> na::DVector<f64> {
+        let mut rhs = self.d().into_owned();
+        if !self.parent_keys().is_empty() {
+            rhs -= self.s() * parents.vector(self.parent_keys());
+        }
+        rhs
+    }
+
+    fn solve_r(&self, rhs: &na::DVector<f64>) -> na::DVector<f64> {
+        self.r()
+            .solve_upper_triangular(rhs)
+            .expect("R of a conditional must be invertible")
+    }
+
+    /// The frontals `x_f = R^-1 (d - S x_p)` given the values of all parents
+    pub fn solve(&self, parents: &VectorValues) -> VectorValues {
+        self.split_frontals(&self.solve_r(&self.rhs(parents)))
+    }
+
+    /// One step of transposed back-substitution, in place on `gy`: the frontal entries become
+    /// `R'^-1 gy_f`, and `S' R'^-1 gy_f` is subtracted from the parent entries.
+    ///
+    /// Applied to a Bayes net in elimination order this solves `R' x = gy` for the whole net.
+    pub fn solve_transpose(&self, gy: &mut VectorValues) {
+        let frontals = gy.vector(self.frontal_keys());
+        let xf = self
+            .r()
+            .tr_solve_upper_triangular(&frontals)
+            .expect("R of a conditional must be invertible");
+
+        let mut row = 0;
+        for j in 0..self.keys.len() {
+            let (key, dim) = (self.keys[j], self.Rd.dim(j));
+            if j < self.n_frontals {
+                gy.at_mut(key).copy_from(&xf.rows(row, dim));
+                row += dim;
+            } else {
+                let S_j = self.Rd.block(j);
+                *gy.at_mut(key) -= S_j.tr_mul(&xf);
+            }
+        }
+    }
+
+    /// A sample of the frontals given the values of all parents
+    pub fn sample<R: Rng + ?Sized>(&self, parents: &VectorValues, rng: &mut R) -> VectorValues {
+        let mut rhs = self.rhs(parents);
+
+        let noise = na::DVector::<f64>::from_fn(self.rows(), |_, _| rng.sample(StandardNormal));
+        match &self.model {
+            Some(model) => rhs += noise.component_mul(&model.sigmas()),
+            None => rhs += noise,
+        }
+
+        self.split_frontals(&self.solve_r(&rhs))
+    }
+
+    /// The whitened residual `(R x_f + S x_p - d) / sigmas`
+    pub fn error_vector(&self, x: &VectorValues) -> na::DVector<f64> {
+        let e = self.r() * x.vector(self.frontal_keys()) - self.rhs(x);
+        match &self.model {
+            Some(model) => model.whiten(&e),
+            None => e,
+        }
+    }
+
+    /// `0.5 * || (R x_f + S x_p - d) / sigmas ||^2`
+    pub fn error(&self, x: &VectorValues) -> f64 {
+        0.5 * self.error_vector(x).norm_squared()
+    }
+
+    /// The factor `0.5 * || (R x_f + S x_p - d) / sigmas ||^2` on frontals and parents
+    pub fn to_jacobian(&self) -> JacobianFactor {
+        let terms = self
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(j, key)| (*key, self.Rd.block(j).into_owned()))
+            .collect();
+
+        JacobianFactor::new(terms, self.d().into_owned(), self.model.clone())
+    }
+}
+
+impl Factor for GaussianConditional {
+    fn num_keys(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn key_at(&self, index: usize) -> Result<KeyType, std::io::Error> {
+        self.keys
+            .get(index)
+            .cloned()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Range"))
+    }
+
+    fn dim_at(&self, index: usize) -> Option<usize> {
+        if index < self.keys.len() {
+            Some(self.Rd.dim(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl Conditional<JacobianFactor> for GaussianConditional {
+    fn num_frontals(&self) -> usize {
+        self.n_frontals
+    }
+
+    fn num_parents(&self) -> usize {
+        self.keys.len() - self.n_frontals
+    }
+
+    fn frontals<'a>(&'a self) -> Box<dyn Iterator<Item = &'a u64> + 'a> {
+        Box::new(self.frontal_keys().iter())
+    }
+
+    fn parents<'a>(&'a self) -> Box<dyn Iterator<Item = &'a u64> + 'a> {
+        Box::new(self.parent_keys().iter())
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use na::{DMatrix, DVector};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// p(x0 | x1) with x0 of dimension 2 and x1 of dimension 1
+    fn example() -> GaussianConditional {
+        GaussianConditional::from_blocks(
+            vec![(0, DMatrix::from_row_slice(2, 2, &[2.0, 1.0, 0.0, 3.0]))],
+            vec![(1, DMatrix::from_row_slice(2, 1, &[1.0, 0.0]))],
+            DVector::from_vec(vec![5.0, 6.0]),
+            Some(Diagonal::from_sigmas(&DVector::from_vec(vec![0.5, 0.5]))),
+        )
+    }
+
+    fn parents() -> VectorValues {
+        let mut x = VectorValues::new();
+        x.insert(1, DVector::from_vec(vec![1.0]));
+        x
+    }
+
+    #[test]
+    fn keys_and_blocks() {
+        let c = example();
+
+        assert_eq!((c.num_frontals(), c.num_parents()), (1, 1));
+        assert_eq!(c.frontals().cloned().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(c.parents().cloned().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(c.key_at(1).unwrap(), 1);
+        assert_eq!(c.rows(), 2);
+        assert_relative_eq!(
+            c.s().into_owned(),
+            DMatrix::from_row_slice(2, 1, &[1.0, 0.0])
+        );
+        assert_relative_eq!(c.d().into_owned(), DVector::from_vec(vec![5.0, 6.0]));
+    }
+
+    #[test]
+    fn solve_given_parents() {
+        let c = example();
+
+        // R x0 = d - S x1 = [4, 6]
+        let x = c.solve(&parents());
+        assert_relative_eq!(x.at(0).clone(), DVector::from_vec(vec![1.0, 2.0]));
+
+        let mut all = parents();
+        all.insert(0, x.at(0).clone());
+        assert_relative_eq!(c.error(&all), 0.0);
+    }
+
+    #[test]
+    fn solve_transpose_in_place() {
+        let c = example();
+        let mut gy = VectorValues::new();
+        gy.insert(0, DVector::from_vec(vec![2.0, 4.0]));
+        gy.insert(1, DVector::from_vec(vec![1.0]));
+
+        c.solve_transpose(&mut gy);
+
+        // R' x0 = [2, 4] gives x0 = [1, 1], and x1 -= S' x0
+        assert_relative_eq!(gy.at(0).clone(), DVector::from_vec(vec![1.0, 1.0]));
+        assert_relative_eq!(gy.at(1).clone(), DVector::from_vec(vec![0.0]));
+    }
+
+    #[test]
+    fn conversion_to_jacobian_keeps_the_error() {
+        let c = example();
+        let f = c.to_jacobian();
+        assert_eq!(f.keys(), c.keys());
+
+        let mut x = parents();
+        x.insert(0, DVector::from_vec(vec![0.3, -0.7]));
+        let values = [x.at(0).clone(), x.at(1).clone()];
+
+        assert_relative_eq!(f.error(&values), c.error(&x), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn samples_are_centered_on_the_solution() {
+        let c = example();
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let n = 2000;
+        let mut mean = DVector::zeros(2);
+        for _ in 0..n {
+            mean += c.sample(&parents(), &mut rng).at(0);
+        }
+        mean /= n as f64;
+
+        assert_relative_eq!(mean, DVector::from_vec(vec![1.0, 2.0]), epsilon = 0.05);
+    }
+}
@@ -1,13 +1,659 @@
-//use crate::inference::{Factor, FactorGraph};
-//use std::sync::Arc;
-//use crate::linear::GaussianLikeFactor;
-//
-//#[derive(Debug)]
-//pub struct GaussianFactorGraph
-//{
-//    factors: Vec<Arc<GaussianLikeFactorFactor>>,
-//}
-//
-//impl FactorGraph<dyn GaussianLikeFactor> for GaussianFactorGraph {
-//
-//}
+use crate::inference::factor::KeyType;
+use crate::inference::factor_graph::{EliminateableFactorGraph, SimpleFactorGraph};
+use crate::inference::{FactorKeys, Ordering, VariableIndex};
+use crate::linear::gaussian_conditional::GaussianConditional;
+use crate::linear::gaussian_like::GaussianLikeFactor;
+use crate::linear::jacobian::eliminate_qr;
+use crate::linear::vector_values::VectorValues;
+use nalgebra as na;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// A factor graph of linear factors, which represents the least-squares problem
+/// `min_x sum_i 0.5 * || A_i x - b_i ||^2`
+pub type GaussianFactorGraph = SimpleFactorGraph<dyn GaussianLikeFactor>;
+
+/// The keys of `factor` with their dimensions, in the order of its blocks
+fn keys_and_dims(factor: &dyn GaussianLikeFactor) -> Vec<(KeyType, usize)> {
+    factor
+        .key_iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let dim = factor
+                .dim_at(i)
+                .expect("Gaussian factors know the dimensions of their keys");
+            (key, dim)
+        })
+        .collect()
+}
+
+/// `frontals` followed by the other keys of `graph` in increasing order, which is the order of
+/// the blocks when eliminating `frontals`, and the dimensions of these keys
+pub(crate) fn frontals_first(
+    graph: &GaussianFactorGraph,
+    frontals: &[KeyType],
+) -> (Vec<KeyType>, Vec<usize>) {
+    let dims = graph.dims();
+
+    let mut keys = frontals.to_vec();
+    keys.extend(dims.keys().filter(|key| !frontals.contains(key)));
+    let key_dims = keys
+        .iter()
+        .map(|key| {
+            *dims
+                .get(key)
+                .unwrap_or_else(|| panic!("Key {} is not in the graph", key))
+        })
+        .collect();
+
+    (keys, key_dims)
+}
+
+/// The values of `keys` stacked into one vector
+fn stack(x: &VectorValues, keys: &[(KeyType, usize)]) -> na::DVector<f64> {
+    let mut v = na::DVector::zeros(keys.iter().map(|(_, dim)| dim).sum());
+
+    let mut row = 0;
+    for (key, dim) in keys {
+        let value = x.at(*key);
+        assert_eq!(value.len(), *dim, "Key {} has the wrong dimension", key);
+        v.rows_mut(row, *dim).copy_from(value);
+        row += dim;
+    }
+
+    v
+}
+
+#[allow(non_snake_case)]
+impl SimpleFactorGraph<dyn GaussianLikeFactor> {
+    /// Adds a factor to the graph
+    pub fn add<F: GaussianLikeFactor + 'static>(&mut self, factor: F) {
+        self.factors.push(Arc::new(factor));
+    }
+
+    /// The dimension of every key in the graph
+    pub fn dims(&self) -> BTreeMap<KeyType, usize> {
+        let mut dims = BTreeMap::new();
+        for factor in self.factors.iter() {
+            for (key, dim) in keys_and_dims(factor.as_ref()) {
+                let d = dims.entry(key).or_insert(dim);
+                assert_eq!(*d, dim, "Key {} has inconsistent dimensions", key);
+            }
+        }
+        dims
+    }
+
+    /// All keys of the graph in increasing order
+    pub fn keys(&self) -> Vec<KeyType> {
+        self.dims().keys().cloned().collect()
+    }
+
+    /// The column offset of every key when its blocks are laid out in `ordering`, and the
+    /// total number of columns
+    fn column_offsets(&self, ordering: &[KeyType]) -> (BTreeMap<KeyType, usize>, usize) {
+        let dims = self.dims();
+        assert_eq!(
+            ordering.len(),
+            dims.len(),
+            "Ordering must contain every key of the graph exactly once"
+        );
+
+        let mut offsets = BTreeMap::new();
+        let mut col = 0;
+        for key in ordering {
+            let dim = dims
+                .get(key)
+                .unwrap_or_else(|| panic!("Key {} is not in the graph", key));
+            assert!(
+                offsets.insert(*key, col).is_none(),
+                "Key {} appears twice in the ordering",
+                key
+            );
+            col += dim;
+        }
+
+        (offsets, col)
+    }
+
+    /// The whitened [A b] of the whole graph, with the factors stacked vertically and the
+    /// blocks of the keys in the order of `ordering`
+    pub fn augmented_jacobian_ordered(&self, ordering: &[KeyType]) -> na::DMatrix<f64> {
+        let (offsets, n) = self.column_offsets(ordering);
+
+        let blocks: Vec<_> = self
+            .factors
+            .iter()
+            .map(|f| (keys_and_dims(f.as_ref()), f.augmented_jacobian()))
+            .collect();
+
+        let rows = blocks.iter().map(|(_, Ab)| Ab.nrows()).sum();
+        let mut Ab = na::DMatrix::zeros(rows, n + 1);
+
+        let mut row = 0;
+        for (keys, Ab_f) in blocks.iter() {
+            let mut col = 0;
+            for (key, dim) in keys {
+                Ab.slice_mut((row, offsets[key]), (Ab_f.nrows(), *dim))
+                    .copy_from(&Ab_f.columns(col, *dim));
+                col += dim;
+            }
+            Ab.slice_mut((row, n), (Ab_f.nrows(), 1))
+                .copy_from(&Ab_f.column(col));
+            row += Ab_f.nrows();
+        }
+
+        Ab
+    }
+
+    /// The whitened [A b] of the whole graph, with the keys in increasing order
+    pub fn augmented_jacobian(&self) -> na::DMatrix<f64> {
+        self.augmented_jacobian_ordered(&self.keys())
+    }
+
+    /// The whitened A and b of the whole graph, with the keys in increasing order
+    pub fn jacobian(&self) -> (na::DMatrix<f64>, na::DVector<f64>) {
+        let Ab = self.augmented_jacobian();
+        let n = Ab.ncols() - 1;
+
+        (Ab.columns(0, n).into_owned(), Ab.column(n).into_owned())
+    }
+
+    /// The augmented information matrix [A b]' [A b] of the whole graph, with the blocks of
+    /// the keys in the order of `ordering`
+    pub fn augmented_hessian_ordered(&self, ordering: &[KeyType]) -> na::DMatrix<f64> {
+        let (offsets, n) = self.column_offsets(ordering);
+
+        let mut info = na::DMatrix::zeros(n + 1, n + 1);
+        for factor in self.factors.iter() {
+            let keys = keys_and_dims(factor.as_ref());
+            let info_f = factor.augmented_information();
+
+            // Where each block of the factor, including its last row and column, goes
+            let mut scatter: Vec<(usize, usize, usize)> = Vec::new();
+            let mut col = 0;
+            for (key, dim) in keys.iter() {
+                scatter.push((col, offsets[key], *dim));
+                col += dim;
+            }
+            scatter.push((col, n, 1));
+
+            for &(ci, oi, di) in scatter.iter() {
+                for &(cj, oj, dj) in scatter.iter() {
+                    let mut block = info.slice_mut((oi, oj), (di, dj));
+                    block += info_f.slice((ci, cj), (di, dj));
+                }
+            }
+        }
+
+        info
+    }
+
+    /// The augmented information matrix [A b]' [A b] of the whole graph, with the keys in
+    /// increasing order
+    pub fn augmented_hessian(&self) -> na::DMatrix<f64> {
+        self.augmented_hessian_ordered(&self.keys())
+    }
+
+    /// The information matrix A' A and the information vector A' b of the whole graph, with
+    /// the keys in increasing order
+    pub fn hessian(&self) -> (na::DMatrix<f64>, na::DVector<f64>) {
+        let info = self.augmented_hessian();
+        let n = info.ncols() - 1;
+
+        (
+            info.slice((0, 0), (n, n)).into_owned(),
+            info.slice((0, n), (n, 1)).column(0).into_owned(),
+        )
+    }
+
+    /// `sum_i 0.5 * || A_i x - b_i ||^2`
+    pub fn error(&self, x: &VectorValues) -> f64 {
+        self.factors
+            .iter()
+            .map(|factor| {
+                let keys = keys_and_dims(factor.as_ref());
+                let xf = stack(x, &keys);
+                let Ab = factor.augmented_jacobian();
+
+                let n = xf.len();
+                let e = Ab.columns(0, n) * xf - Ab.column(n);
+                0.5 * e.norm_squared()
+            })
+            .sum()
+    }
+
+    /// The gradient `A' (A x - b)` of the error at `x`
+    pub fn gradient(&self, x: &VectorValues) -> VectorValues {
+        let mut g = VectorValues::new();
+        for (key, dim) in self.dims() {
+            g.insert(key, na::DVector::zeros(dim));
+        }
+
+        for factor in self.factors.iter() {
+            let keys = keys_and_dims(factor.as_ref());
+            let (A, b) = factor.jacobian();
+            let g_f = A.tr_mul(&(&A * stack(x, &keys) - b));
+
+            let mut row = 0;
+            for (key, dim) in keys {
+                *g.at_mut(key) += g_f.rows(row, dim);
+                row += dim;
+            }
+        }
+
+        g
+    }
+
+    /// The least-squares solution, with the variables eliminated multifrontally by QR in the
+    /// order of `ordering` and the resulting Bayes tree solved by back-substitution.
+    ///
+    /// Returns `None` if the system is underdetermined or `ordering` does not hold every key of
+    /// the graph exactly once.
+    pub fn optimize_ordered(&self, ordering: &[KeyType]) -> Option<VectorValues> {
+        // Back-substitution needs every key, and repeated keys are rejected by the elimination
+        if ordering.len() != self.keys().len() {
+            return None;
+        }
+
+        let (bayes_tree, _) = self.eliminate_multifrontal(ordering, eliminate_qr)?;
+
+        Some(bayes_tree.optimize())
+    }
+
+    /// The least-squares solution, with the variables eliminated in COLAMD order
+    pub fn optimize(&self) -> Option<VectorValues> {
+        self.optimize_ordered(&Ordering::colamd(&VariableIndex::new(self)))
+    }
+}
+
+impl EliminateableFactorGraph for SimpleFactorGraph<dyn GaussianLikeFactor> {
+    type ConditionalType = GaussianConditional;
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use crate::inference::nested_dissection::NestedDissection;
+    use crate::inference::FactorGraph;
+    use crate::linear::hessian::{eliminate_cholesky, HessianFactor};
+    use crate::linear::jacobian::JacobianFactor;
+    use crate::linear::noise_model::Diagonal;
+    use na::{DMatrix, DVector};
+
+    /// A 2D chain x1 - x2 - x3 with a prior on x1, odometry between neighbours and a
+    /// landmark-like measurement of x3, the odometry given in information form
+    fn chain() -> GaussianFactorGraph {
+        let I = DMatrix::<f64>::identity(2, 2);
+        let mut graph = GaussianFactorGraph::new();
+
+        graph.add(JacobianFactor::new(
+            vec![(1, I.clone())],
+            DVector::from_vec(vec![0.1, -0.2]),
+            Some(Diagonal::from_sigmas(&DVector::from_vec(vec![0.1, 0.1]))),
+        ));
+        graph.add(JacobianFactor::new(
+            vec![(1, -&I), (2, I.clone())],
+            DVector::from_vec(vec![1.0, 0.0]),
+            None,
+        ));
+        graph.add(HessianFactor::from_jacobian(&JacobianFactor::new(
+            vec![(2, -&I), (3, I.clone())],
+            DVector::from_vec(vec![1.0, 0.5]),
+            Some(Diagonal::from_sigmas(&DVector::from_vec(vec![0.5, 0.5]))),
+        )));
+        graph.add(JacobianFactor::new(
+            vec![(3, DMatrix::from_row_slice(1, 2, &[1.0, 1.0]))],
+            DVector::from_vec(vec![2.0]),
+            None,
+        ));
+
+        graph
+    }
+
+    #[test]
+    fn jacobian_and_hessian_agree() {
+        let graph = chain();
+        assert_eq!(graph.size(), 4);
+        assert_eq!(graph.keys(), vec![1, 2, 3]);
+
+        // The Hessian factor contributes the 5 rows of its augmented square root
+        let (A, b) = graph.jacobian();
+        assert_eq!((A.nrows(), A.ncols()), (10, 6));
+
+        let (H, eta) = graph.hessian();
+        assert_relative_eq!(H, A.tr_mul(&A), epsilon = 1e-9);
+        assert_relative_eq!(eta, A.tr_mul(&b), epsilon = 1e-9);
+        assert_relative_eq!(
+            graph.augmented_hessian(),
+            graph
+                .augmented_jacobian()
+                .tr_mul(&graph.augmented_jacobian()),
+            epsilon = 1e-9
+        );
+
+        // Reordering moves the blocks of the keys
+        let reordered = graph.augmented_jacobian_ordered(&[3, 1, 2]);
+        let Ab = graph.augmented_jacobian();
+        assert_relative_eq!(
+            reordered.columns(0, 2).into_owned(),
+            Ab.columns(4, 2).into_owned()
+        );
+    }
+
+    #[test]
+    fn optimize_solves_least_squares() {
+        let graph = chain();
+        let x = graph.optimize().unwrap();
+
+        // Against the normal equations
+        let (H, eta) = graph.hessian();
+        let expected = H.cholesky().unwrap().solve(&eta);
+        assert_relative_eq!(
+            x.at(1).clone(),
+            expected.rows(0, 2).into_owned(),
+            epsilon = 1e-9
+        );
+        assert_relative_eq!(
+            x.at(2).clone(),
+            expected.rows(2, 2).into_owned(),
+            epsilon = 1e-9
+        );
+        assert_relative_eq!(
+            x.at(3).clone(),
+            expected.rows(4, 2).into_owned(),
+            epsilon = 1e-9
+        );
+
+        // The gradient vanishes at the minimum, and the ordering does not matter
+        for (_, g) in graph.gradient(&x).iter() {
+            assert_relative_eq!(g.norm(), 0.0, epsilon = 1e-9);
+        }
+        let y = graph.optimize_ordered(&[3, 1, 2]).unwrap();
+        for key in 1..=3 {
+            assert_relative_eq!(x.at(key).clone(), y.at(key).clone(), epsilon = 1e-9);
+        }
+
+        // Moving away from the minimum increases the error
+        let mut z = x.clone();
+        *z.at_mut(2) += DVector::from_vec(vec![0.1, 0.0]);
+        assert!(graph.error(&z) > graph.error(&x));
+    }
+
+    #[test]
+    fn error_and_gradient() {
+        let graph = chain();
+        let mut x = VectorValues::new();
+        for key in 1..=3 {
+            x.insert(key, DVector::from_vec(vec![0.5 * key as f64, -1.0]));
+        }
+
+        let (A, b) = graph.jacobian();
+        let xv = DVector::from_vec(vec![0.5, -1.0, 1.0, -1.0, 1.5, -1.0]);
+        let e = &A * &xv - &b;
+        assert_relative_eq!(graph.error(&x), 0.5 * e.norm_squared(), epsilon = 1e-9);
+
+        let g = A.tr_mul(&e);
+        let gradient = graph.gradient(&x);
+        assert_relative_eq!(
+            gradient.at(2).clone(),
+            g.rows(2, 2).into_owned(),
+            epsilon = 1e-9
+        );
+    }
+
+    #[test]
+    fn partial_elimination_with_qr_and_cholesky() {
+        let graph = chain();
+        let x = graph.optimize().unwrap();
+
+        for (name, result) in [
+            ("QR", graph.eliminate_sequential(&[1, 2], eliminate_qr)),
+            (
+                "Cholesky",
+                graph.eliminate_sequential(&[1, 2], eliminate_cholesky),
+            ),
+        ] {
+            let (bayes_net, remaining) = result.unwrap();
+            assert_eq!(bayes_net.len(), 2, "{}", name);
+            assert_eq!(bayes_net.conditionals[0].parent_keys(), &[2]);
+            assert_eq!(bayes_net.conditionals[1].parent_keys(), &[3]);
+            assert_eq!(remaining.keys(), vec![3], "{}", name);
+
+            // Solving the rest and back-substituting gives the full solution
+            let x3 = remaining.optimize().unwrap();
+            let y = bayes_net.optimize_given(&x3);
+            for key in 1..=3 {
+                assert_relative_eq!(x.at(key).clone(), y.at(key).clone(), epsilon = 1e-9);
+            }
+
+            // The error splits into the conditionals and the remaining factors, up to the
+            // constant part that both methods keep in the last factor
+            let mut z = x.clone();
+            *z.at_mut(1) += DVector::from_vec(vec![0.3, -0.1]);
+            assert_relative_eq!(
+                graph.error(&z) - graph.error(&x),
+                bayes_net.error(&z) + remaining.error(&z)
+                    - bayes_net.error(&x)
+                    - remaining.error(&x),
+                epsilon = 1e-9
+            );
+        }
+    }
+
+    #[test]
+    fn sequential_elimination_rejects_bad_orderings() {
+        let graph = chain();
+
+        // A key that is not in the graph, and a key that is eliminated twice
+        assert!(graph.eliminate_sequential(&[1, 7], eliminate_qr).is_none());
+        assert!(graph
+            .eliminate_sequential(&[1, 2, 1], eliminate_cholesky)
+            .is_none());
+    }
+
+    #[test]
+    fn multifrontal_matches_sequential() {
+        let graph = chain();
+
+        let (bayes_net, _) = graph
+            .eliminate_sequential(&[1, 2, 3], eliminate_qr)
+            .unwrap();
+        let x = bayes_net.optimize();
+
+        for eliminate in [eliminate_qr, eliminate_cholesky] {
+            let (bayes_tree, remaining) =
+                graph.eliminate_multifrontal(&[1, 2, 3], eliminate).unwrap();
+            assert_eq!(remaining.keys(), Vec::<KeyType>::new());
+
+            // The cliques of the chain are {2, 3} and {1 | 2}
+            assert_eq!(bayes_tree.size(), 2);
+            assert_eq!(bayes_tree.roots[0].conditional.frontal_keys(), &[2, 3]);
+
+            let y = bayes_tree.optimize();
+            for key in 1..=3 {
+                assert_relative_eq!(x.at(key).clone(), y.at(key).clone(), epsilon = 1e-9);
+            }
+        }
+
+        // Partially, the root clique depends on the key left out
+        let (bayes_tree, remaining) = graph.eliminate_multifrontal(&[1, 2], eliminate_qr).unwrap();
+        assert_eq!(remaining.keys(), vec![3]);
+        assert_eq!(bayes_tree.roots[0].conditional.parent_keys(), &[3]);
+        let y = bayes_tree.optimize_given(&remaining.optimize().unwrap());
+        for key in 1..=3 {
+            assert_relative_eq!(x.at(key).clone(), y.at(key).clone(), epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn multifrontal_elimination_rejects_bad_orderings() {
+        let graph = chain();
+
+        assert!(graph
+            .eliminate_multifrontal(&[1, 7], eliminate_qr)
+            .is_none());
+        assert!(graph
+            .eliminate_multifrontal_parallel(&[1, 2, 1], eliminate_qr, 1)
+            .is_none());
+
+        // Only complete orderings of the keys of the graph can be solved
+        assert!(graph.optimize_ordered(&[1, 2]).is_none());
+        assert!(graph.optimize_ordered(&[1, 2, 2]).is_none());
+        assert!(graph.optimize_ordered(&[1, 2, 3, 4]).is_none());
+        assert!(graph.optimize_ordered(&[3, 1, 2]).is_some());
+    }
+
+    #[test]
+    fn multifrontal_elimination_of_a_tree() {
+        // A hub 0 with leaves 1, 2 and 3, each leaf measured directly
+        let mut graph = GaussianFactorGraph::new();
+        for leaf in 1..=3 {
+            graph.add(JacobianFactor::new(
+                vec![
+                    (0, -DMatrix::identity(1, 1)),
+                    (leaf, DMatrix::identity(1, 1)),
+                ],
+                DVector::from_vec(vec![leaf as f64]),
+                None,
+            ));
+            graph.add(JacobianFactor::new(
+                vec![(leaf, DMatrix::identity(1, 1))],
+                DVector::from_vec(vec![2.0 * leaf as f64]),
+                None,
+            ));
+        }
+
+        let (bayes_tree, _) = graph
+            .eliminate_multifrontal(&[1, 2, 3, 0], eliminate_qr)
+            .unwrap();
+        assert_eq!(bayes_tree.size(), 3);
+
+        let x = bayes_tree.optimize();
+        let (H, eta) = graph.hessian();
+        let expected = H.cholesky().unwrap().solve(&eta);
+        assert_relative_eq!(x.vector(&[0, 1, 2, 3]), expected, epsilon = 1e-9);
+    }
+
+    /// A `size` by `size` grid of scalars with a prior on the corner and noisy differences
+    fn grid(size: u64) -> GaussianFactorGraph {
+        let mut graph = GaussianFactorGraph::new();
+        graph.add(JacobianFactor::new(
+            vec![(0, DMatrix::identity(1, 1))],
+            DVector::from_vec(vec![1.0]),
+            None,
+        ));
+        for key in 0..size * size {
+            for next in [key + 1, key + size] {
+                if next < size * size && (next == key + size || next % size != 0) {
+                    graph.add(JacobianFactor::new(
+                        vec![
+                            (key, -DMatrix::identity(1, 1)),
+                            (next, DMatrix::identity(1, 1)),
+                        ],
+                        DVector::from_vec(vec![(key % 5) as f64 - 0.3 * next as f64]),
+                        None,
+                    ));
+                }
+            }
+        }
+        graph
+    }
+
+    #[test]
+    fn nested_dissection_junction_tree() {
+        let graph = grid(6);
+        let dissection = NestedDissection::with_leaf_size(&VariableIndex::new(&graph), 4);
+        let (bayes_tree, _) = dissection
+            .junction_tree(&graph)
+            .eliminate(eliminate_qr)
+            .unwrap();
+        assert!(bayes_tree.size() > 1);
+
+        let keys: Vec<KeyType> = (0..36).collect();
+        let x = bayes_tree.optimize();
+        let (H, eta) = graph.hessian();
+        let expected = H.cholesky().unwrap().solve(&eta);
+        assert_relative_eq!(x.vector(&keys), expected, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn parallel_elimination_is_identical() {
+        let graph = grid(12);
+        let index = VariableIndex::new(&graph);
+        let keys: Vec<KeyType> = (0..144).collect();
+
+        let ordering = Ordering::colamd(&index);
+        let (sequential, _) = graph
+            .eliminate_multifrontal(&ordering, eliminate_qr)
+            .unwrap();
+        let expected = sequential.optimize().vector(&keys);
+        for threshold in [0, 16, usize::MAX] {
+            let (parallel, _) = graph
+                .eliminate_multifrontal_parallel(&ordering, eliminate_qr, threshold)
+                .unwrap();
+            assert_eq!(parallel.size(), sequential.size());
+            assert_eq!(parallel.optimize().vector(&keys), expected);
+        }
+
+        let junction_tree = NestedDissection::with_leaf_size(&index, 8).junction_tree(&graph);
+        let (sequential, _) = junction_tree.eliminate(eliminate_cholesky).unwrap();
+        let (parallel, _) = junction_tree
+            .eliminate_parallel(eliminate_cholesky, 0)
+            .unwrap();
+        assert_eq!(
+            parallel.optimize().vector(&keys),
+            sequential.optimize().vector(&keys)
+        );
+    }
+
+    #[test]
+    fn optimize_long_chain() {
+        // The elimination tree of an odometry chain is about as deep as the chain is long, so
+        // building, eliminating, solving and dropping the trees must not recurse per level
+        let n = 100_000;
+        let I = DMatrix::<f64>::identity(1, 1);
+        let mut graph = GaussianFactorGraph::new();
+        graph.add(JacobianFactor::new(
+            vec![(0, I.clone())],
+            DVector::zeros(1),
+            None,
+        ));
+        for key in 1..n {
+            graph.add(JacobianFactor::new(
+                vec![(key - 1, -&I), (key, I.clone())],
+                DVector::from_element(1, 1.0),
+                None,
+            ));
+        }
+
+        let solution = graph.optimize().unwrap();
+        assert_eq!(solution.len(), n as usize);
+        assert_relative_eq!(solution.at(n - 1)[0], (n - 1) as f64, epsilon = 1e-6);
+
+        let ordering = Ordering::colamd(&VariableIndex::new(&graph));
+        let (parallel, _) = graph
+            .eliminate_multifrontal_parallel(&ordering, eliminate_qr, 64)
+            .unwrap();
+        assert_relative_eq!(
+            parallel.optimize().at(n - 1)[0],
+            (n - 1) as f64,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn underdetermined_system_has_no_solution() {
+        let mut graph = GaussianFactorGraph::new();
+        graph.add(JacobianFactor::new(
+            vec![(1, DMatrix::identity(1, 1)), (2, -DMatrix::identity(1, 1))],
+            DVector::from_vec(vec![1.0]),
+            None,
+        ));
+
+        assert!(graph.optimize().is_none());
+        assert!(graph
+            .eliminate_sequential(&[1, 2], eliminate_cholesky)
+            .is_none());
+    }
+}
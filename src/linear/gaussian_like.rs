@@ -1,9 +1,8 @@
-use crate::inference::Conditional;
 use crate::inference::Factor;
 
 use nalgebra as na;
 
-pub trait GaussianLikeFactor: Factor {
+pub trait GaussianLikeFactor: Factor + Send + Sync {
     fn augmented_jacobian(&self) -> na::OMatrix<f64, na::Dynamic, na::Dynamic>;
 
     fn jacobian(
@@ -21,5 +20,3 @@ pub trait GaussianLikeFactor: Factor {
 
     fn hessian_block_diagonal(&self) -> Vec<(u64, na::OMatrix<f64, na::Dynamic, na::Dynamic>)>;
 }
-
-pub trait GaussianConditional<F: GaussianLikeFactor>: Conditional<F> {}
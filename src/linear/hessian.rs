@@ -0,0 +1,439 @@
+use crate::core::matrix::{SymmetricBlockMatrix, VerticalBlockMatrix};
+use crate::inference::factor::{Factor, KeyType};
+use crate::inference::{FactorGraph, FactorKeys};
+use crate::linear::gaussian_conditional::GaussianConditional;
+use crate::linear::gaussian_factor_graph::{frontals_first, GaussianFactorGraph};
+use crate::linear::gaussian_like::GaussianLikeFactor;
+use crate::linear::jacobian::JacobianFactor;
+use nalgebra as na;
+use std::io::ErrorKind;
+use std::sync::Arc;
+
+/// A linear factor in information form, `0.5 * (x' G x - 2 x' g + f)`.
+///
+/// The augmented information matrix [G g; g' f] is stored in a `SymmetricBlockMatrix` with one
+/// block per key, plus a last block of dimension 1 for the linear and constant terms.
+#[derive(Debug)]
+pub struct HessianFactor {
+    keys: Vec<KeyType>,
+    info: SymmetricBlockMatrix<f64>,
+}
+
+#[allow(non_snake_case)]
+impl HessianFactor {
+    /// A factor from the augmented information matrix [G g; g' f], whose upper triangle is used
+    pub fn new(
+        keys: Vec<KeyType>,
+        dims: &[usize],
+        augmented_information: na::DMatrix<f64>,
+    ) -> Self {
+        assert_eq!(keys.len(), dims.len(), "Need one dimension per key");
+
+        HessianFactor {
+            keys,
+            info: SymmetricBlockMatrix::from_matrix(dims, augmented_information, true),
+        }
+    }
+
+    /// A factor from raw blocks: `G` holds the upper triangular blocks G_ij, i <= j, in row
+    /// major order, i.e. G_11, G_12, ..., G_1n, G_22, ..., G_nn, and `g` holds one g_i per key.
+    pub fn from_blocks(
+        keys: Vec<KeyType>,
+        G: &[na::DMatrix<f64>],
+        g: &[na::DVector<f64>],
+        f: f64,
+    ) -> Self {
+        let n = keys.len();
+        assert_eq!(G.len(), n * (n + 1) / 2, "Need the upper triangle of G");
+        assert_eq!(g.len(), n, "Need one g_i per key");
+
+        let dims: Vec<usize> = g.iter().map(|g| g.len()).collect();
+        let mut offsets = vec![0; n + 1];
+        for i in 0..n {
+            offsets[i + 1] = offsets[i] + dims[i];
+        }
+        let d = offsets[n];
+
+        let mut info = na::DMatrix::zeros(d + 1, d + 1);
+        let mut blocks = G.iter();
+        for i in 0..n {
+            for j in i..n {
+                let G_ij = blocks.next().unwrap();
+                assert_eq!(
+                    (G_ij.nrows(), G_ij.ncols()),
+                    (dims[i], dims[j]),
+                    "Block ({}, {}) has the wrong size",
+                    i,
+                    j
+                );
+                info.slice_mut((offsets[i], offsets[j]), (dims[i], dims[j]))
+                    .copy_from(G_ij);
+            }
+            info.slice_mut((offsets[i], d), (dims[i], 1))
+                .copy_from(&g[i]);
+        }
+        info[(d, d)] = f;
+
+        HessianFactor::new(keys, &dims, info)
+    }
+
+    /// The factor with the same error as `factor`, i.e. with [G g; g' f] = [A b]' [A b]
+    pub fn from_jacobian(factor: &JacobianFactor) -> Self {
+        let dims: Vec<usize> = (0..factor.num_keys()).map(|i| factor.dim(i)).collect();
+
+        HessianFactor::new(
+            factor.keys().to_vec(),
+            &dims,
+            factor.augmented_information(),
+        )
+    }
+
+    /// Sums several factors into one, with the keys in order of first appearance
+    pub fn sum(factors: &[&dyn GaussianLikeFactor]) -> Self {
+        let mut keys = Vec::new();
+        for factor in factors {
+            for key in factor.key_iter() {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        HessianFactor::sum_with_keys(factors, &keys)
+    }
+
+    /// Sums several factors into one whose blocks are in the order of `keys`, which must
+    /// contain all keys of the factors
+    pub fn sum_with_keys(factors: &[&dyn GaussianLikeFactor], keys: &[KeyType]) -> Self {
+        let position = |key: KeyType| {
+            keys.iter()
+                .position(|k| *k == key)
+                .unwrap_or_else(|| panic!("Key {} is missing from the ordering", key))
+        };
+
+        // The dimensions of the keys can be read off the Hessian diagonals of the factors
+        let mut dims = vec![None; keys.len()];
+        for factor in factors {
+            for (key, diag) in factor.hessian_diagonal() {
+                let dim = &mut dims[position(key)];
+                assert!(
+                    dim.is_none() || *dim == Some(diag.len()),
+                    "Key {} has inconsistent dimensions",
+                    key
+                );
+                *dim = Some(diag.len());
+            }
+        }
+        let dims: Vec<usize> = dims
+            .into_iter()
+            .map(|d| d.expect("Key does not appear in any factor"))
+            .collect();
+
+        let mut offsets = vec![0; keys.len() + 1];
+        for i in 0..keys.len() {
+            offsets[i + 1] = offsets[i] + dims[i];
+        }
+        let d = offsets[keys.len()];
+
+        let mut info = na::DMatrix::zeros(d + 1, d + 1);
+        for factor in factors {
+            let augmented = factor.augmented_information();
+
+            // Scatter the blocks of the factor, and its last row and column, into the sum
+            let mut scatter: Vec<(usize, usize, usize)> = Vec::new();
+            let mut col = 0;
+            for key in factor.key_iter() {
+                let p = position(key);
+                scatter.push((col, offsets[p], dims[p]));
+                col += dims[p];
+            }
+            scatter.push((col, d, 1));
+
+            for (src_i, dst_i, dim_i) in scatter.iter() {
+                for (src_j, dst_j, dim_j) in scatter.iter() {
+                    let mut dst = info.slice_mut((*dst_i, *dst_j), (*dim_i, *dim_j));
+                    dst += augmented.slice((*src_i, *src_j), (*dim_i, *dim_j));
+                }
+            }
+        }
+
+        HessianFactor::new(keys.to_vec(), &dims, info)
+    }
+
+    pub fn keys(&self) -> &[KeyType] {
+        &self.keys
+    }
+
+    /// The augmented information matrix [G g; g' f]
+    pub fn info(&self) -> &SymmetricBlockMatrix<f64> {
+        &self.info
+    }
+
+    /// Total dimension of all keys
+    pub fn cols(&self) -> usize {
+        self.info.offset(self.keys.len())
+    }
+
+    /// Dimension of the variable at position `index`
+    pub fn dim(&self, index: usize) -> usize {
+        self.info.offset(index + 1) - self.info.offset(index)
+    }
+
+    /// The information block G_ij between the variables at positions `i` and `j`
+    pub fn information_block(&self, i: usize, j: usize) -> na::DMatrix<f64> {
+        self.info.block(i, j)
+    }
+
+    /// The linear term g
+    pub fn linear_term(&self) -> na::DVector<f64> {
+        let n = self.keys.len();
+        self.info.block_(0, n, n, 1).column(0).into_owned()
+    }
+
+    /// The constant term f
+    pub fn constant_term(&self) -> f64 {
+        let n = self.keys.len();
+        self.info.block(n, n)[(0, 0)]
+    }
+
+    /// `0.5 * (x' G x - 2 x' g + f)`, with `x[j]` the value of the `j`-th key
+    pub fn error(&self, x: &[na::DVector<f64>]) -> f64 {
+        assert_eq!(x.len(), self.keys.len(), "Wrong number of values");
+
+        let mut xs = na::DVector::zeros(self.cols() + 1);
+        for (i, x) in x.iter().enumerate() {
+            xs.rows_mut(self.info.offset(i), x.len()).copy_from(x);
+        }
+        xs[self.cols()] = -1.0;
+
+        0.5 * xs.dot(&(self.info.selfadjoint_view() * &xs))
+    }
+}
+
+impl Factor for HessianFactor {
+    fn num_keys(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn key_at(&self, index: usize) -> Result<KeyType, std::io::Error> {
+        self.keys
+            .get(index)
+            .cloned()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Range"))
+    }
+
+    fn dim_at(&self, index: usize) -> Option<usize> {
+        if index < self.keys.len() {
+            Some(self.dim(index))
+        } else {
+            None
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+impl GaussianLikeFactor for HessianFactor {
+    /// A square root [A b] of the augmented information, i.e. [A b]' [A b] = [G g; g' f].
+    ///
+    /// This is the upper Cholesky factor if [G g; g' f] is positive definite, and otherwise
+    /// comes from its eigendecomposition.
+    fn augmented_jacobian(&self) -> na::DMatrix<f64> {
+        let info = self.info.selfadjoint_view();
+
+        match na::Cholesky::new(info.clone()) {
+            Some(llt) => llt.l().transpose(),
+            None => {
+                let eigen = info.symmetric_eigen();
+                let sqrt_values = eigen.eigenvalues.map(|l| l.max(0.0).sqrt());
+                na::DMatrix::from_diagonal(&sqrt_values) * eigen.eigenvectors.transpose()
+            }
+        }
+    }
+
+    fn jacobian(
+        &self,
+    ) -> (
+        na::OMatrix<f64, na::Dynamic, na::Dynamic>,
+        na::OVector<f64, na::Dynamic>,
+    ) {
+        let Ab = self.augmented_jacobian();
+        let n = self.cols();
+
+        (Ab.columns(0, n).into_owned(), Ab.column(n).into_owned())
+    }
+
+    fn augmented_information(&self) -> na::OMatrix<f64, na::Dynamic, na::Dynamic> {
+        self.info.selfadjoint_view()
+    }
+
+    fn information(&self) -> na::OMatrix<f64, na::Dynamic, na::Dynamic> {
+        let n = self.cols();
+        self.info
+            .selfadjoint_view()
+            .slice((0, 0), (n, n))
+            .into_owned()
+    }
+
+    fn hessian_diagonal(&self) -> Vec<(u64, na::OVector<f64, na::Dynamic>)> {
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (*key, self.info.diagonal(i)))
+            .collect()
+    }
+
+    fn hessian_block_diagonal(&self) -> Vec<(u64, na::OMatrix<f64, na::Dynamic, na::Dynamic>)> {
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (*key, self.info.block(i, i)))
+            .collect()
+    }
+}
+
+/// Eliminates `frontals` from `factors` by a partial Cholesky factorization of the sum of
+/// their augmented information matrices.
+///
+/// Returns the conditional of the frontals given the other keys, with unit noise, and the
+/// Schur complement as a factor on the other keys, or `None` if the information of the frontals
+/// is not positive definite.
+pub fn eliminate_cholesky(
+    factors: &GaussianFactorGraph,
+    frontals: &[KeyType],
+) -> Option<(GaussianConditional, Arc<dyn GaussianLikeFactor>)> {
+    let (keys, dims) = frontals_first(factors, frontals);
+
+    let refs: Vec<&dyn GaussianLikeFactor> = factors
+        .shared_factors()
+        .iter()
+        .map(|f| f.as_ref())
+        .collect();
+    let mut sum = HessianFactor::sum_with_keys(&refs, &keys);
+
+    if !sum.info.cholesky_partial(frontals.len()) {
+        return None;
+    }
+    let frontal = sum.info.split(frontals.len());
+
+    let conditional = GaussianConditional::new(
+        keys.clone(),
+        frontals.len(),
+        VerticalBlockMatrix::from_matrix(&dims, frontal, true),
+        None,
+    );
+    let factor = HessianFactor {
+        keys: keys[frontals.len()..].to_vec(),
+        info: sum.info,
+    };
+
+    Some((conditional, Arc::new(factor)))
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use crate::linear::noise_model::Diagonal;
+    use na::{DMatrix, DVector};
+
+    fn jacobian_factor() -> JacobianFactor {
+        let A1 = DMatrix::from_row_slice(3, 2, &[1.0, 2.0, 3.0, 4.0, 0.0, 1.0]);
+        let A2 = DMatrix::from_row_slice(3, 1, &[5.0, 6.0, 2.0]);
+        let b = DVector::from_vec(vec![7.0, 8.0, 1.0]);
+        let model = Diagonal::from_sigmas(&DVector::from_vec(vec![0.5, 2.0, 1.0]));
+
+        JacobianFactor::new(
+            vec![3, 1].into_iter().zip(vec![A1, A2]).collect(),
+            b,
+            Some(model),
+        )
+    }
+
+    #[test]
+    fn from_jacobian_has_the_same_error() {
+        let jf = jacobian_factor();
+        let hf = HessianFactor::from_jacobian(&jf);
+
+        assert_eq!(hf.keys(), &[3, 1]);
+        assert_eq!((hf.dim(0), hf.dim(1), hf.cols()), (2, 1, 3));
+        assert_relative_eq!(hf.information(), jf.information(), epsilon = 1e-12);
+        assert_relative_eq!(
+            hf.augmented_information(),
+            jf.augmented_information(),
+            epsilon = 1e-12
+        );
+
+        let x = [
+            DVector::from_vec(vec![0.3, -1.0]),
+            DVector::from_vec(vec![2.0]),
+        ];
+        assert_relative_eq!(hf.error(&x), jf.error(&x), epsilon = 1e-9);
+
+        // The square root reproduces the information
+        let Ab = hf.augmented_jacobian();
+        assert_relative_eq!(Ab.tr_mul(&Ab), jf.augmented_information(), epsilon = 1e-9);
+    }
+
+    #[test]
+    fn from_blocks_works() {
+        let G11 = DMatrix::from_row_slice(2, 2, &[4.0, 1.0, 1.0, 3.0]);
+        let G12 = DMatrix::from_row_slice(2, 1, &[0.5, -1.0]);
+        let G22 = DMatrix::from_row_slice(1, 1, &[2.0]);
+        let g1 = DVector::from_vec(vec![1.0, 2.0]);
+        let g2 = DVector::from_vec(vec![-3.0]);
+
+        let hf = HessianFactor::from_blocks(
+            vec![0, 1],
+            &[G11.clone(), G12.clone(), G22.clone()],
+            &[g1.clone(), g2],
+            10.0,
+        );
+
+        assert_relative_eq!(hf.information_block(0, 0), G11);
+        assert_relative_eq!(hf.information_block(0, 1), G12);
+        assert_relative_eq!(hf.information_block(1, 0), G12.transpose());
+        assert_relative_eq!(hf.linear_term(), DVector::from_vec(vec![1.0, 2.0, -3.0]));
+        assert_relative_eq!(hf.constant_term(), 10.0);
+
+        let diag = hf.hessian_diagonal();
+        assert_relative_eq!(diag[0].1, DVector::from_vec(vec![4.0, 3.0]));
+        assert_relative_eq!(hf.hessian_block_diagonal()[1].1, G22);
+
+        // 0.5 * (x' G x - 2 x' g + f) at x = [1, 0, 0]
+        let x = [
+            DVector::from_vec(vec![1.0, 0.0]),
+            DVector::from_vec(vec![0.0]),
+        ];
+        assert_relative_eq!(hf.error(&x), 0.5 * (4.0 - 2.0 + 10.0));
+    }
+
+    #[test]
+    fn sum_matches_stacked_jacobian() {
+        let jf1 = jacobian_factor();
+        let jf2 = JacobianFactor::new(
+            vec![
+                (1, DMatrix::from_row_slice(1, 1, &[2.0])),
+                (5, DMatrix::identity(1, 2)),
+            ],
+            DVector::from_vec(vec![1.0]),
+            None,
+        );
+        let hf2 = HessianFactor::from_jacobian(&jf2);
+
+        let sum = HessianFactor::sum(&[&jf1, &hf2]);
+        assert_eq!(sum.keys(), &[3, 1, 5]);
+        assert_eq!(sum.cols(), 5);
+
+        let x = [
+            DVector::from_vec(vec![0.3, -1.0]),
+            DVector::from_vec(vec![2.0]),
+            DVector::from_vec(vec![0.5, 4.0]),
+        ];
+        let expected = jf1.error(&x[..2]) + jf2.error(&x[1..]);
+        assert_relative_eq!(sum.error(&x), expected, epsilon = 1e-9);
+
+        let reordered = HessianFactor::sum_with_keys(&[&jf1, &jf2], &[5, 3, 1]);
+        let y = [x[2].clone(), x[0].clone(), x[1].clone()];
+        assert_relative_eq!(reordered.error(&y), expected, epsilon = 1e-9);
+    }
+}
@@ -1,47 +1,414 @@
+use crate::core::matrix::VerticalBlockMatrix;
 use crate::inference::factor::{Factor, KeyType};
+use crate::linear::gaussian_conditional::GaussianConditional;
+use crate::linear::gaussian_factor_graph::{frontals_first, GaussianFactorGraph};
 use crate::linear::gaussian_like::GaussianLikeFactor;
+use crate::linear::noise_model::{Diagonal, NoiseModel};
 use nalgebra as na;
+use std::io::ErrorKind;
+use std::sync::Arc;
 
+/// A linear factor of the form `0.5 * || W (sum_j A_j x_j - b) ||^2`, where the whitening
+/// `W` comes from an optional diagonal noise model and is the identity if there is none.
+///
+/// Only diagonal models are kept with the factor. Any other noise model, e.g. a full
+/// `Gaussian`, mixes the rows and is folded into `A` and `b` by `JacobianFactor::prewhitened`.
+///
+/// All `A_j` and `b` are stored contiguously as the blocks of [A_1 ... A_n b].
 #[derive(Debug)]
-pub struct JacobianFactor {}
+#[allow(non_snake_case)]
+pub struct JacobianFactor {
+    keys: Vec<KeyType>,
+    Ab: VerticalBlockMatrix<f64>,
+    model: Option<Diagonal<na::Dynamic>>,
+}
+
+#[allow(non_snake_case)]
+impl JacobianFactor {
+    /// A factor with one `(key, A_j)` term per variable.
+    ///
+    /// Every `A_j` must have as many rows as `b`, and so must the noise model.
+    pub fn new(
+        terms: Vec<(KeyType, na::DMatrix<f64>)>,
+        b: na::DVector<f64>,
+        model: Option<Diagonal<na::Dynamic>>,
+    ) -> Self {
+        for (key, A) in terms.iter() {
+            assert_eq!(
+                A.nrows(),
+                b.len(),
+                "Block of key {} has {} rows, but b has {}",
+                key,
+                A.nrows(),
+                b.len()
+            );
+        }
+        if let Some(model) = &model {
+            assert_eq!(model.dim(), b.len(), "Noise model has the wrong dimension");
+        }
+
+        let dims: Vec<usize> = terms.iter().map(|(_, A)| A.ncols()).collect();
+        let mut Ab = VerticalBlockMatrix::from_dimensions(&dims, b.len(), true);
+        for (j, (_, A)) in terms.iter().enumerate() {
+            Ab.block_mut(j).copy_from(A);
+        }
+        Ab.block_mut(dims.len()).copy_from(&b);
+
+        let keys = terms.into_iter().map(|(key, _)| key).collect();
+
+        JacobianFactor { keys, Ab, model }
+    }
+
+    /// A factor with the given noise model folded into the `A_j` and `b`, and no noise model
+    /// left. This is how noise models that are not diagonal are attached.
+    pub fn prewhitened(
+        terms: Vec<(KeyType, na::DMatrix<f64>)>,
+        b: na::DVector<f64>,
+        model: &dyn NoiseModel<na::Dynamic>,
+    ) -> Self {
+        assert_eq!(model.dim(), b.len(), "Noise model has the wrong dimension");
+
+        let terms = terms
+            .into_iter()
+            .map(|(key, A)| (key, model.whiten_mat(&A)))
+            .collect();
+        JacobianFactor::new(terms, model.whiten(&b), None)
+    }
+
+    /// A factor from [A_1 ... A_n b] stored with one block per key plus the RHS
+    pub fn from_block_matrix(
+        keys: Vec<KeyType>,
+        Ab: VerticalBlockMatrix<f64>,
+        model: Option<Diagonal<na::Dynamic>>,
+    ) -> Self {
+        assert_eq!(
+            Ab.num_blocks(),
+            keys.len() + 1,
+            "Need one block per key plus the RHS"
+        );
+        if let Some(model) = &model {
+            assert_eq!(
+                model.dim(),
+                Ab.rows(),
+                "Noise model has the wrong dimension"
+            );
+        }
+
+        JacobianFactor { keys, Ab, model }
+    }
+
+    pub fn keys(&self) -> &[KeyType] {
+        &self.keys
+    }
+
+    pub fn rows(&self) -> usize {
+        self.Ab.rows()
+    }
+
+    /// Total number of columns of all `A_j`, i.e. without the RHS
+    pub fn cols(&self) -> usize {
+        self.Ab.offset(self.keys.len())
+    }
+
+    /// Dimension of the variable at position `index`
+    pub fn dim(&self, index: usize) -> usize {
+        self.Ab.dim(index)
+    }
+
+    /// The unwhitened block of the variable at position `index`
+    pub fn a(&self, index: usize) -> na::DMatrixSlice<'_, f64> {
+        self.Ab.block(index)
+    }
+
+    /// The unwhitened RHS
+    pub fn b(&self) -> na::DVectorSlice<'_, f64> {
+        self.Ab.column(self.cols())
+    }
+
+    /// The unwhitened [A_1 ... A_n b] with its column blocks
+    pub fn matrix_object(&self) -> &VerticalBlockMatrix<f64> {
+        &self.Ab
+    }
+
+    pub fn model(&self) -> Option<&Diagonal<na::Dynamic>> {
+        self.model.as_ref()
+    }
+
+    /// Position of `key` in this factor
+    pub fn find(&self, key: KeyType) -> Option<usize> {
+        self.keys.iter().position(|k| *k == key)
+    }
+
+    fn whiten_mat(&self, m: &na::DMatrix<f64>) -> na::DMatrix<f64> {
+        match &self.model {
+            Some(model) => model.whiten_mat(m),
+            None => m.clone(),
+        }
+    }
+
+    fn whiten(&self, v: &na::DVector<f64>) -> na::DVector<f64> {
+        match &self.model {
+            Some(model) => model.whiten(v),
+            None => v.clone(),
+        }
+    }
+
+    fn dims(&self) -> Vec<usize> {
+        (0..self.keys.len()).map(|j| self.dim(j)).collect()
+    }
+
+    /// The same factor with the noise model folded into `A` and `b`
+    pub fn whitened(&self) -> JacobianFactor {
+        JacobianFactor {
+            keys: self.keys.clone(),
+            Ab: VerticalBlockMatrix::from_matrix(&self.dims(), self.augmented_jacobian(), true),
+            model: None,
+        }
+    }
+
+    /// The whitened residual `W (sum_j A_j x_j - b)`, with `x[j]` the value of the `j`-th key
+    pub fn error_vector(&self, x: &[na::DVector<f64>]) -> na::DVector<f64> {
+        assert_eq!(x.len(), self.keys.len(), "Wrong number of values");
+
+        let mut e = -self.b();
+        for (j, x) in x.iter().enumerate() {
+            e += self.a(j) * x;
+        }
+        self.whiten(&e)
+    }
+
+    /// `0.5 * || W (sum_j A_j x_j - b) ||^2`
+    pub fn error(&self, x: &[na::DVector<f64>]) -> f64 {
+        0.5 * self.error_vector(x).norm_squared()
+    }
+}
 
 impl Factor for JacobianFactor {
     fn num_keys(&self) -> usize {
-        todo!()
+        self.keys.len()
     }
 
     fn key_at(&self, index: usize) -> Result<KeyType, std::io::Error> {
-        todo!()
+        self.keys
+            .get(index)
+            .cloned()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "Range"))
+    }
+
+    fn dim_at(&self, index: usize) -> Option<usize> {
+        if index < self.keys.len() {
+            Some(self.dim(index))
+        } else {
+            None
+        }
     }
 }
 
+#[allow(non_snake_case)]
 impl GaussianLikeFactor for JacobianFactor {
+    /// The whitened [A_1 ... A_n b]
     fn augmented_jacobian(&self) -> na::DMatrix<f64> {
-        unimplemented!()
+        self.whiten_mat(&self.Ab.full().into_owned())
     }
 
+    /// The whitened [A_1 ... A_n] and b
     fn jacobian(
         &self,
     ) -> (
         na::OMatrix<f64, na::Dynamic, na::Dynamic>,
         na::OVector<f64, na::Dynamic>,
     ) {
-        unimplemented!()
+        let Ab = self.augmented_jacobian();
+        let n = self.cols();
+
+        (Ab.columns(0, n).into_owned(), Ab.column(n).into_owned())
     }
 
     fn augmented_information(&self) -> na::OMatrix<f64, na::Dynamic, na::Dynamic> {
-        unimplemented!()
+        let Ab = self.augmented_jacobian();
+        Ab.tr_mul(&Ab)
     }
 
     fn information(&self) -> na::OMatrix<f64, na::Dynamic, na::Dynamic> {
-        unimplemented!()
+        let (A, _) = self.jacobian();
+        A.tr_mul(&A)
     }
 
     fn hessian_diagonal(&self) -> Vec<(u64, na::OVector<f64, na::Dynamic>)> {
-        unimplemented!()
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(j, key)| {
+                let A = self.whiten_mat(&self.a(j).into_owned());
+                let diag = na::DVector::from_iterator(
+                    A.ncols(),
+                    A.column_iter().map(|c| c.norm_squared()),
+                );
+                (*key, diag)
+            })
+            .collect()
     }
 
     fn hessian_block_diagonal(&self) -> Vec<(u64, na::OMatrix<f64, na::Dynamic, na::Dynamic>)> {
-        unimplemented!()
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(j, key)| {
+                let A = self.whiten_mat(&self.a(j).into_owned());
+                (*key, A.tr_mul(&A))
+            })
+            .collect()
+    }
+}
+
+/// Eliminates `frontals` from `factors` by QR factorization of their stacked whitened [A b].
+///
+/// Returns the conditional of the frontals given the other keys, with unit noise, and the
+/// upper triangular factor on the other keys, or `None` if the frontals are not determined.
+#[allow(non_snake_case)]
+pub fn eliminate_qr(
+    factors: &GaussianFactorGraph,
+    frontals: &[KeyType],
+) -> Option<(GaussianConditional, Arc<dyn GaussianLikeFactor>)> {
+    let (keys, dims) = frontals_first(factors, frontals);
+    let n = dims.iter().sum::<usize>();
+    let frontal_dim = dims[..frontals.len()].iter().sum::<usize>();
+
+    let mut Ab =
+        VerticalBlockMatrix::from_matrix(&dims, factors.augmented_jacobian_ordered(&keys), true);
+    if Ab.rows() < frontal_dim {
+        return None;
+    }
+    Ab.householder_qr(0, keys.len() + 1);
+
+    let full = Ab.full();
+    let R = full.slice((0, 0), (frontal_dim, frontal_dim));
+    let tolerance = R.diagonal().amax() * n as f64 * f64::EPSILON;
+    if R.diagonal().iter().any(|r| r.abs() <= tolerance) {
+        return None;
+    }
+
+    let conditional = GaussianConditional::new(
+        keys.clone(),
+        frontals.len(),
+        VerticalBlockMatrix::from_matrix(&dims, full.rows(0, frontal_dim).into_owned(), true),
+        None,
+    );
+
+    // Below the triangular part of [A b] everything is zero
+    let rows = Ab.rows().min(n + 1) - frontal_dim;
+    let remaining = full
+        .slice((frontal_dim, frontal_dim), (rows, n + 1 - frontal_dim))
+        .into_owned();
+    let factor = JacobianFactor::from_block_matrix(
+        keys[frontals.len()..].to_vec(),
+        VerticalBlockMatrix::from_matrix(&dims[frontals.len()..], remaining, true),
+        None,
+    );
+
+    Some((conditional, Arc::new(factor)))
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use na::{DMatrix, DVector};
+
+    fn example() -> JacobianFactor {
+        let A1 = DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 3.0, 4.0]);
+        let A2 = DMatrix::from_row_slice(2, 1, &[5.0, 6.0]);
+        let b = DVector::from_vec(vec![7.0, 8.0]);
+        let model = Diagonal::from_sigmas(&DVector::from_vec(vec![0.5, 2.0]));
+
+        JacobianFactor::new(vec![(3, A1), (1, A2)], b, Some(model))
+    }
+
+    #[test]
+    fn keys_and_dims() {
+        let f = example();
+
+        assert_eq!(f.num_keys(), 2);
+        assert_eq!(f.key_at(0).unwrap(), 3);
+        assert_eq!(f.key_at(1).unwrap(), 1);
+        assert!(f.key_at(2).is_err());
+        assert_eq!((f.rows(), f.cols()), (2, 3));
+        assert_eq!((f.dim(0), f.dim(1)), (2, 1));
+        assert_eq!(f.find(1), Some(1));
+    }
+
+    #[test]
+    fn augmented_jacobian_is_whitened() {
+        let f = example();
+
+        let expected = DMatrix::from_row_slice(
+            2,
+            4,
+            &[2.0, 4.0, 10.0, 14.0, 1.5, 2.0, 3.0, 4.0], //
+        );
+        assert_relative_eq!(f.augmented_jacobian(), expected);
+
+        let (A, b) = f.jacobian();
+        assert_relative_eq!(A, expected.columns(0, 3).into_owned());
+        assert_relative_eq!(b, expected.column(3).into_owned());
+        assert_relative_eq!(f.whitened().augmented_jacobian(), expected);
+    }
+
+    #[test]
+    fn prewhitened_with_full_noise_model() {
+        use crate::linear::noise_model::{Gaussian, GaussianNoise};
+
+        let R = DMatrix::from_row_slice(2, 2, &[2.0, 1.0, 0.0, 0.5]);
+        let A1 = DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 3.0, 4.0]);
+        let A2 = DMatrix::from_row_slice(2, 1, &[5.0, 6.0]);
+        let b = DVector::from_vec(vec![7.0, 8.0]);
+        let f = JacobianFactor::prewhitened(
+            vec![(3, A1.clone()), (1, A2.clone())],
+            b.clone(),
+            &Gaussian::from_sqrtinfo(&R, false),
+        );
+
+        assert!(f.model().is_none());
+        assert_relative_eq!(f.a(0).into_owned(), &R * A1);
+        assert_relative_eq!(f.a(1).into_owned(), &R * A2);
+        assert_relative_eq!(f.b().into_owned(), &R * b);
+    }
+
+    #[test]
+    fn information_and_hessian_diagonals() {
+        let f = example();
+        let (A, _) = f.jacobian();
+        let info = A.tr_mul(&A);
+
+        assert_relative_eq!(f.information(), info);
+        assert_relative_eq!(
+            f.augmented_information().slice((0, 0), (3, 3)).into_owned(),
+            info
+        );
+
+        let diag = f.hessian_diagonal();
+        assert_eq!(diag[0].0, 3);
+        assert_relative_eq!(
+            diag[0].1,
+            DVector::from_vec(vec![info[(0, 0)], info[(1, 1)]])
+        );
+        assert_relative_eq!(diag[1].1, DVector::from_vec(vec![info[(2, 2)]]));
+
+        let blocks = f.hessian_block_diagonal();
+        assert_relative_eq!(blocks[0].1, info.slice((0, 0), (2, 2)).into_owned());
+        assert_relative_eq!(blocks[1].1, info.slice((2, 2), (1, 1)).into_owned());
+    }
+
+    #[test]
+    fn error_is_half_squared_whitened_residual() {
+        let f = example();
+        let x = [
+            DVector::from_vec(vec![1.0, 0.0]),
+            DVector::from_vec(vec![1.0]),
+        ];
+
+        // A x - b = [-1, 1], whitened [-2, 0.5]
+        assert_relative_eq!(f.error_vector(&x), DVector::from_vec(vec![-2.0, 0.5]));
+        assert_relative_eq!(f.error(&x), 0.5 * (4.0 + 0.25));
     }
 }
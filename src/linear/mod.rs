@@ -1,7 +1,17 @@
 pub mod gaussian;
+pub mod gaussian_bayes_net;
+pub mod gaussian_bayes_tree;
+pub mod gaussian_conditional;
 pub mod gaussian_factor_graph;
 pub mod gaussian_like;
+pub mod hessian;
 pub mod jacobian;
 pub mod noise_model;
+pub mod vector_values;
 
+pub use gaussian_bayes_net::GaussianBayesNet;
+pub use gaussian_bayes_tree::GaussianBayesTree;
+pub use gaussian_conditional::GaussianConditional;
+pub use gaussian_factor_graph::GaussianFactorGraph;
 pub use gaussian_like::GaussianLikeFactor;
+pub use vector_values::VectorValues;
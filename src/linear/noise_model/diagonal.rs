@@ -7,7 +7,7 @@ use std::fmt::Debug;
 
 use super::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Diagonal<D: Dim, T: RealField + Copy = f64>
 where
     DefaultAllocator: Allocator<T, D>,
@@ -18,6 +18,44 @@ where
     precisions_: OVector<T, D>,
 }
 
+impl<D: Dim, T: RealField + Copy> Diagonal<D, T>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// A diagonal noise model with standard deviations `sigmas`, which must be positive
+    pub fn from_sigmas(sigmas: &OVector<T, D>) -> Self {
+        assert!(
+            sigmas.iter().all(|s| *s > T::zero()),
+            "Sigmas of a diagonal noise model must be positive"
+        );
+
+        Diagonal {
+            dim: sigmas.len(),
+            sigmas_: sigmas.clone(),
+            invsigmas_: sigmas.map(|s| T::one() / s),
+            precisions_: sigmas.map(|s| T::one() / (s * s)),
+        }
+    }
+
+    /// A diagonal noise model with the given variances
+    pub fn from_variances(variances: &OVector<T, D>) -> Self {
+        Diagonal::from_sigmas(&variances.map(|v| v.sqrt()))
+    }
+
+    /// A diagonal noise model with the given precisions, i.e. inverse variances
+    pub fn from_precisions(precisions: &OVector<T, D>) -> Self {
+        Diagonal::from_sigmas(&precisions.map(|p| T::one() / p.sqrt()))
+    }
+
+    pub fn invsigmas(&self) -> &OVector<T, D> {
+        &self.invsigmas_
+    }
+
+    pub fn precisions(&self) -> &OVector<T, D> {
+        &self.precisions_
+    }
+}
+
 #[allow(non_snake_case)]
 impl<D: Dim, T: RealField + Copy> GaussianNoise<D, T> for Diagonal<D, T>
 where
@@ -68,12 +106,13 @@ impl<D: Dim, T: RealField + Copy> NoiseModel<D, T> for Diagonal<D, T>
 where
     DefaultAllocator: Allocator<T, D>,
 {
+    /// Sigmas are always positive, see `Diagonal::from_sigmas`
     fn is_constrained(&self) -> bool {
-        unimplemented!()
+        false
     }
 
     fn is_unit(&self) -> bool {
-        unimplemented!()
+        self.sigmas_.iter().all(|s| *s == T::one())
     }
 
     fn dim(&self) -> usize {
@@ -81,41 +120,72 @@ where
     }
 
     fn sigmas(&self) -> DVector<T> {
-        unimplemented!()
+        DVector::from_iterator(self.dim, self.sigmas_.iter().cloned())
     }
 
-    fn whiten(&self, _v: &OVector<T, D>) -> OVector<T, D>
+    fn whiten(&self, v: &OVector<T, D>) -> OVector<T, D>
     where
         DefaultAllocator: Allocator<T, D>,
     {
-        unimplemented!()
+        v.component_mul(&self.invsigmas_)
     }
 
-    fn whiten_mat(&self, _m: &OMatrix<T, D, D>) -> OMatrix<T, D, D>
+    /// Scales the rows of `m`, which may have any number of columns if `D` is `Dynamic`
+    fn whiten_mat(&self, m: &OMatrix<T, D, D>) -> OMatrix<T, D, D>
     where
         DefaultAllocator: Allocator<T, D, D>,
     {
-        unimplemented!()
+        let mut res = m.clone();
+        for (i, mut row) in res.row_iter_mut().enumerate() {
+            row *= self.invsigmas_[i];
+        }
+        res
     }
 
-    fn unwhiten(&self, _v: &OVector<T, D>) -> OVector<T, D>
+    fn unwhiten(&self, v: &OVector<T, D>) -> OVector<T, D>
     where
         DefaultAllocator: Allocator<T, D>,
     {
-        unimplemented!()
+        v.component_mul(&self.sigmas_)
     }
 
-    fn distance(&self, _v: &OVector<T, D>) -> T
+    fn distance(&self, v: &OVector<T, D>) -> T
     where
         DefaultAllocator: Allocator<T, D>,
     {
-        unimplemented!()
+        self.mahalanobis_dist(v)
     }
 
     fn whiten_system<_D: Dim>(&self, _A: &[DMatrix<T>], _b: &OVector<T, _D>)
     where
+        Self: Sized,
         DefaultAllocator: Allocator<T, _D>,
     {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{Dynamic, Vector3};
+
+    #[test]
+    fn diagonal_whitening() {
+        let model = Diagonal::from_sigmas(&Vector3::new(0.5, 2.0, 1.0));
+        let v = Vector3::new(1.0, 1.0, 3.0);
+
+        assert_relative_eq!(model.whiten(&v), Vector3::new(2.0, 0.5, 3.0));
+        assert_relative_eq!(model.unwhiten(&model.whiten(&v)), v);
+        assert_relative_eq!(model.distance(&v), 4.0 + 0.25 + 9.0);
+        assert!(!model.is_unit());
+
+        // Dynamic models whiten the rows of matrices with any number of columns
+        let model = Diagonal::<Dynamic>::from_variances(&DVector::from_vec(vec![4.0, 1.0]));
+        let m = DMatrix::from_row_slice(2, 3, &[2.0, 4.0, 6.0, 1.0, 2.0, 3.0]);
+        assert_relative_eq!(
+            model.whiten_mat(&m),
+            DMatrix::from_row_slice(2, 3, &[1.0, 2.0, 3.0, 1.0, 2.0, 3.0])
+        );
+    }
+}
@@ -29,7 +29,7 @@ where
         let (m, n) = (R.nrows(), R.ncols());
         assert_eq!(m, n, "Non-square Matrix");
         if smart {
-            if let Some(_diagonal) = check_diagonal_upper(&R) {
+            if let Some(_diagonal) = check_diagonal_upper(R) {
                 unimplemented!();
             }
         }
@@ -53,10 +53,10 @@ where
         let llt = Cholesky::new(info.clone()).unwrap();
         let R = llt.l_dirty();
 
-        return Gaussian {
+        Gaussian {
             dim: m,
             sqrt_info: Some(R.transpose()),
-        };
+        }
     }
 
     fn from_covariance(cov: &OMatrix<T, D, D>, smart: bool) -> Self
@@ -161,6 +161,7 @@ where
 
     fn whiten_system<_D: Dim>(&self, _A: &[DMatrix<T>], _b: &OVector<T, _D>)
     where
+        Self: Sized,
         DefaultAllocator: Allocator<T, D, D> + Allocator<T, _D>,
     {
         unimplemented!()
@@ -14,6 +14,33 @@ pub struct Isotropic<D: Dim, T: RealField + Copy = f64> {
     _phantom: std::marker::PhantomData<D>,
 }
 
+impl<D: Dim, T: RealField + Copy> Isotropic<D, T> {
+    /// An isotropic noise model of dimension `dim` with standard deviation `sigma`, which must
+    /// be positive
+    pub fn from_sigma(dim: usize, sigma: T) -> Self {
+        assert!(
+            sigma > T::zero(),
+            "Sigma of an isotropic noise model must be positive"
+        );
+
+        Isotropic {
+            dim,
+            sigma_: sigma,
+            invsigma_: T::one() / sigma,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// An isotropic noise model of dimension `dim` with the given variance
+    pub fn from_variance(dim: usize, variance: T) -> Self {
+        Isotropic::from_sigma(dim, variance.sqrt())
+    }
+
+    pub fn sigma(&self) -> T {
+        self.sigma_
+    }
+}
+
 #[allow(non_snake_case)]
 impl<D: Dim, T: RealField + Copy> GaussianNoise<D, T> for Isotropic<D, T>
 where
@@ -63,12 +90,13 @@ impl<D: Dim, T: RealField + Copy> NoiseModel<D, T> for Isotropic<D, T>
 where
     DefaultAllocator: Allocator<T, D>,
 {
+    /// Sigma is always positive, see `Isotropic::from_sigma`
     fn is_constrained(&self) -> bool {
-        unimplemented!()
+        false
     }
 
     fn is_unit(&self) -> bool {
-        unimplemented!()
+        self.sigma_ == T::one()
     }
 
     fn dim(&self) -> usize {
@@ -76,7 +104,7 @@ where
     }
 
     fn sigmas(&self) -> DVector<T> {
-        unimplemented!()
+        DVector::from_element(self.dim, self.sigma_)
     }
 
     fn whiten(&self, v: &OVector<T, D>) -> OVector<T, D>
@@ -86,31 +114,54 @@ where
         v * self.invsigma_
     }
 
-    fn whiten_mat(&self, _m: &OMatrix<T, D, D>) -> OMatrix<T, D, D>
+    fn whiten_mat(&self, m: &OMatrix<T, D, D>) -> OMatrix<T, D, D>
     where
         DefaultAllocator: Allocator<T, D, D>,
     {
-        unimplemented!()
+        m * self.invsigma_
     }
 
-    fn unwhiten(&self, _v: &OVector<T, D>) -> OVector<T, D>
+    fn unwhiten(&self, v: &OVector<T, D>) -> OVector<T, D>
     where
         DefaultAllocator: Allocator<T, D>,
     {
-        unimplemented!()
+        v * self.sigma_
     }
 
-    fn distance(&self, _v: &OVector<T, D>) -> T
+    fn distance(&self, v: &OVector<T, D>) -> T
     where
         DefaultAllocator: Allocator<T, D>,
     {
-        unimplemented!()
+        self.mahalanobis_dist(v)
     }
 
     fn whiten_system<_D: Dim>(&self, _A: &[DMatrix<T>], _b: &OVector<T, _D>)
     where
+        Self: Sized,
         DefaultAllocator: Allocator<T, _D>,
     {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{Dynamic, Vector2};
+
+    #[test]
+    fn isotropic_whitening() {
+        let model = Isotropic::from_sigma(2, 0.5);
+        let v = Vector2::new(1.0, -2.0);
+
+        assert_relative_eq!(model.whiten(&v), Vector2::new(2.0, -4.0));
+        assert_relative_eq!(model.unwhiten(&model.whiten(&v)), v);
+        assert_relative_eq!(model.distance(&v), 20.0);
+        assert_relative_eq!(model.sigmas(), DVector::from_vec(vec![0.5, 0.5]));
+        assert!(!model.is_unit());
+
+        let model = Isotropic::<Dynamic>::from_variance(2, 4.0);
+        let m = DMatrix::from_row_slice(2, 3, &[2.0, 4.0, 6.0, 1.0, 2.0, 3.0]);
+        assert_relative_eq!(model.whiten_mat(&m), m / 2.0);
+    }
+}
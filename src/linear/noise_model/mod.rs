@@ -43,6 +43,7 @@ pub trait NoiseModel<D: Dim, T: RealField + Copy = f64>: Debug {
 
     fn whiten_system<_D: Dim>(&self, A: &[DMatrix<T>], b: &OVector<T, _D>)
     where
+        Self: Sized,
         DefaultAllocator: Allocator<T, _D>;
 }
 
@@ -111,9 +112,9 @@ mod tests {
     #[test]
     fn check_upper_diagonal() {
         let mat = Matrix4::<f64>::identity();
-        assert_eq!(check_diagonal_upper(&mat).is_some(), true);
+        assert!(check_diagonal_upper(&mat).is_some());
 
-        let eps = std::f64::EPSILON;
+        let eps = f64::EPSILON;
 
         let mat1 = DMatrix::from_row_slice(
             4,
@@ -126,9 +127,9 @@ mod tests {
             ],
         );
 
-        assert_eq!(check_diagonal_upper(&mat1).is_some(), true);
+        assert!(check_diagonal_upper(&mat1).is_some());
 
-        let eps2 = 2.0 * std::f64::EPSILON;
+        let eps2 = 2.0 * f64::EPSILON;
         let mat2 = DMatrix::from_row_slice(
             4,
             3, // dim
@@ -141,6 +142,6 @@ mod tests {
         );
 
         println!("{:}", mat2);
-        assert_eq!(check_diagonal_upper(&mat2).is_some(), false);
+        assert!(check_diagonal_upper(&mat2).is_none());
     }
 }
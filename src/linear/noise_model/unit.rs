@@ -18,6 +18,32 @@ where
     precisions_: OVector<T, D>,
 }
 
+impl<D: Dim, T: RealField + Copy> Unit<D, T>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// A unit noise model of dimension `dim`, whose whitening is the identity
+    pub fn new(dim: usize) -> Self {
+        let ones =
+            OVector::from_element_generic(D::from_usize(dim), nalgebra::Const::<1>, T::one());
+
+        Unit {
+            dim,
+            sigmas_: ones.clone(),
+            invsigmas_: ones.clone(),
+            precisions_: ones,
+        }
+    }
+
+    pub fn invsigmas(&self) -> &OVector<T, D> {
+        &self.invsigmas_
+    }
+
+    pub fn precisions(&self) -> &OVector<T, D> {
+        &self.precisions_
+    }
+}
+
 #[allow(non_snake_case)]
 impl<D: Dim, T: RealField + Copy> GaussianNoise<D, T> for Unit<D, T>
 where
@@ -69,11 +95,11 @@ where
     DefaultAllocator: Allocator<T, D>,
 {
     fn is_constrained(&self) -> bool {
-        unimplemented!()
+        false
     }
 
     fn is_unit(&self) -> bool {
-        unimplemented!()
+        true
     }
 
     fn dim(&self) -> usize {
@@ -81,41 +107,60 @@ where
     }
 
     fn sigmas(&self) -> DVector<T> {
-        unimplemented!()
+        DVector::from_iterator(self.dim, self.sigmas_.iter().cloned())
     }
 
-    fn whiten(&self, _v: &OVector<T, D>) -> OVector<T, D>
+    fn whiten(&self, v: &OVector<T, D>) -> OVector<T, D>
     where
         DefaultAllocator: Allocator<T, D>,
     {
-        unimplemented!()
+        v.clone()
     }
 
-    fn whiten_mat(&self, _m: &OMatrix<T, D, D>) -> OMatrix<T, D, D>
+    fn whiten_mat(&self, m: &OMatrix<T, D, D>) -> OMatrix<T, D, D>
     where
         DefaultAllocator: Allocator<T, D, D>,
     {
-        unimplemented!()
+        m.clone()
     }
 
-    fn unwhiten(&self, _v: &OVector<T, D>) -> OVector<T, D>
+    fn unwhiten(&self, v: &OVector<T, D>) -> OVector<T, D>
     where
         DefaultAllocator: Allocator<T, D>,
     {
-        unimplemented!()
+        v.clone()
     }
 
-    fn distance(&self, _v: &OVector<T, D>) -> T
+    fn distance(&self, v: &OVector<T, D>) -> T
     where
         DefaultAllocator: Allocator<T, D>,
     {
-        unimplemented!()
+        v.dot(v)
     }
 
     fn whiten_system<_D: Dim>(&self, _A: &[DMatrix<T>], _b: &OVector<T, _D>)
     where
+        Self: Sized,
         DefaultAllocator: Allocator<T, _D>,
     {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn unit_whitening_is_identity() {
+        let model = Unit::new(3);
+        let v = Vector3::new(1.0, -2.0, 2.0);
+
+        assert_eq!(model.whiten(&v), v);
+        assert_eq!(model.unwhiten(&v), v);
+        assert_relative_eq!(model.distance(&v), 9.0);
+        assert_eq!(model.sigmas(), DVector::from_element(3, 1.0));
+        assert!(model.is_unit());
+    }
+}
@@ -0,0 +1,295 @@
+use crate::inference::factor::KeyType;
+use nalgebra as na;
+use std::collections::BTreeMap;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// A collection of dynamically sized vectors indexed by key, e.g. the solution of a linear
+/// system or an update to a set of nonlinear values
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VectorValues {
+    values: BTreeMap<KeyType, na::DVector<f64>>,
+}
+
+impl VectorValues {
+    pub fn new() -> Self {
+        VectorValues {
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// Zero vectors of the given dimensions
+    pub fn zero(dims: &BTreeMap<KeyType, usize>) -> Self {
+        VectorValues {
+            values: dims
+                .iter()
+                .map(|(key, dim)| (*key, na::DVector::zeros(*dim)))
+                .collect(),
+        }
+    }
+
+    /// Splits `v` into consecutive vectors for the keys of `ordering`, with the dimensions
+    /// from `dims`
+    pub fn from_vector(
+        v: &na::DVector<f64>,
+        ordering: &[KeyType],
+        dims: &BTreeMap<KeyType, usize>,
+    ) -> Self {
+        let mut values = VectorValues::new();
+
+        let mut row = 0;
+        for key in ordering {
+            let dim = *dims
+                .get(key)
+                .unwrap_or_else(|| panic!("Key {} has no dimension", key));
+            assert!(row + dim <= v.len(), "Vector is too short for the ordering");
+            values.insert(*key, v.rows(row, dim).into_owned());
+            row += dim;
+        }
+        assert_eq!(row, v.len(), "Vector is too long for the ordering");
+
+        values
+    }
+
+    /// The vectors of the keys of `ordering` concatenated in that order
+    pub fn vector(&self, ordering: &[KeyType]) -> na::DVector<f64> {
+        let dim = ordering.iter().map(|key| self.at(*key).len()).sum();
+        let mut v = na::DVector::zeros(dim);
+
+        let mut row = 0;
+        for key in ordering {
+            let value = self.at(*key);
+            v.rows_mut(row, value.len()).copy_from(value);
+            row += value.len();
+        }
+
+        v
+    }
+
+    /// Inserts the vector of `key`, which must not be present yet
+    pub fn insert(&mut self, key: KeyType, value: na::DVector<f64>) {
+        let previous = self.values.insert(key, value);
+        assert!(previous.is_none(), "Key {} is already present", key);
+    }
+
+    /// The vector of `key`, which must be present
+    pub fn at(&self, key: KeyType) -> &na::DVector<f64> {
+        self.values
+            .get(&key)
+            .unwrap_or_else(|| panic!("Key {} is not present", key))
+    }
+
+    pub fn at_mut(&mut self, key: KeyType) -> &mut na::DVector<f64> {
+        self.values
+            .get_mut(&key)
+            .unwrap_or_else(|| panic!("Key {} is not present", key))
+    }
+
+    pub fn get(&self, key: KeyType) -> Option<&na::DVector<f64>> {
+        self.values.get(&key)
+    }
+
+    pub fn exists(&self, key: KeyType) -> bool {
+        self.values.contains_key(&key)
+    }
+
+    /// Number of vectors
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The keys in increasing order
+    pub fn keys(&self) -> impl Iterator<Item = KeyType> + '_ {
+        self.values.keys().cloned()
+    }
+
+    /// The `(key, vector)` pairs in increasing key order
+    pub fn iter(&self) -> impl Iterator<Item = (KeyType, &na::DVector<f64>)> + '_ {
+        self.values.iter().map(|(k, v)| (*k, v))
+    }
+
+    /// The dimension of every vector
+    pub fn dims(&self) -> BTreeMap<KeyType, usize> {
+        self.values.iter().map(|(k, v)| (*k, v.len())).collect()
+    }
+
+    /// Total dimension of all vectors
+    pub fn dim(&self) -> usize {
+        self.values.values().map(|v| v.len()).sum()
+    }
+
+    /// Whether both contain the same keys with the same dimensions
+    pub fn has_same_structure(&self, other: &VectorValues) -> bool {
+        self.values.len() == other.values.len()
+            && self
+                .values
+                .iter()
+                .zip(other.values.iter())
+                .all(|((k1, v1), (k2, v2))| k1 == k2 && v1.len() == v2.len())
+    }
+
+    fn assert_same_structure(&self, other: &VectorValues) {
+        assert!(
+            self.has_same_structure(other),
+            "VectorValues have different keys or dimensions"
+        );
+    }
+
+    /// The inner product, summed over all keys
+    pub fn dot(&self, other: &VectorValues) -> f64 {
+        self.assert_same_structure(other);
+
+        self.values
+            .values()
+            .zip(other.values.values())
+            .map(|(a, b)| a.dot(b))
+            .sum()
+    }
+
+    pub fn norm_squared(&self) -> f64 {
+        self.values.values().map(|v| v.norm_squared()).sum()
+    }
+
+    pub fn norm(&self) -> f64 {
+        self.norm_squared().sqrt()
+    }
+
+    /// `alpha * self`
+    pub fn scale(&self, alpha: f64) -> VectorValues {
+        VectorValues {
+            values: self.values.iter().map(|(k, v)| (*k, v * alpha)).collect(),
+        }
+    }
+
+    /// `self += alpha * other`
+    pub fn axpy(&mut self, alpha: f64, other: &VectorValues) {
+        self.assert_same_structure(other);
+
+        for (a, b) in self.values.values_mut().zip(other.values.values()) {
+            a.axpy(alpha, b, 1.0);
+        }
+    }
+}
+
+impl<'a> Add<&'a VectorValues> for &'a VectorValues {
+    type Output = VectorValues;
+
+    fn add(self, rhs: &'a VectorValues) -> VectorValues {
+        let mut res = self.clone();
+        res += rhs;
+        res
+    }
+}
+
+impl<'a> Sub<&'a VectorValues> for &'a VectorValues {
+    type Output = VectorValues;
+
+    fn sub(self, rhs: &'a VectorValues) -> VectorValues {
+        let mut res = self.clone();
+        res -= rhs;
+        res
+    }
+}
+
+impl<'a> AddAssign<&'a VectorValues> for VectorValues {
+    fn add_assign(&mut self, rhs: &'a VectorValues) {
+        self.axpy(1.0, rhs);
+    }
+}
+
+impl<'a> SubAssign<&'a VectorValues> for VectorValues {
+    fn sub_assign(&mut self, rhs: &'a VectorValues) {
+        self.axpy(-1.0, rhs);
+    }
+}
+
+impl Neg for &VectorValues {
+    type Output = VectorValues;
+
+    fn neg(self) -> VectorValues {
+        self.scale(-1.0)
+    }
+}
+
+impl Mul<f64> for &VectorValues {
+    type Output = VectorValues;
+
+    fn mul(self, alpha: f64) -> VectorValues {
+        self.scale(alpha)
+    }
+}
+
+impl MulAssign<f64> for VectorValues {
+    fn mul_assign(&mut self, alpha: f64) {
+        for v in self.values.values_mut() {
+            *v *= alpha;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::DVector;
+
+    fn example() -> VectorValues {
+        let mut x = VectorValues::new();
+        x.insert(7, DVector::from_vec(vec![1.0, 2.0]));
+        x.insert(2, DVector::from_vec(vec![3.0]));
+        x.insert(5, DVector::from_vec(vec![-1.0, 0.0, 4.0]));
+        x
+    }
+
+    #[test]
+    fn vector_space_operations() {
+        let x = example();
+        let y = x.scale(2.0);
+
+        assert_relative_eq!(x.dot(&y), 2.0 * 31.0);
+        assert_relative_eq!(x.norm(), 31f64.sqrt());
+        assert_relative_eq!((&y - &x).at(5).clone(), x.at(5).clone());
+        assert_relative_eq!((&x + &y).at(7).clone(), DVector::from_vec(vec![3.0, 6.0]));
+        assert_relative_eq!((-&x).at(2)[0], -3.0);
+
+        let mut z = &x * 0.5;
+        z *= 4.0;
+        z -= &y;
+        assert_relative_eq!(z.norm(), 0.0);
+        z.axpy(3.0, &x);
+        assert_eq!(z, x.scale(3.0));
+    }
+
+    #[test]
+    fn zero_and_dims() {
+        let x = example();
+        let dims = x.dims();
+        assert_eq!(x.dim(), 6);
+        assert_eq!(dims[&5], 3);
+
+        let zero = VectorValues::zero(&dims);
+        assert!(zero.has_same_structure(&x));
+        assert_eq!(zero.norm(), 0.0);
+        assert_eq!(&zero + &x, x);
+    }
+
+    #[test]
+    fn concatenated_vector_round_trip() {
+        let x = example();
+        let ordering = [5, 7, 2];
+
+        let v = x.vector(&ordering);
+        assert_eq!(v, DVector::from_vec(vec![-1.0, 0.0, 4.0, 1.0, 2.0, 3.0]));
+        assert_eq!(VectorValues::from_vector(&v, &ordering, &x.dims()), x);
+    }
+
+    #[test]
+    #[should_panic]
+    fn mismatched_structure_panics() {
+        let mut y = VectorValues::new();
+        y.insert(7, DVector::from_vec(vec![1.0]));
+        example().dot(&y);
+    }
+}
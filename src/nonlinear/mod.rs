@@ -1 +1,5 @@
 pub mod nonlinear_factor;
+pub mod values;
+
+pub use nonlinear_factor::{FactorVariables, NoiseModelFactor, NoiseModelFactorN};
+pub use values::{Value, Values, ValuesError};
@@ -1 +1,454 @@
+use crate::inference::factor::{Factor, KeyType, NonlinearFactor};
+use crate::inference::FactorKeys;
+use crate::linear::jacobian::JacobianFactor;
+use crate::linear::noise_model::NoiseModel;
+use crate::nonlinear::values::{Value, Values, ValuesError};
+use nalgebra as na;
 
+fn whiten(model: Option<&dyn NoiseModel<na::Dynamic>>, e: na::DVector<f64>) -> na::DVector<f64> {
+    match model {
+        Some(model) => model.whiten(&e),
+        None => e,
+    }
+}
+
+/// A nonlinear factor `0.5 * || W h(x) ||^2`, where `h` is a vector-valued error function of
+/// the values of the keys of the factor and the whitening `W` comes from an optional noise
+/// model, which is the identity if there is none
+#[allow(non_snake_case)]
+pub trait NoiseModelFactor: NonlinearFactor + Send + Sync {
+    fn noise_model(&self) -> Option<&dyn NoiseModel<na::Dynamic>>;
+
+    /// The unwhitened error `h(x)` at `values`.
+    ///
+    /// If `H` is given it has one matrix per key, which is set to the Jacobian of `h` wrpt the
+    /// tangent space of the value of that key.
+    fn evaluate_error(
+        &self,
+        values: &Values,
+        H: Option<&mut [na::DMatrix<f64>]>,
+    ) -> Result<na::DVector<f64>, ValuesError>;
+
+    fn whitened_error(&self, values: &Values) -> Result<na::DVector<f64>, ValuesError> {
+        let e = self.evaluate_error(values, None)?;
+        Ok(whiten(self.noise_model(), e))
+    }
+
+    fn error(&self, values: &Values) -> Result<f64, ValuesError> {
+        Ok(0.5 * self.whitened_error(values)?.norm_squared())
+    }
+
+    /// The linear factor `0.5 * || A dx - b ||^2` on the tangent spaces at `values`, with
+    /// `A_j = W H_j` and `b = -W h(x)`, so that it approximates the error at the values
+    /// retracted by `dx`
+    fn linearize(&self, values: &Values) -> Result<JacobianFactor, ValuesError> {
+        let keys: Vec<KeyType> = self.key_iter().collect();
+        let mut H = vec![na::DMatrix::zeros(0, 0); keys.len()];
+        let e = self.evaluate_error(values, Some(&mut H))?;
+
+        let model = self.noise_model();
+        let mut terms = Vec::with_capacity(keys.len());
+        for (key, H) in keys.into_iter().zip(H) {
+            let dim = values.at_dyn(key)?.dim();
+            assert_eq!(
+                H.shape(),
+                (e.len(), dim),
+                "Jacobian of key {} has the wrong shape",
+                key
+            );
+
+            let A = match model {
+                Some(model) => model.whiten_mat(&H),
+                None => H,
+            };
+            terms.push((key, A));
+        }
+
+        Ok(JacobianFactor::new(terms, -whiten(model, e), None))
+    }
+}
+
+/// The value types of the keys of a `NoiseModelFactorN`, as a tuple of 1 to 6 `Value`s
+pub trait FactorVariables {
+    const N: usize;
+
+    /// A tuple of references to the values
+    type Refs<'a>;
+
+    /// A tuple of optional Jacobians, one per value
+    type Jacobians<'a>;
+
+    fn fetch<'a>(values: &'a Values, keys: &[KeyType]) -> Result<Self::Refs<'a>, ValuesError>;
+
+    #[allow(non_snake_case)]
+    fn jacobians(H: Option<&mut [na::DMatrix<f64>]>) -> Self::Jacobians<'_>;
+}
+
+/// Expands to the tokens after the first, once per repetition of the first
+macro_rules! replace_with(
+    ($_ignored: tt, $($tokens: tt)*) => { $($tokens)* }
+);
+
+macro_rules! impl_factor_variables(
+    ($n: expr; $($T: ident $i: tt),+) => {
+        impl<$($T: Value),+> FactorVariables for ($($T,)+) {
+            const N: usize = $n;
+
+            type Refs<'a> = ($(&'a $T,)+);
+
+            type Jacobians<'a> = ($(replace_with!($T, Option<&'a mut na::DMatrix<f64>>),)+);
+
+            fn fetch<'a>(
+                values: &'a Values,
+                keys: &[KeyType],
+            ) -> Result<Self::Refs<'a>, ValuesError> {
+                assert_eq!(keys.len(), $n, "Factor needs exactly {} keys", $n);
+                Ok(($(values.at::<$T>(keys[$i])?,)+))
+            }
+
+            #[allow(non_snake_case)]
+            fn jacobians(H: Option<&mut [na::DMatrix<f64>]>) -> Self::Jacobians<'_> {
+                if let Some(H) = &H {
+                    assert_eq!(H.len(), $n, "Need one Jacobian per key");
+                }
+                let mut H = H.map(|H| H.iter_mut());
+                ($(replace_with!($T, H.as_mut().and_then(|H| H.next())),)+)
+            }
+        }
+    }
+);
+
+impl_factor_variables!(1; T1 0);
+impl_factor_variables!(2; T1 0, T2 1);
+impl_factor_variables!(3; T1 0, T2 1, T3 2);
+impl_factor_variables!(4; T1 0, T2 1, T3 2, T4 3);
+impl_factor_variables!(5; T1 0, T2 1, T3 2, T4 3, T5 4);
+impl_factor_variables!(6; T1 0, T2 1, T3 2, T4 3, T5 4, T6 5);
+
+/// A `NoiseModelFactor` on a fixed number of keys with values of known types, e.g.
+/// `Variables = (SE3<f64>, SE3<f64>)` for a factor between two poses.
+///
+/// The keys are those of the `Factor` implementation, in the order of `Variables`.
+#[allow(non_snake_case)]
+pub trait NoiseModelFactorN: Factor + Send + Sync {
+    type Variables: FactorVariables;
+
+    /// The noise model, see `NoiseModelFactor::noise_model`
+    fn model(&self) -> Option<&dyn NoiseModel<na::Dynamic>>;
+
+    /// The unwhitened error of the values, setting every given Jacobian as in
+    /// `NoiseModelFactor::evaluate_error`
+    fn evaluate(
+        &self,
+        x: <Self::Variables as FactorVariables>::Refs<'_>,
+        H: <Self::Variables as FactorVariables>::Jacobians<'_>,
+    ) -> na::DVector<f64>;
+}
+
+impl<F: NoiseModelFactorN> NonlinearFactor for F {}
+
+#[allow(non_snake_case)]
+impl<F: NoiseModelFactorN> NoiseModelFactor for F {
+    fn noise_model(&self) -> Option<&dyn NoiseModel<na::Dynamic>> {
+        self.model()
+    }
+
+    fn evaluate_error(
+        &self,
+        values: &Values,
+        H: Option<&mut [na::DMatrix<f64>]>,
+    ) -> Result<na::DVector<f64>, ValuesError> {
+        let keys: Vec<KeyType> = self.key_iter().collect();
+        let x = F::Variables::fetch(values, &keys)?;
+        Ok(self.evaluate(x, F::Variables::jacobians(H)))
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use crate::core::group::LieGroup;
+    use crate::core::manifold::Manifold;
+    use crate::geometry::se2::SE2;
+    use crate::inference::FactorGraph;
+    use crate::linear::noise_model::{Diagonal, Isotropic};
+    use crate::linear::{GaussianFactorGraph, GaussianLikeFactor, VectorValues};
+    use nalgebra::allocator::Allocator;
+    use nalgebra::{DMatrix, DVector, DefaultAllocator, Dynamic, Vector2, Vector3};
+    use std::io::{Error, ErrorKind};
+
+    fn key_at(keys: &[KeyType], index: usize) -> Result<KeyType, Error> {
+        keys.get(index)
+            .cloned()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Key index out of range"))
+    }
+
+    fn to_dynamic(m: &[f64], rows: usize) -> DMatrix<f64> {
+        DMatrix::from_column_slice(rows, m.len() / rows, m)
+    }
+
+    /// `local(prior, x)` on any manifold
+    #[derive(Debug)]
+    struct PriorFactor<T> {
+        key: KeyType,
+        prior: T,
+        model: Diagonal<Dynamic>,
+    }
+
+    impl<T: std::fmt::Debug> Factor for PriorFactor<T> {
+        fn num_keys(&self) -> usize {
+            1
+        }
+
+        fn key_at(&self, index: usize) -> Result<KeyType, Error> {
+            key_at(&[self.key], index)
+        }
+    }
+
+    impl<T> NoiseModelFactorN for PriorFactor<T>
+    where
+        T: Value + Manifold<Field = f64>,
+        DefaultAllocator:
+            Allocator<f64, T::TangentDim> + Allocator<f64, T::TangentDim, T::TangentDim>,
+    {
+        type Variables = (T,);
+
+        fn model(&self) -> Option<&dyn NoiseModel<Dynamic>> {
+            Some(&self.model)
+        }
+
+        fn evaluate(&self, (x,): (&T,), (H,): (Option<&mut DMatrix<f64>>,)) -> DVector<f64> {
+            let mut D = na::OMatrix::<f64, T::TangentDim, T::TangentDim>::zeros();
+            let e = T::local_with_derivative(&self.prior, x, None, Some(&mut D));
+            if let Some(H) = H {
+                *H = to_dynamic(D.as_slice(), <T as Manifold>::dim());
+            }
+            DVector::from_column_slice(e.as_slice())
+        }
+    }
+
+    /// `local(measured, between(x1, x2))` on planar poses
+    #[derive(Debug)]
+    struct BetweenFactor {
+        keys: [KeyType; 2],
+        measured: SE2<f64>,
+        model: Isotropic<Dynamic>,
+    }
+
+    impl Factor for BetweenFactor {
+        fn num_keys(&self) -> usize {
+            2
+        }
+
+        fn key_at(&self, index: usize) -> Result<KeyType, Error> {
+            key_at(&self.keys, index)
+        }
+    }
+
+    impl NoiseModelFactorN for BetweenFactor {
+        type Variables = (SE2<f64>, SE2<f64>);
+
+        fn model(&self) -> Option<&dyn NoiseModel<Dynamic>> {
+            Some(&self.model)
+        }
+
+        fn evaluate(
+            &self,
+            (x1, x2): (&SE2<f64>, &SE2<f64>),
+            (H1, H2): (Option<&mut DMatrix<f64>>, Option<&mut DMatrix<f64>>),
+        ) -> DVector<f64> {
+            let (mut D1, mut D2, mut D_local) = Default::default();
+            let b = x1.between_with_derivative(x2, Some(&mut D1), Some(&mut D2));
+            let e = SE2::local_with_derivative(&self.measured, &b, None, Some(&mut D_local));
+            if let Some(H1) = H1 {
+                *H1 = to_dynamic((D_local * D1).as_slice(), 3);
+            }
+            if let Some(H2) = H2 {
+                *H2 = to_dynamic((D_local * D2).as_slice(), 3);
+            }
+            DVector::from_column_slice(e.as_slice())
+        }
+    }
+
+    /// `x1 + x2 - x3` on planar points, without a noise model
+    #[derive(Debug)]
+    struct SumFactor {
+        keys: [KeyType; 3],
+    }
+
+    impl Factor for SumFactor {
+        fn num_keys(&self) -> usize {
+            3
+        }
+
+        fn key_at(&self, index: usize) -> Result<KeyType, Error> {
+            key_at(&self.keys, index)
+        }
+    }
+
+    impl NoiseModelFactorN for SumFactor {
+        type Variables = (Vector2<f64>, Vector2<f64>, Vector2<f64>);
+
+        fn model(&self) -> Option<&dyn NoiseModel<Dynamic>> {
+            None
+        }
+
+        fn evaluate(
+            &self,
+            (x1, x2, x3): (&Vector2<f64>, &Vector2<f64>, &Vector2<f64>),
+            H: (
+                Option<&mut DMatrix<f64>>,
+                Option<&mut DMatrix<f64>>,
+                Option<&mut DMatrix<f64>>,
+            ),
+        ) -> DVector<f64> {
+            let I = DMatrix::identity(2, 2);
+            for (H, sign) in [(H.0, 1.0), (H.1, 1.0), (H.2, -1.0)] {
+                if let Some(H) = H {
+                    *H = &I * sign;
+                }
+            }
+            DVector::from_column_slice((x1 + x2 - x3).as_slice())
+        }
+    }
+
+    fn poses() -> Values {
+        let mut values = Values::new();
+        values
+            .insert(0, SE2::new(Vector2::new(0.1, -0.2), 0.3))
+            .unwrap();
+        values
+            .insert(1, SE2::new(Vector2::new(1.2, 0.4), 0.8))
+            .unwrap();
+        values
+    }
+
+    fn between() -> BetweenFactor {
+        BetweenFactor {
+            keys: [0, 1],
+            measured: SE2::new(Vector2::new(1.0, 0.0), 0.5),
+            model: Isotropic::from_sigma(3, 0.1),
+        }
+    }
+
+    #[test]
+    fn error_is_half_the_whitened_norm() {
+        let mut values = Values::new();
+        values.insert(7, Vector3::new(1.0, 2.0, 3.0)).unwrap();
+        let factor = PriorFactor {
+            key: 7,
+            prior: Vector3::new(1.0, 1.0, 1.0),
+            model: Diagonal::from_sigmas(&DVector::from_vec(vec![1.0, 0.5, 2.0])),
+        };
+
+        assert_eq!(
+            factor.evaluate_error(&values, None).unwrap(),
+            DVector::from_vec(vec![0.0, 1.0, 2.0])
+        );
+        assert_relative_eq!(
+            factor.whitened_error(&values).unwrap(),
+            DVector::from_vec(vec![0.0, 2.0, 1.0])
+        );
+        assert_relative_eq!(factor.error(&values).unwrap(), 2.5);
+
+        let linear = factor.linearize(&values).unwrap();
+        let (A, b) = linear.jacobian();
+        assert_relative_eq!(
+            A,
+            DMatrix::from_diagonal(&DVector::from_vec(vec![1.0, 2.0, 0.5]))
+        );
+        assert_relative_eq!(b, DVector::from_vec(vec![0.0, -2.0, -1.0]));
+    }
+
+    #[test]
+    fn linearization_predicts_retracted_error() {
+        let factor = between();
+        let values = poses();
+        let linear = factor.linearize(&values).unwrap();
+        assert_eq!(linear.key_iter().collect::<Vec<_>>(), vec![0, 1]);
+
+        let mut delta = VectorValues::new();
+        delta.insert(0, DVector::from_vec(vec![1e-4, -2e-4, 3e-4]));
+        delta.insert(1, DVector::from_vec(vec![-2e-4, 1e-4, 2e-4]));
+
+        let (A, b) = linear.jacobian();
+        let predicted = A * delta.vector(&[0, 1]) - b;
+        let actual = factor.whitened_error(&values.retract(&delta)).unwrap();
+        assert_relative_eq!(predicted, actual, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn variables_of_three_keys() {
+        let mut values = Values::new();
+        values.insert(0, Vector2::new(1.0, 2.0)).unwrap();
+        values.insert(1, Vector2::new(3.0, 4.0)).unwrap();
+        values.insert(2, Vector2::new(4.0, 5.0)).unwrap();
+        let factor = SumFactor { keys: [0, 1, 2] };
+
+        assert_relative_eq!(factor.error(&values).unwrap(), 0.5);
+
+        let (A, b) = factor.linearize(&values).unwrap().jacobian();
+        assert_eq!(A.columns(4, 2), -DMatrix::identity(2, 2));
+        assert_eq!(b, DVector::from_vec(vec![0.0, -1.0]));
+    }
+
+    #[test]
+    fn type_mismatch_is_an_error() {
+        let mut values = poses();
+        values.erase(1).unwrap();
+        values.insert(1, Vector3::new(1.0, 2.0, 0.5)).unwrap();
+
+        let factor = between();
+        assert!(matches!(
+            factor.error(&values),
+            Err(ValuesError::TypeMismatch { key: 1, .. })
+        ));
+        assert!(matches!(
+            factor.linearize(&Values::new()),
+            Err(ValuesError::KeyMissing(0))
+        ));
+    }
+
+    #[test]
+    fn gauss_newton_on_a_pose_chain() {
+        let factors: Vec<Box<dyn NoiseModelFactor>> = vec![
+            Box::new(PriorFactor {
+                key: 0,
+                prior: SE2::new(Vector2::new(0.0, 0.0), 0.0),
+                model: Diagonal::from_sigmas(&DVector::from_vec(vec![0.01, 0.01, 0.01])),
+            }),
+            Box::new(between()),
+            Box::new(BetweenFactor {
+                keys: [1, 2],
+                measured: SE2::new(Vector2::new(0.5, 0.5), -1.0),
+                model: Isotropic::from_sigma(3, 0.1),
+            }),
+        ];
+
+        let mut values = poses();
+        values
+            .insert(2, SE2::new(Vector2::new(1.5, 1.0), 0.0))
+            .unwrap();
+        let total_error =
+            |values: &Values| -> f64 { factors.iter().map(|f| f.error(values).unwrap()).sum() };
+        let initial_error = total_error(&values);
+
+        for _ in 0..10 {
+            let mut graph = GaussianFactorGraph::new();
+            for factor in factors.iter() {
+                graph.add(factor.linearize(&values).unwrap());
+            }
+            let delta = graph.optimize().unwrap();
+            values = values.retract(&delta);
+        }
+
+        assert!(initial_error > 1.0);
+        assert!(total_error(&values) < 1e-12);
+
+        let x2 = values.at::<SE2<f64>>(2).unwrap();
+        let expected =
+            SE2::new(Vector2::new(1.0, 0.0), 0.5) * SE2::new(Vector2::new(0.5, 0.5), -1.0);
+        assert_relative_eq!(SE2::local(&expected, x2), Vector3::zeros(), epsilon = 1e-9);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::core::manifold::Manifold;
+use crate::inference::factor::KeyType;
+use crate::linear::VectorValues;
+use nalgebra as na;
+use nalgebra::allocator::Allocator;
+use nalgebra::{DefaultAllocator, DimName};
+use std::any::{type_name, Any};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Errors from accessing or modifying `Values`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValuesError {
+    /// No value is stored for the key
+    KeyMissing(KeyType),
+    /// A value is already stored for the key
+    KeyExists(KeyType),
+    /// The value stored for the key is not of the requested type
+    TypeMismatch {
+        key: KeyType,
+        requested: &'static str,
+        stored: &'static str,
+    },
+}
+
+impl fmt::Display for ValuesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValuesError::KeyMissing(key) => write!(f, "no value for key {}", key),
+            ValuesError::KeyExists(key) => write!(f, "a value already exists for key {}", key),
+            ValuesError::TypeMismatch {
+                key,
+                requested,
+                stored,
+            } => write!(
+                f,
+                "the value for key {} is a {}, not a {}",
+                key, stored, requested
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValuesError {}
+
+/// A `Manifold` over `f64` behind a trait object, with tangent vectors as `DVector`s
+pub trait Value: Any + fmt::Debug + Send + Sync {
+    /// Dimension of the tangent space
+    fn dim(&self) -> usize;
+
+    fn retract_dyn(&self, v: &na::DVector<f64>) -> Box<dyn Value>;
+
+    /// Local coordinates of `other`, which must be of the same type
+    fn local_dyn(&self, other: &dyn Value) -> Option<na::DVector<f64>>;
+
+    fn clone_dyn(&self) -> Box<dyn Value>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn type_name(&self) -> &'static str;
+}
+
+impl<T> Value for T
+where
+    T: Manifold<Field = f64> + Clone + fmt::Debug + Send + Sync + 'static,
+    DefaultAllocator: Allocator<f64, T::TangentDim>,
+{
+    fn dim(&self) -> usize {
+        T::dim()
+    }
+
+    fn retract_dyn(&self, v: &na::DVector<f64>) -> Box<dyn Value> {
+        assert_eq!(
+            v.len(),
+            T::dim(),
+            "tangent vector of the wrong dimension for a {}",
+            type_name::<T>()
+        );
+        let v = na::OVector::<f64, T::TangentDim>::from_column_slice_generic(
+            T::TangentDim::name(),
+            na::Const::<1>,
+            v.as_slice(),
+        );
+        Box::new(T::retract(self, &v))
+    }
+
+    fn local_dyn(&self, other: &dyn Value) -> Option<na::DVector<f64>> {
+        let other = other.as_any().downcast_ref::<T>()?;
+        let v = T::local(self, other);
+        Some(na::DVector::from_column_slice(v.as_slice()))
+    }
+
+    fn clone_dyn(&self) -> Box<dyn Value> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+}
+
+/// A collection of values of any manifold type indexed by key, e.g. the variables of a
+/// nonlinear factor graph
+#[derive(Debug, Default)]
+pub struct Values {
+    values: BTreeMap<KeyType, Box<dyn Value>>,
+}
+
+impl Clone for Values {
+    fn clone(&self) -> Self {
+        Values {
+            values: self
+                .values
+                .iter()
+                .map(|(key, value)| (*key, value.clone_dyn()))
+                .collect(),
+        }
+    }
+}
+
+impl Values {
+    pub fn new() -> Self {
+        Values {
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert<T: Value>(&mut self, key: KeyType, value: T) -> Result<(), ValuesError> {
+        self.insert_boxed(key, Box::new(value))
+    }
+
+    pub fn insert_boxed(&mut self, key: KeyType, value: Box<dyn Value>) -> Result<(), ValuesError> {
+        if self.values.contains_key(&key) {
+            return Err(ValuesError::KeyExists(key));
+        }
+        self.values.insert(key, value);
+        Ok(())
+    }
+
+    /// Replaces the value of an existing key with one of the same type
+    pub fn update<T: Value>(&mut self, key: KeyType, value: T) -> Result<(), ValuesError> {
+        let stored = self
+            .values
+            .get_mut(&key)
+            .ok_or(ValuesError::KeyMissing(key))?;
+        if !stored.as_any().is::<T>() {
+            return Err(ValuesError::TypeMismatch {
+                key,
+                requested: type_name::<T>(),
+                stored: stored.type_name(),
+            });
+        }
+        *stored = Box::new(value);
+        Ok(())
+    }
+
+    /// Removes the value of `key`, returning it
+    pub fn erase(&mut self, key: KeyType) -> Result<Box<dyn Value>, ValuesError> {
+        self.values.remove(&key).ok_or(ValuesError::KeyMissing(key))
+    }
+
+    /// The value of `key` as a `T`
+    pub fn at<T: Value>(&self, key: KeyType) -> Result<&T, ValuesError> {
+        let stored = self.at_dyn(key)?;
+        stored
+            .as_any()
+            .downcast_ref::<T>()
+            .ok_or_else(|| ValuesError::TypeMismatch {
+                key,
+                requested: type_name::<T>(),
+                stored: stored.type_name(),
+            })
+    }
+
+    pub fn at_dyn(&self, key: KeyType) -> Result<&dyn Value, ValuesError> {
+        self.values
+            .get(&key)
+            .map(|value| value.as_ref())
+            .ok_or(ValuesError::KeyMissing(key))
+    }
+
+    pub fn exists(&self, key: KeyType) -> bool {
+        self.values.contains_key(&key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Keys in increasing order
+    pub fn keys(&self) -> impl Iterator<Item = KeyType> + '_ {
+        self.values.keys().cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (KeyType, &dyn Value)> + '_ {
+        self.values
+            .iter()
+            .map(|(key, value)| (*key, value.as_ref()))
+    }
+
+    /// Tangent space dimension of every key
+    pub fn dims(&self) -> BTreeMap<KeyType, usize> {
+        self.values
+            .iter()
+            .map(|(key, value)| (*key, value.dim()))
+            .collect()
+    }
+
+    /// Total tangent space dimension
+    pub fn dim(&self) -> usize {
+        self.values.values().map(|value| value.dim()).sum()
+    }
+
+    /// Zero tangent vectors for all keys
+    pub fn zero_vectors(&self) -> VectorValues {
+        VectorValues::zero(&self.dims())
+    }
+
+    /// Retracts every value by its vector in `delta`, leaving keys without one unchanged.
+    ///
+    /// Panics if a vector in `delta` has the wrong dimension.
+    pub fn retract(&self, delta: &VectorValues) -> Values {
+        Values {
+            values: self
+                .values
+                .iter()
+                .map(|(key, value)| match delta.get(*key) {
+                    Some(v) => (*key, value.retract_dyn(v)),
+                    None => (*key, value.clone_dyn()),
+                })
+                .collect(),
+        }
+    }
+
+    /// The tangent vectors that retract these values to `other`, which must hold values of the
+    /// same types for all keys
+    pub fn local_coordinates(&self, other: &Values) -> Result<VectorValues, ValuesError> {
+        let mut delta = VectorValues::new();
+        for (key, value) in self.values.iter() {
+            let other = other.at_dyn(*key)?;
+            let v = value
+                .local_dyn(other)
+                .ok_or_else(|| ValuesError::TypeMismatch {
+                    key: *key,
+                    requested: value.type_name(),
+                    stored: other.type_name(),
+                })?;
+            delta.insert(*key, v);
+        }
+        Ok(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::se3::SE3;
+    use crate::geometry::so3::SO3;
+    use nalgebra::{DVector, Vector3};
+
+    fn values() -> Values {
+        let mut values = Values::new();
+        values
+            .insert(0, SO3::from_scaled_axis(Vector3::new(0.1, -0.2, 0.3)))
+            .unwrap();
+        values.insert(1, Vector3::new(1.0, 2.0, 3.0)).unwrap();
+        values.insert(2, SE3::<f64>::identity()).unwrap();
+        values
+    }
+
+    #[test]
+    fn typed_access() {
+        let mut values = values();
+        assert_eq!(values.len(), 3);
+        assert_eq!(
+            values.at::<Vector3<f64>>(1),
+            Ok(&Vector3::new(1.0, 2.0, 3.0))
+        );
+
+        let error = values.at::<SE3<f64>>(0).unwrap_err();
+        assert!(matches!(error, ValuesError::TypeMismatch { key: 0, .. }));
+        assert!(error.to_string().contains("Rotation"));
+        assert_eq!(values.at::<SO3<f64>>(5), Err(ValuesError::KeyMissing(5)));
+
+        assert_eq!(
+            values.insert(1, Vector3::<f64>::zeros()),
+            Err(ValuesError::KeyExists(1))
+        );
+        values.update(1, Vector3::new(0.0, 0.0, 1.0)).unwrap();
+        assert_eq!(values.at::<Vector3<f64>>(1).unwrap().z, 1.0);
+        assert!(values.update(1, SO3::<f64>::identity()).is_err());
+
+        values.erase(1).unwrap();
+        assert!(!values.exists(1));
+        assert!(values.erase(1).is_err());
+    }
+
+    #[test]
+    fn dimensions() {
+        let values = values();
+        assert_eq!(values.dim(), 12);
+        assert_eq!(
+            values.dims().into_iter().collect::<Vec<_>>(),
+            vec![(0, 3), (1, 3), (2, 6)]
+        );
+        assert_eq!(values.zero_vectors().dim(), 12);
+    }
+
+    #[test]
+    fn retract_and_local_coordinates() {
+        let values = values();
+
+        let mut delta = VectorValues::new();
+        delta.insert(0, DVector::from_vec(vec![0.01, 0.02, -0.03]));
+        delta.insert(2, DVector::from_vec(vec![0.1, 0.0, 0.2, 1.0, -1.0, 0.5]));
+        let retracted = values.retract(&delta);
+
+        // Keys without a delta are unchanged
+        assert_eq!(
+            retracted.at::<Vector3<f64>>(1),
+            values.at::<Vector3<f64>>(1)
+        );
+
+        let local = values.local_coordinates(&retracted).unwrap();
+        assert_relative_eq!(local.at(0), delta.at(0), epsilon = 1e-9);
+        assert_relative_eq!(local.at(1), &DVector::zeros(3), epsilon = 1e-12);
+        assert_relative_eq!(local.at(2), delta.at(2), epsilon = 1e-9);
+
+        let mut other = values.clone();
+        other.erase(0).unwrap();
+        other.insert(0, Vector3::<f64>::zeros()).unwrap();
+        assert!(matches!(
+            values.local_coordinates(&other),
+            Err(ValuesError::TypeMismatch { key: 0, .. })
+        ));
+    }
+}